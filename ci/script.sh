@@ -27,6 +27,7 @@ case $TARGET in
     cargo build --target $TARGET --verbose
     cargo run --target $TARGET
     cargo test --target $TARGET
+    cargo test --target $TARGET --features pdf
     ;;
 esac
 
@@ -19,6 +19,11 @@ pub fn print_warning(msg: &str) {
               msg);
 }
 
+/// Prints an info message
+pub fn print_info(msg: &str) {
+    eprintln!("{}", style(msg).cyan());
+}
+
 /// Prints an error
 pub fn print_error(s: &str) {
     eprint!("{}", style(ERROR).red());
@@ -167,6 +172,11 @@ pub fn create_matches<'a>() -> (ArgMatches<'a>, String, String) {
         static ref PRINT_TEMPLATE: String = lformat!("Prints the default content of a template");
         static ref BOOK: String = lformat!("File containing the book configuration file, or a Markdown file when called with --single");
         static ref STATS: String = lformat!("Print some project statistics");
+        static ref CHECK: String = lformat!("Check the book configuration, chapters, templates, images and links without rendering");
+        static ref EDITION: String = lformat!("Load an edition of a project file instead of a book configuration file");
+        static ref ALL_EDITIONS: String = lformat!("Render all the editions of a project file");
+        static ref PRINT_MANIFEST: String = lformat!("Prints a YAML manifest equivalent to a book configuration file");
+        static ref KEEP_GOING: String = lformat!("Keep going after errors and print a summary at the end");
         static ref TEMPLATE: String = lformat!("\
 {{bin}} {{version}} by {{author}}
 {{about}}
@@ -212,6 +222,10 @@ ARGS:
                                "html",
                                "tex",
                                "odt",
+                               "docx",
+                               "typst",
+                               "txt",
+                               "md",
                                "html.dir",
                                "proofread.html",
                                "proofread.html.dir",
@@ -228,6 +242,19 @@ ARGS:
              .help(LANG.as_str()))
         .arg(Arg::from_usage("--print-template [TEMPLATE]").help(PRINT_TEMPLATE.as_str()))
         .arg(Arg::from_usage("--stats -S").help(STATS.as_str()))
+        .arg(Arg::from_usage("-k, --keep-going").help(KEEP_GOING.as_str()))
+        .arg(Arg::from_usage("-E, --edition [NAME]")
+             .help(EDITION.as_str())
+             .conflicts_with("single"))
+        .arg(Arg::from_usage("--all-editions")
+             .help(ALL_EDITIONS.as_str())
+             .conflicts_with_all(&["single", "edition", "to", "stats"]))
+        .arg(Arg::from_usage("--print-manifest")
+             .help(PRINT_MANIFEST.as_str())
+             .conflicts_with_all(&["single", "edition", "all-editions"]))
+        .arg(Arg::from_usage("--check")
+             .help(CHECK.as_str())
+             .conflicts_with("to"))
         .arg(Arg::with_name("BOOK")
             .index(1)
             .help(BOOK.as_str()))
@@ -17,7 +17,8 @@
 
 use helpers::*;
 
-use crowbook::{Result, Book, BookOptions};
+use crowbook::{Result, Book, BookOptions, Project, Error, Source};
+use crowbook::book_to_manifest;
 use crowbook_intl_runtime::set_lang;
 use crowbook::Stats;
 use tempdir::TempDir;
@@ -30,7 +31,9 @@ use std::fs::File;
 use simplelog::{Config, TermLogger, LogLevel, LogLevelFilter, SimpleLogger, WriteLogger};
 
 /// Render a book to specific format
-fn render_format(book: &mut Book, matches: &ArgMatches, format: &str) {
+fn render_format(book: &mut Book, matches: &ArgMatches, format: &str) -> Result<()> {
+    book.warn_links();
+
     let mut key = String::from("output.");
     key.push_str(format);
 
@@ -47,18 +50,13 @@ fn render_format(book: &mut Book, matches: &ArgMatches, format: &str) {
 
     let res = book.options.get_path(&key);
 
-    let result = match(file, res, stdout) {
+    match(file, res, stdout) {
         (Some(file), _, _) |
         (None, Ok(file), false) => book.render_format_to_file(format, file),
 
         (None, Err(_), _) |
         (None, _, true)
         => book.render_format_to(format, &mut io::stdout()),
-    };
-    
-    match result {
-        Err(err) => print_error(&format!("{}", err)),
-        Ok(_) => {}
     }
 }
 
@@ -89,7 +87,7 @@ pub fn try_main() -> Result<()> {
         exit(0);
     }
 
-    if matches.is_present("no-fancy") {
+    if matches.is_present("no-fancy") || matches.is_present("all-editions") {
         fancy_ui = false;
     }
 
@@ -137,6 +135,15 @@ pub fn try_main() -> Result<()> {
     // ok to unwrap since clap checks it's there
     let s = matches.value_of("BOOK").unwrap();
 
+    if matches.is_present("print-manifest") {
+        let mut content = String::new();
+        File::open(s)
+            .and_then(|mut f| f.read_to_string(&mut content))
+            .map_err(|_| Error::file_not_found(Source::empty(), lformat!("book"), s.to_owned()))?;
+        println!("{}", book_to_manifest(&content)?);
+        exit(0);
+    }
+
     // Initalize logger
     let mut log_config = Config::default();
     log_config.target = None;
@@ -169,25 +176,53 @@ pub fn try_main() -> Result<()> {
         }
     }
 
-    {
+    let check = matches.is_present("check");
+    let keep_going = matches.is_present("keep-going") || check;
+    let errors = if matches.is_present("all-editions") {
+        let project = Project::load_file(s)?;
+        let mut books = vec![];
+        for edition in project.editions() {
+            let mut book = Book::new();
+            book.set_keep_going(keep_going);
+            book.set_options(&get_book_options(&matches));
+            project.load_edition(&mut book, edition)?;
+            set_book_options(&mut book, &matches);
+            books.push(book);
+        }
+
+        if check {
+            for book in &books {
+                book.check()?;
+            }
+        } else {
+            Project::render_all(&books);
+        }
+        books.iter_mut()
+            .flat_map(|book| book.take_errors())
+            .collect()
+    } else {
         let mut book = Book::new();
         if fancy_ui {
             book.add_progress_bar();
         }
+        book.set_keep_going(keep_going);
         book.set_options(&get_book_options(&matches));
         
         {
-            let res = if matches.is_present("single") {
+            let res = if let Some(edition) = matches.value_of("edition") {
+                Project::load_file(s)
+                    .and_then(|project| project.load_edition(&mut book, edition))
+            } else if matches.is_present("single") {
                 if s != "-" {
                     book.load_markdown_file(s)
                 } else {
                     book.read_markdown_config(io::stdin())
-                }
+                }.map(|_| ())
             } else if s != "-" {
-                book.load_file(s)
+                book.load_file(s).map(|_| ())
             } else {
-                book.read_config(io::stdin())
-            }.map(|_| ());
+                book.read_config(io::stdin()).map(|_| ())
+            };
             
             match res {
                 Ok(..) => {},
@@ -206,12 +241,21 @@ pub fn try_main() -> Result<()> {
             exit(0);
         }
         
-        if let Some(format) = matches.value_of("to") {
-            render_format(&mut book, &matches, format);
+        if check {
+            book.check()?;
+        } else if let Some(format) = matches.value_of("to") {
+            if let Err(err) = render_format(&mut book, &matches, format) {
+                if keep_going {
+                    book.keep_going_or(err)?;
+                } else {
+                    print_error(&format!("{}", err));
+                }
+            }
         } else {
             book.render_all();
         }
-    }
+        book.take_errors()
+    };
     if fancy_ui {
         let mut errors = String::new();
         let mut file = File::open(error_dir.path().join(error_path)).unwrap();
@@ -233,7 +277,17 @@ pub fn try_main() -> Result<()> {
         }
     }
 
-    
+    if !errors.is_empty() {
+        print_warning(&lformat!("{n} error(s) occurred while building the book:",
+                                n = errors.len()));
+        for err in &errors {
+            print_error(&format!("{}", err));
+        }
+        exit(1);
+    } else if check {
+        print_info(&lformat!("No problem found in {book}", book = s));
+    }
+
     Ok(())
 }
 
@@ -25,19 +25,26 @@ use html_single::{HtmlSingle, ProofHtmlSingle};
 use html_dir::{HtmlDir, ProofHtmlDir};
 use html_if::{HtmlIf};
 use latex::{Latex, ProofLatex, Pdf, ProofPdf};
+use typst::Typst;
 use odt::{Odt};
-use templates::{epub, html, epub3, latex, html_dir, highlight, html_single, html_if};
+use docx::Docx;
+use txt::Txt;
+use markdown::Markdown;
+use templates::{epub, html, epub3, latex, typst, html_dir, highlight, html_single, html_if};
 use number::Number;
 use resource_handler::ResourceHandler;
 use lang;
+use lang_tag::LangTag;
 use misc;
 use book_renderer::BookRenderer;
 use chapter::Chapter;
+use manifest::{self, ChapterEntry};
+use contributor::{self, Contributor};
 use token::Token;
 use text_view::view_as_text;
 
 use std::thread;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::mem;
 
 use indicatif::{ProgressBar, MultiProgress};
@@ -130,7 +137,7 @@ impl fmt::Display for HeaderData {
 ///
 /// It has the tasks of loading a configuration file, loading chapters
 /// and using `Parser`to parse them, and then calling various renderers
-/// (`HtmlRendrer`, `LatexRenderer`, `EpubRenderer` and/or `OdtRenderer`)
+/// (`HtmlRendrer`, `LatexRenderer`, `EpubRenderer`, `OdtRenderer` and/or `DocxRenderer`)
 /// to convert the AST into documents.
 ///
 /// # Examples
@@ -176,6 +183,9 @@ pub struct Book {
     grammalecte: Option<GrammalecteChecker>,
     detector: Option<RepetitionDetector>,
     formats: HashMap<&'static str, (String, Box<BookRenderer>)>,
+    keep_going: bool,
+    errors: Mutex<Vec<Error>>,
+    edition: Option<String>,
 
     #[doc(hidden)]
     pub multibar: Option<Arc<MultiProgress>>,
@@ -203,6 +213,9 @@ impl Book {
             detector: None,
             formats: HashMap::new(),
             features: Features::new(),
+            keep_going: false,
+            errors: Mutex::new(vec![]),
+            edition: None,
             multibar: None,
             mainbar: None,
             secondbar: None,
@@ -216,8 +229,12 @@ impl Book {
             .add_format("proofread.tex", lformat!("LaTeX (proofreading)"), Box::new(ProofLatex{}))
             .add_format("pdf", lformat!("PDF"), Box::new(Pdf{}))
             .add_format("proofread.pdf", lformat!("PDF (proofreading)"), Box::new(ProofPdf{}))
+            .add_format("typst", lformat!("Typst"), Box::new(Typst{}))
             .add_format("epub", lformat!("EPUB"), Box::new(Epub{}))
             .add_format("odt", lformat!("ODT"), Box::new(Odt{}))
+            .add_format("docx", lformat!("DOCX"), Box::new(Docx{}))
+            .add_format("txt", lformat!("Plain text"), Box::new(Txt{}))
+            .add_format("md", lformat!("Markdown"), Box::new(Markdown{}))
             .add_format("html.if", lformat!("HTML (interactive fiction)"), Box::new(HtmlIf{}));
         book
     }
@@ -236,6 +253,60 @@ impl Book {
         self.private_add_progress_bar();
     }
 
+    /// Sets "keep going" mode.
+    ///
+    /// When it is activated, errors in a chapter (when loading the book) or
+    /// in a format (when rendering it) are recorded instead of stopping at
+    /// the first one, and the book proceeds with the remaining chapters and
+    /// formats. Recorded errors can then be retrieved with `take_errors`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crowbook::Book;
+    /// let mut book = Book::new();
+    /// book.set_keep_going(true);
+    /// book.read_config("+ foo.md\n+ bar.md".as_bytes()).unwrap();
+    /// // foo.md and bar.md don't exist
+    /// assert_eq!(book.take_errors().len(), 2);
+    /// ```
+    pub fn set_keep_going(&mut self, keep_going: bool) -> &mut Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Returns true if some errors were recorded in "keep going" mode
+    pub fn has_errors(&self) -> bool {
+        !self.errors.lock().unwrap().is_empty()
+    }
+
+    /// Returns the errors that were recorded in "keep going" mode, and
+    /// clears them.
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        mem::replace(self.errors.get_mut().unwrap(), vec![])
+    }
+
+    /// Records an error if "keep going" mode is set, or returns it else.
+    #[doc(hidden)]
+    pub fn keep_going_or(&self, err: Error) -> Result<()> {
+        if self.keep_going {
+            self.errors.lock().unwrap().push(err);
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Sets the name of the edition of a project this book corresponds to.
+    ///
+    /// This is used to infer output file names when they are set to `auto`,
+    /// so different editions don't overwrite each other.
+    #[doc(hidden)]
+    pub fn set_edition<S: Into<String>>(&mut self, edition: S) -> &mut Self {
+        self.edition = Some(edition.into());
+        self
+    }
+
     /// Register a format that can be rendered.
     ///
     /// The renderer for this format must implement the `BookRenderer` trait.
@@ -326,7 +397,15 @@ impl Book {
             self.options.root = self.root.clone();
         }
 
-        let result = self.read_config(&f);
+        let is_manifest = match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => true,
+            _ => false,
+        };
+        let result = if is_manifest {
+            self.read_manifest(&f)
+        } else {
+            self.read_config(&f)
+        };
         match result {
             Ok(book) => Ok(book),
             Err(err) => {
@@ -430,11 +509,7 @@ impl Book {
             Ok(mut docs) => {
                 if docs.len() == 1 && docs[0].as_hash().is_some() {
                     if let Yaml::Hash(hash) = docs.pop().unwrap() {
-                        for (key, value) in hash {
-                            if let Err(err) = self.options.set_yaml(key, value) {
-                                error!("{}", err);
-                            };
-                        }
+                        self.set_yaml_options(hash);
                     } else {
                         unreachable!();
                     }
@@ -448,6 +523,119 @@ impl Book {
         Ok(self)
     }
         
+    /// Reads a book from a YAML manifest.
+    ///
+    /// This is an alternative to the `.book` syntax where the whole file is
+    /// YAML: the chapter list is given in the `chapters` key and all other
+    /// keys are book options. `load_file` uses this method for files with a
+    /// `.yaml` or `.yml` extension.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crowbook::Book;
+    /// let content = "\
+    /// author: Foo
+    /// title: Bar
+    /// chapters:
+    ///   - file: intro.md
+    ///     hidden: true
+    ///   - part: First part
+    ///   - chapter_01.md
+    ///   - file: annex.md
+    ///     number: false";
+    ///
+    /// let mut book = Book::new();
+    /// book.read_manifest(content.as_bytes()); // no unwraping as the chapters don't exist
+    /// ```
+    pub fn read_manifest<R: Read>(&mut self, mut source: R) -> Result<&mut Book> {
+        if let Some(ref bar) = self.mainbar {
+            bar.set_message(&lformat!("setting options"));
+            bar.tick();
+        }
+
+        let mut s = String::new();
+        source.read_to_string(&mut s)
+            .map_err(|err| Error::config_parser(Source::empty(),
+                                                lformat!("could not read source: {error}",
+                                                         error = err)))?;
+        let docs = YamlLoader::load_from_str(&s)
+            .map_err(|err| Error::config_parser(&self.source,
+                                                lformat!("manifest is not valid YAML: {error}",
+                                                         error = err)))?;
+        let hash = match docs.into_iter().next() {
+            Some(Yaml::Hash(hash)) => hash,
+            _ => {
+                return Err(Error::config_parser(&self.source,
+                                                lformat!("manifest is not a valid hashmap")))
+            }
+        };
+
+        let mut chapters = vec![];
+        let mut options = vec![];
+        for (key, value) in hash {
+            if key.as_str() == Some("chapters") {
+                match value {
+                    Yaml::Array(array) => chapters = array,
+                    Yaml::Null => (),
+                    _ => {
+                        return Err(Error::config_parser(&self.source,
+                                                        lformat!("expected a list as value for \
+                                                                  key 'chapters'")))
+                    },
+                }
+            } else {
+                options.push((key, value));
+            }
+        }
+        self.options.source = self.source.clone();
+        self.set_yaml_options(options);
+
+        // Update grammar checker according to options (proofread.*)
+        self.init_checker();
+
+        if let Some(ref bar) = self.mainbar {
+            bar.set_message(&lformat!("parsing chapters"));
+            bar.tick();
+        }
+
+        self.add_second_bar(&lformat!("Processing..."), chapters.len() as u64);
+        for chapter in &chapters {
+            self.inc_second_bar();
+            let res = manifest::parse_chapter_yaml(&self.source, chapter)
+                .and_then(|entry| self.add_chapter_entry(entry));
+            if let Err(err) = res {
+                self.keep_going_or(err)?;
+            }
+        }
+        self.finish_second_bar();
+
+        self.set_chapter_template()?;
+        Ok(self)
+    }
+
+    /// Sets a list of options from YAML keys and values.
+    ///
+    /// Errors are logged (or recorded in "keep going" mode) instead of
+    /// aborting, so one wrong option doesn't prevent setting the others.
+    #[doc(hidden)]
+    pub fn set_yaml_options<I>(&mut self, options: I) -> &mut Book
+        where I: IntoIterator<Item = (Yaml, Yaml)>
+    {
+        for (key, value) in options {
+            if let Err(err) = self.options.set_yaml(key, value) {
+                if self.keep_going {
+                    self.errors.lock().unwrap().push(err);
+                } else {
+                    error!("{}", err);
+                }
+            };
+        }
+        // set cleaner according to lang and autoclean settings
+        self.update_cleaner();
+        self
+    }
+
     /// Reads a book configuration from a `Read`able source.
     ///
     /// # Book configuration
@@ -478,18 +666,6 @@ impl Book {
     /// book.read_config(content.as_bytes()); // no unwraping as `intro.md` and `chapter_01.md` don't exist
     /// ```
     pub fn read_config<R: Read>(&mut self, mut source: R) -> Result<&mut Book> {
-        fn get_filename<'a>(source: &Source, s: &'a str) -> Result<&'a str> {
-            let words: Vec<&str> = (&s[1..]).split_whitespace().collect();
-            if words.len() > 1 {
-                return Err(Error::config_parser(source,
-                                                lformat!("chapter filenames must not contain \
-                                                          whitespace")));
-            } else if words.len() < 1 {
-                return Err(Error::config_parser(source, lformat!("no chapter name specified")));
-            }
-            Ok(words[0])
-        }
-
         if let Some(ref bar) = self.mainbar {
             bar.set_message(&lformat!("setting options"));
             bar.tick();
@@ -515,10 +691,7 @@ impl Book {
 
         loop {
             if let Some(next_line) = lines.peek() {
-                if next_line.starts_with(|c| match c {
-                    '-' | '+' | '!' | '@'  => true,
-                    _ => c.is_digit(10),
-                }) {
+                if manifest::is_chapter_line(next_line) {
                     break;
                 }
             } else {
@@ -574,7 +747,9 @@ impl Book {
                 }
             }
         }
-        self.set_options_from_yaml(&yaml)?;
+        if !yaml.trim().is_empty() {
+            self.set_options_from_yaml(&yaml)?;
+        }
 
         // Update cleaner according to options (autoclean/lang)
         self.update_cleaner();
@@ -594,92 +769,8 @@ impl Book {
             self.inc_second_bar();
             line_number += 1;
             self.source.set_line(line_number);
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            } if line.starts_with("--") {
-                // Subchapter
-                let mut level = 0;
-                for b in line.bytes() {
-                    if b == b'-' {
-                        level += 1;
-                    } else {
-                        break;
-                    }
-                }
-                assert!(level > 1);
-                level -= 1;
-                let file = get_filename(&self.source, &line[level..])?;
-                self.add_subchapter(level as i32, file)?;
-            } else if line.starts_with('-') {
-                // unnumbered chapter
-                let file = get_filename(&self.source, line)?;
-                self.add_chapter(Number::Unnumbered, file)?;
-            } else if line.starts_with('+') {
-                // numbered chapter
-                let file = get_filename(&self.source, line)?;
-                self.add_chapter(Number::Default, file)?;
-            } else if line.starts_with('!') {
-                // hidden chapter
-                let file = get_filename(&self.source, line)?;
-                self.add_chapter(Number::Hidden, file)?;
-            } else if line.starts_with(|c: char| c.is_digit(10)) {
-                // chapter with specific number
-                let parts: Vec<_> = line.splitn(2, |c: char| c == '.' || c == ':' || c == '+')
-                    .collect();
-                if parts.len() != 2 {
-                    return Err(Error::config_parser(&self.source,
-                                                    lformat!("ill-formatted line specifying \
-                                                              chapter number")));
-                }
-                let file = get_filename(&self.source, parts[1])?;
-                let number = parts[0].parse::<i32>()
-                    .map_err(|err| {
-                        Error::config_parser(&self.source,
-                                             lformat!("error parsing chapter number: {error}",
-                                             error = err))})?;
-                self.add_chapter(Number::Specified(number), file)?;
-            } else if line.starts_with('@') {
-                /* Part */
-                let subline = &line[1..];
-                if subline.starts_with(|c: char| c.is_whitespace()) {
-                    let subline = subline.trim();
-                    let ast = Parser::from(&self)
-                        .parse_inline(subline)?;
-                    let ast = vec!(Token::Header(1, ast));
-                    self.chapters.push(Chapter::new(Number::DefaultPart, String::new(), ast));
-                } else if subline.starts_with('+') {
-                    /* Numbered part */
-                    let file = get_filename(&self.source, subline)?;
-                    self.add_chapter(Number::DefaultPart, file)?;
-                } else if subline.starts_with('-') {
-                    /* Unnumbered part */
-                    let file = get_filename(&self.source, line)?;
-                    self.add_chapter(Number::UnnumberedPart, file)?;
-                } else if subline.starts_with(|c: char| c.is_digit(10)) {
-                    /* Specified  part*/
-                    let parts: Vec<_> = subline.splitn(2, |c: char| c == '.' || c == ':' || c == '+')
-                        .collect();
-                    if parts.len() != 2 {
-                        return Err(Error::config_parser(&self.source,
-                                                        lformat!("ill-formatted line specifying \
-                                                                  part number")));
-                    }
-                    let file = get_filename(&self.source, parts[1])?;
-                    let number = parts[0].parse::<i32>()
-                        .map_err(|err| {
-                            Error::config_parser(&self.source,
-                                                 lformat!("error parsing part number: {error}",
-                                                          error = err))})?;
-                    self.add_chapter(Number::SpecifiedPart(number), file)?;
-                } else {
-                    return Err(Error::config_parser(&self.source,
-                                                    lformat!("found invalid part definition in the chapter list")));
-                }
-            } else {
-                return Err(Error::config_parser(&self.source,
-                                                lformat!("found invalid chapter definition in \
-                                                          the chapter list")));
+            if let Err(err) = self.read_chapter_line(line) {
+                self.keep_going_or(err)?;
             }
         }
 
@@ -690,6 +781,34 @@ impl Book {
         Ok(self)
     }
 
+    /// Reads a line of the chapter list of a book configuration file
+    #[doc(hidden)]
+    pub fn read_chapter_line(&mut self, line: &str) -> Result<()> {
+        if let Some(entry) = manifest::parse_chapter_line(&self.source, line)? {
+            self.add_chapter_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    /// Adds an entry of the chapter list to the book
+    fn add_chapter_entry(&mut self, entry: ChapterEntry) -> Result<()> {
+        match entry {
+            ChapterEntry::Chapter(number, file) => {
+                self.add_chapter(number, &file)?;
+            },
+            ChapterEntry::Subchapter(level, file) => {
+                self.add_subchapter(level, &file)?;
+            },
+            ChapterEntry::PartTitle(title) => {
+                let ast = Parser::from(&self)
+                    .parse_inline(&title)?;
+                let ast = vec!(Token::Header(1, ast));
+                self.chapters.push(Chapter::new(Number::DefaultPart, String::new(), ast));
+            },
+        }
+        Ok(())
+    }
+
     /// Determine whether proofreading is activated or not
     fn is_proofread(&self) -> bool {
         self.options.get_bool("proofread").unwrap() &&
@@ -746,7 +865,15 @@ impl Book {
                 if let Some(bar) = bar {
                     self.finish_spinner_error(bar, format, &format!("{}", err));
                 }
-                error!("{}", lformat!("Error rendering {name}: {error}", name = format, error = err));
+                let err = Error::default(Source::empty(),
+                                         lformat!("Error rendering {name}: {error}",
+                                                  name = format,
+                                                  error = err));
+                if self.keep_going {
+                    self.errors.lock().unwrap().push(err);
+                } else {
+                    error!("{}", err);
+                }
             }
         }
     }
@@ -768,6 +895,9 @@ impl Book {
 
     /// Generates output files acccording to book options.
     ///
+    /// Broken links and missing images are first displayed as warnings
+    /// (see `check_links`).
+    ///
     /// # Example
     ///
     /// ```
@@ -788,6 +918,8 @@ impl Book {
     ///       .render_all(); // renders foo.tex in /tmp
     /// ```
     pub fn render_all(&self) -> () {
+        self.warn_links();
+
         let mut keys: Vec<_> = self.formats
             .keys()
             .filter(|fmt| {
@@ -927,7 +1059,11 @@ impl Book {
                         .file
                         .as_ref()
                         .and_then(|f| Path::new(f).file_stem()) {
-                        s.to_string_lossy().into_owned()
+                        if let Some(ref edition) = self.edition {
+                            format!("{}-{}", s.to_string_lossy(), edition)
+                        } else {
+                            s.to_string_lossy().into_owned()
+                        }
                     } else {
                         return Err(Error::default(&self.source, lformat!("output to {format} set to auto but can't find book file name to infer it",
                                                                      format = description)));
@@ -992,7 +1128,7 @@ impl Book {
         }
         let mut parser = Parser::from(self);
         parser.set_source_file(file);
-        let mut tokens = parser.parse(&content)?;
+        let (mut tokens, mut lines) = parser.parse_with_lines(&content)?;
         self.features = self.features | parser.features();
 
         // transform the AST to make local links and images relative to `book` directory
@@ -1031,7 +1167,11 @@ impl Book {
         ResourceHandler::add_offset(link_offset.as_ref(), image_offset.as_ref(), &mut tokens);
 
         // Add a title if there is none in the chapter
+        let len = tokens.len();
         misc::insert_title(&mut tokens);
+        if tokens.len() > len {
+            lines.insert(0, 1);
+        }
 
         // If one of the renderers requires it, perform grammarcheck
         if cfg!(feature = "proofread") && self.is_proofread() {
@@ -1082,7 +1222,9 @@ impl Book {
         if let Some(ref bar) = self.secondbar {
             bar.set_message("");
         }
-        self.chapters.push(Chapter::new(number, file, tokens));
+        let mut chapter = Chapter::new(number, file, tokens);
+        chapter.lines = lines;
+        self.chapters.push(chapter);
 
         Ok(self)
     }
@@ -1188,7 +1330,13 @@ impl Book {
     pub fn get_template(&self, template: &str) -> Result<Cow<'static, str>> {
         let option = self.options.get_path(template);
         let fallback = match template {
-            "epub.css" => epub::CSS,
+            "epub.css" => {
+                if self.options.get_str("epub.profile")? == "kindle" {
+                    epub::KINDLE_CSS
+                } else {
+                    epub::CSS
+                }
+            }
             "epub.chapter.xhtml" => {
                 if self.options.get_i32("epub.version")? == 3 {
                     epub3::TEMPLATE
@@ -1208,6 +1356,7 @@ impl Book {
             "html.if.js" => html_if::JS,
             "html.if.new_game" => html_if::NEW_GAME,
             "tex.template" => latex::TEMPLATE,
+            "typst.template" => typst::TEMPLATE,
             _ => {
                 return Err(Error::config_parser(&self.source,
                                                 lformat!("invalid template '{template}'",
@@ -1337,6 +1486,42 @@ impl Book {
         self.get_header(Header::Part, n, title, f)
     }
 
+    /// Returns true if the book is written from right to left.
+    ///
+    /// This is set by `rendering.direction` or, if it is `auto`, deduced
+    /// from `lang`.
+    pub fn is_rtl(&self) -> bool {
+        match self.options.get_str("rendering.direction").unwrap() {
+            "rtl" => true,
+            "ltr" => false,
+            _ => {
+                LangTag::parse(self.options.get_str("lang").unwrap())
+                    .map_or(false, |tag| tag.is_rtl())
+            }
+        }
+    }
+
+    /// Returns the authors of the book, set either with `authors` or, if it
+    /// isn't, with `author`.
+    pub fn authors(&self) -> Vec<Contributor> {
+        if let Ok(authors) = self.options.get_str_vec("authors") {
+            authors.iter()
+                .map(|s| Contributor::parse_author(s))
+                .collect()
+        } else {
+            match self.options.get_str("author") {
+                Ok(author) if !author.is_empty() => {
+                    vec![Contributor {
+                             name: author.to_owned(),
+                             role: String::from("aut"),
+                             file_as: None,
+                         }]
+                },
+                _ => vec![],
+            }
+        }
+    }
+
     /// Returns a `MapBuilder` (used by `Mustache` for templating), to be used (and completed)
     /// by renderers. It fills it with the metadata options.
     ///
@@ -1353,6 +1538,9 @@ impl Book {
         mapbuilder =
             mapbuilder.insert_bool(&format!("lang_{}", self.options.get_str("lang").unwrap()),
                                    true);
+        let rtl = self.is_rtl();
+        mapbuilder = mapbuilder.insert_str("dir", if rtl { "rtl" } else { "ltr" })
+            .insert_bool("rtl", rtl);
 
         // Add metadata to mapbuilder
         for key in self.options.get_metadata() {
@@ -1389,6 +1577,35 @@ impl Book {
             }
         }
 
+        // Add the list of authors; if it is set with `authors`, `author` is
+        // also replaced by their joined names
+        let authors = self.authors();
+        let mut names = vec![];
+        for author in &authors {
+            names.push(f(&author.name)?);
+        }
+        let len = authors.len();
+        mapbuilder = mapbuilder.insert_vec("authors", |mut builder| {
+            for (i, (author, name)) in authors.iter().zip(names.iter()).enumerate() {
+                builder = builder.push_map(|map| {
+                    map.insert_str("name", name.as_str())
+                        .insert_str("name_raw", author.name.as_str())
+                        .insert_str("file_as", author.file_as())
+                        .insert_str("role", author.role.as_str())
+                        .insert_bool("first", i == 0)
+                        .insert_bool("last", i + 1 == len)
+                });
+            }
+            builder
+        });
+        if self.options.get_str_vec("authors").is_ok() {
+            let and = lang::get_str(self.options.get_str("lang").unwrap(), "and");
+            let raw: Vec<String> = authors.iter().map(|a| a.name.clone()).collect();
+            mapbuilder = mapbuilder.insert_str("author", contributor::join_names(&names, &and))
+                .insert_str("author_raw", contributor::join_names(&raw, &and))
+                .insert_bool("has_author", !authors.is_empty());
+        }
+
         // Add localization strings
         let hash = lang::get_hash(self.options.get_str("lang").unwrap());
         for (key, value) in hash {
@@ -1488,6 +1705,12 @@ impl Book {
                         // Block was invalid, so add it to markdown content
                         new_content.push_str(&yaml_block);
                         new_content.push_str("\n");
+                    } else {
+                        // Replace the block (and its delimiters) by empty
+                        // lines, so line numbers in the chapter are kept
+                        for _ in 0..yaml_block.lines().count() + 2 {
+                            new_content.push_str("\n");
+                        }
                     }
                 } else if line.is_empty() {
                     previous_empty = true;
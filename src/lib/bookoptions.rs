@@ -1,6 +1,8 @@
 use error::{Error, Result, Source};
 use bookoption::BookOption;
 use book::Book;
+use misc;
+use lang_tag::LangTag;
 
 use yaml_rust::{Yaml, YamlLoader};
 use std::collections::HashMap;
@@ -12,6 +14,7 @@ lazy_static! {
     static ref OPTIONS: String = format!("\
 # {metadata}
 author:meta:\"\"                    # {author}
+authors:strvec                      # {authors}
 title:meta:\"\"                     # {title}
 lang:meta:en                        # {lang}
 subject:meta                        # {subject}
@@ -23,6 +26,14 @@ subtitle:meta                       # {subtitle}
 license:meta                        # {license}
 version:meta                        # {version}
 date:meta                           # {date}
+identifier:str                      # {identifier}
+publisher:meta                      # {publisher}
+rights:meta                         # {rights}
+date.published:str                  # {date_published}
+date.modified:str                   # {date_modified}
+contributors:strvec                 # {contributors}
+series:meta                         # {series}
+series.index:str                    # {series_index}
 
 # {output_opt}
 output:strvec                       # {output}
@@ -32,6 +43,10 @@ output.html.dir:path                # {output_html_dir}
 output.tex:path                     # {output_tex}
 output.pdf:path                     # {output_pdf}
 output.odt:path                     # {output_odt}
+output.docx:path                    # {output_docx}
+output.typst:path                   # {output_typst}
+output.txt:path                     # {output_txt}
+output.md:path                      # {output_md}
 output.html.if:path                 # {output_if}
 output.base_path:path:\"\"            # {output_base_path}
 
@@ -42,6 +57,7 @@ rendering.initials:bool:false                                        # {renderin
 rendering.inline_toc:bool:false                                      # {inline_toc}
 rendering.inline_toc.name:str:\"{{{{{{loc_toc}}}}}}\"                        # {toc_name}
 rendering.num_depth:int:1                                            # {num_depth}
+rendering.direction:str:auto                                         # {direction}
 rendering.chapter:str                                                # {chapter}
 rendering.part:str                                                   # {part}
 rendering.chapter.roman_numerals:bool:false                                  # {roman_numerals_chapters}
@@ -91,30 +107,83 @@ html.if.new_game:tpl                # {if_new_game}
 
 # {epub_opt}
 epub.version:int:2                  # {epub_ver}
+epub.profile:str:default            # {epub_profile}
 epub.highlight.theme:str            # {epub_theme}
 epub.css:tpl                        # {epub_css}
 epub.css.add:str                    # {epub_css_add}
 epub.chapter.xhtml:tpl              # {chapter_xhtml}
 epub.toc.extras:bool:true           # {epub_toc}
 epub.escape_nb_spaces:bool:true     # {nb_spaces}
+epub.validate:bool:false            # {epub_validate}
+epub.notes.single_file:bool:false   # {epub_notes_file}
+epub.fonts:strvec                   # {epub_fonts}
+epub.fonts.obfuscate:bool:false     # {epub_fonts_obfuscate}
+epub.page_list:bool:false           # {epub_page_list}
+epub.page_list.chars:int:2000       # {epub_page_chars}
+epub.layout:str:reflowable          # {epub_layout}
+epub.layout.pages:str:image         # {epub_layout_pages}
+epub.layout.width:int:1200          # {epub_layout_width}
+epub.layout.height:int:1600         # {epub_layout_height}
+epub.layout.spread:str:auto         # {epub_layout_spread}
+epub.accessibility:bool:false       # {epub_a11y}
+epub.accessibility.summary:str      # {epub_a11y_summary}
+epub.accessibility.modes:strvec:[textual]             # {epub_a11y_modes}
+epub.accessibility.modes_sufficient:strvec:[textual]  # {epub_a11y_sufficient}
+epub.accessibility.features:strvec:[structuralNavigation, tableOfContents, readingOrder]  # {epub_a11y_features}
+epub.accessibility.hazards:strvec:[none]              # {epub_a11y_hazards}
+epub.accessibility.conforms_to:str  # {epub_a11y_conforms}
+epub.accessibility.certified_by:str # {epub_a11y_certified}
 
 # {tex_opt}
 tex.highlight.theme:str             # {tex_theme}
 tex.links_as_footnotes:bool:true    # {tex_links}
 tex.command:str:xelatex             # {tex_command}
+tex.max_runs:int:4                  # {tex_max_runs}
 tex.template:tpl                    # {tex_tmpl}
 tex.template.add:str                # {tex_tmpl_add}
 tex.class:str:book                  # {tex_class}
+tex.chapter.style:str:default       # {tex_chapter_style}
+tex.header.left:str                 # {tex_header_left}
+tex.header.center:str               # {tex_header_center}
+tex.header.right:str                # {tex_header_right}
+tex.footer.left:str                 # {tex_footer_left}
+tex.footer.center:str               # {tex_footer_center}
+tex.footer.right:str                # {tex_footer_right}
 tex.paper.size:str:a5paper          # {tex_paper_size}
 tex.margin.left:str                 # {tex_margin_left}
 tex.margin.right:str                # {tex_margin_right}
 tex.margin.top:str:\"2cm\"          # {tex_margin_top}
 tex.margin.bottom:str:\"1.5cm\"     # {tex_margin_bottom}
+tex.margin.inside:str               # {tex_margin_inside}
+tex.margin.outside:str              # {tex_margin_outside}
+tex.margin.gutter:str               # {tex_margin_gutter}
+tex.trim.width:str                  # {tex_trim_width}
+tex.trim.height:str                 # {tex_trim_height}
+tex.bleed:str                       # {tex_bleed}
+tex.crop_marks:bool:false           # {tex_crop_marks}
+tex.pdfx:str                        # {tex_pdfx}
+tex.pdfx.color_profile:path         # {tex_pdfx_profile}
+tex.pdfx.output_intent:str          # {tex_pdfx_intent}
 tex.title:bool:true                 # {tex_title}
 tex.font.size:int                   # {tex_font_size}
+tex.font.main:str                   # {tex_font_main}
+tex.font.sans:str                   # {tex_font_sans}
+tex.font.mono:str                   # {tex_font_mono}
+tex.font.cjk:str                    # {tex_font_cjk}
+tex.font.arabic:str                 # {tex_font_arabic}
+tex.font.cyrillic:str               # {tex_font_cyrillic}
 tex.hyperref:bool:true              # {tex_hyperref}
 tex.stdpage:bool:false              # {tex_stdpage}
 
+# {typst_opt}
+pdf.engine:str:latex                # {pdf_engine}
+typst.command:str:typst             # {typst_command}
+typst.template:tpl                  # {typst_tmpl}
+typst.template.add:str              # {typst_tmpl_add}
+
+# {docx_opt}
+docx.reference:path                 # {docx_reference}
+
 
 # {rs_opt}
 resources.files:strvec               # {rs_files}
@@ -139,6 +208,10 @@ crowbook.markdown.superscript:bool:false  # {superscript}
 crowbook.temp_dir:path:             # {tmp_dir}
 crowbook.zip.command:str:zip        # {zip}
 
+# {check_opt}
+check.links.external:bool:false     # {check_external}
+check.links.command:str:curl        # {check_command}
+
 # {prf_opt}
 output.proofread.html:path          # {prf_html}
 output.proofread.html.dir:path      # {prf_html_dir}
@@ -212,14 +285,18 @@ crowbook.verbose:alias                              # {removed}
                                          html_if_opt = lformat!("Interactive fiction HTML options"),
                                          epub_opt = lformat!("EPUB options"),
                                          tex_opt = lformat!("LaTeX options"),
+                                         typst_opt = lformat!("Typst options"),
+                                         docx_opt = lformat!("DOCX options"),
                                          rs_opt = lformat!("Resources option"),
                                          input_opt = lformat!("Input options"),
                                          crowbook_opt = lformat!("Crowbook options"),
+                                         check_opt = lformat!("Checking options (for --check)"),
                                          prf_opt = lformat!("Output options (for proofreading)"),
                                          prf_opt2 = lformat!("Proofreading options (only for output.proofread.* targets)"),
                                          deprecated_opt = lformat!("Deprecated options"),
                                          
                                          author = lformat!("Author of the book"),
+                                         authors = lformat!("List of authors, of the form 'Name [File-as] (role)', e.g. 'Jane Doe [Doe, Jane]'. Overrides author if set"),
                                          title = lformat!("Title of the book"),
                                          lang = lformat!("Language of the book"),
                                          subject = lformat!("Subject of the book (used for EPUB metadata)"),
@@ -230,12 +307,24 @@ crowbook.verbose:alias                              # {removed}
                                          license = lformat!("License of the book. This information will be displayed on PDF documents"),
                                          version = lformat!("Version of the book"),
                                          date = lformat!("Date the book was revised"),
+                                         identifier = lformat!("Unique identifier of the book (ISBN, UUID or URI). If not set, EPUB files get a new random identifier each time they are generated"),
+                                         publisher = lformat!("Publisher of the book"),
+                                         rights = lformat!("Copyright statement of the book"),
+                                         date_published = lformat!("Publication date of the book (YYYY-MM-DD)"),
+                                         date_modified = lformat!("Modification date of the book (YYYY-MM-DD, or YYYY-MM-DDThh:mm:ssZ)"),
+                                         contributors = lformat!("List of contributors other than authors, of the form 'Name (role)', e.g. 'Jane Doe (translator)'"),
+                                         series = lformat!("Name of the series the book belongs to"),
+                                         series_index = lformat!("Position of the book in its series"),
                                          
                                          output_epub = lformat!("Output file name for EPUB rendering"),
                                          output_html = lformat!("Output file name for HTML rendering"),
                                          output_tex = lformat!("Output file name for LaTeX rendering"),
                                          output_pdf = lformat!("Output file name for PDF rendering"),
                                          output_odt = lformat!("Output file name for ODT rendering"),
+                                         output_docx = lformat!("Output file name for DOCX rendering"),
+                                         output_typst = lformat!("Output file name for Typst rendering"),
+                                         output_txt = lformat!("Output file name for plain text rendering"),
+                                         output_md = lformat!("Output file name for Markdown rendering (a single CommonMark file)"),
                                          output_if = lformat!("Output file name for HTML (interactive fiction) rendering"),
                                          output_html_dir = lformat!("Output directory name for HTML rendering"),
                                          output_base_path = lformat!("Directory where those output files will we written"),
@@ -246,6 +335,7 @@ crowbook.verbose:alias                              # {removed}
                                          inline_toc = lformat!("Display a table of content in the document"),
                                          toc_name = lformat!("Name of the table of contents if it is displayed in document"),
                                          num_depth = lformat!("The  maximum heading levels that should be numbered (0: no numbering, 1: only chapters, ..., 6: all)"),
+                                         direction = lformat!("Direction of the text: ltr (left to right), rtl (right to left) or auto (set according to lang)"),
                                          part = lformat!("How to call parts (or 'books', 'episodes', ..."),
                                          chapter = lformat!("How to call chapters"),
                                          chapter_template = lformat!("Naming scheme of chapters, for TOC"),
@@ -286,21 +376,71 @@ crowbook.verbose:alias                              # {removed}
                                          epub_css_add = lformat!("Inline CSS added to the EPUB stylesheet template"),
                                          chapter_xhtml = lformat!("Path of an xhtml template for each chapter"),
                                          epub_toc = lformat!("Add 'Title' and (if set) 'Cover' in the EPUB table of contents"),
+                                         epub_fonts = lformat!("List of font files to embed in the EPUB file, with the corresponding @font-face rules. Each entry is either a file name, or 'file; family; weight; style'"),
+                                         epub_fonts_obfuscate = lformat!("Obfuscate embedded fonts with the IDPF algorithm (EPUB 3 only)"),
+                                         epub_notes_file = lformat!("Gather all notes in a single document at the end of the book, instead of at the end of each chapter"),
+                                         epub_page_list = lformat!("Insert page break markers and a page list in EPUB 3 files (synthetic pagination, based on the number of characters)"),
+                                         epub_page_chars = lformat!("Approximate number of characters of a page for epub.page_list"),
+                                         epub_profile = lformat!("Adjust the markup and CSS of the EPUB file for some readers: 'default' or 'kindle'"),
+                                         epub_layout = lformat!("Layout of the EPUB file, either 'reflowable' or 'pre-paginated' (fixed layout, EPUB 3 only)"),
+                                         epub_layout_pages = lformat!("With a fixed layout, start a new page at each standalone image ('image') or only at each chapter ('chapter')"),
+                                         epub_layout_width = lformat!("With a fixed layout, width of the pages, in pixels"),
+                                         epub_layout_height = lformat!("With a fixed layout, height of the pages, in pixels"),
+                                         epub_layout_spread = lformat!("With a fixed layout, when to display two pages side by side: 'none', 'landscape', 'both' or 'auto'"),
+                                         epub_a11y = lformat!("Add accessibility metadata (schema.org) and landmarks to the EPUB file"),
+                                         epub_a11y_summary = lformat!("Human-readable summary of the accessibility of the EPUB file"),
+                                         epub_a11y_modes = lformat!("Access modes of the EPUB file (schema:accessMode)"),
+                                         epub_a11y_sufficient = lformat!("Sets of access modes sufficient to read the EPUB file, each one being a comma-separated list (schema:accessModeSufficient)"),
+                                         epub_a11y_features = lformat!("Accessibility features of the EPUB file (schema:accessibilityFeature). alternativeText, pageBreakMarkers and pageNavigation are added automatically when relevant"),
+                                         epub_a11y_hazards = lformat!("Accessibility hazards of the EPUB file (schema:accessibilityHazard)"),
+                                         epub_a11y_conforms = lformat!("Accessibility standard the EPUB file conforms to, e.g. 'EPUB Accessibility 1.1 - WCAG 2.1 Level AA'"),
+                                         epub_a11y_certified = lformat!("Name of the person or organization that certified the accessibility of the EPUB file"),
+                                         epub_validate = lformat!("Check the generated EPUB file (well-formed XHTML, manifest, spine, missing files, duplicate ids, navigation document)"),
                                          
                                          tex_links = lformat!("Add foontotes to URL of links so they are readable when printed"),
                                          tex_command = lformat!("LaTeX command to use for generating PDF"),
+                                         tex_max_runs = lformat!("Maximal number of LaTeX runs to get cross-references and table of contents right"),
                                          tex_tmpl = lformat!("Path of a LaTeX template file"),
                                          tex_tmpl_add = lformat!("Inline code added in the LaTeX template"),
-                                         tex_class = lformat!("LaTeX class to use"),
+                                         tex_class = lformat!("LaTeX class to use (e.g. book, article, scrbook, scrartcl or memoir)"),
+                                         tex_chapter_style = lformat!("Chapter style, for the memoir class (e.g. bringhurst, veelo or companion)"),
+                                         tex_header_left = lformat!("LaTeX code for the left part of the running header"),
+                                         tex_header_center = lformat!("LaTeX code for the center part of the running header (default: page number, if no header or footer is set)"),
+                                         tex_header_right = lformat!("LaTeX code for the right part of the running header"),
+                                         tex_footer_left = lformat!("LaTeX code for the left part of the running footer"),
+                                         tex_footer_center = lformat!("LaTeX code for the center part of the running footer"),
+                                         tex_footer_right = lformat!("LaTeX code for the right part of the running footer"),
                                          tex_title = lformat!("If true, generate a title with \\maketitle"),
                                          tex_paper_size = lformat!("Specifies the size of the page."),
                                          tex_margin_left = lformat!("Specifies left margin (note that with book class left and right margins are reversed for odd pages, thus the default value is 1.5cm for book class and 2cm else)"),
                                          tex_margin_right = lformat!("Specifies right margin(note that with book class left and right margins are reversed for odd pages, thus the default value is 2.5cm for book class and 2cm else)"),
                                          tex_margin_top = lformat!("Specifies top margin"),
                                          tex_margin_bottom = lformat!("Specifies left margin"),
+                                         tex_margin_inside = lformat!("Specifies inside margin (on the binding side), overriding tex.margin.left"),
+                                         tex_margin_outside = lformat!("Specifies outside margin, overriding tex.margin.right"),
+                                         tex_margin_gutter = lformat!("Additional inside margin, lost in the binding"),
+                                         tex_trim_width = lformat!("Width of the trimmed page, overriding tex.paper.size (e.g. 5.5in)"),
+                                         tex_trim_height = lformat!("Height of the trimmed page, overriding tex.paper.size (e.g. 8.5in)"),
+                                         tex_bleed = lformat!("Bleed added on each side of the trimmed page (e.g. 3mm)"),
+                                         tex_crop_marks = lformat!("If set to true, add crop marks around the trimmed page"),
+                                         tex_pdfx = lformat!("Generates a PDF/X document, using the pdfx package (x-1a, x-3 or x-4)"),
+                                         tex_pdfx_profile = lformat!("ICC color profile used as output intent for PDF/X"),
+                                         tex_pdfx_intent = lformat!("Identifier of the output intent for PDF/X (e.g. FOGRA39), defaults to the name of the color profile"),
                                          tex_font_size = lformat!("Specify latex font size (in pt, 10 (default), 11, or 12 are accepted)"),
+                                         tex_font_main = lformat!("Main font (requires xelatex or lualatex)"),
+                                         tex_font_sans = lformat!("Sans serif font (requires xelatex or lualatex)"),
+                                         tex_font_mono = lformat!("Monospace font (requires xelatex or lualatex)"),
+                                         tex_font_cjk = lformat!("Font for Chinese, Japanese and Korean characters (requires xelatex or lualatex)"),
+                                         tex_font_arabic = lformat!("Fallback font for runs of Arabic text (requires xelatex or lualatex)"),
+                                         tex_font_cyrillic = lformat!("Fallback font for runs of Cyrillic text (requires xelatex or lualatex)"),
                                          tex_hyperref = lformat!("If disabled, don't try to find references inside the document"),
                                          tex_stdpage = lformat!("If set to true, use 'stdpage' package to format a manuscript according to standards"),
+
+                                         pdf_engine = lformat!("Engine used for PDF rendering: latex, typst or builtin (only if Crowbook was compiled with the pdf feature)"),
+                                         typst_command = lformat!("Typst command to use for generating PDF"),
+                                         typst_tmpl = lformat!("Path of a Typst template file"),
+                                         typst_tmpl_add = lformat!("Inline code added in the Typst template"),
+                                         docx_reference = lformat!("DOCX document whose styles are used instead of the default ones"),
                                          
                                          rs_files = lformat!("Whitespace-separated list of files to embed in e.g. EPUB file; useful for including e.g. fonts"),
                                          rs_out = lformat!("Paths where additional resources should be copied in the EPUB file or HTML directory"),
@@ -319,6 +459,8 @@ crowbook.verbose:alias                              # {removed}
                                          html_as_text = lformat!("Consider HTML blocks as text. This avoids having <foo> being considered as HTML and thus ignored."),
                                          tmp_dir = lformat!("Path where to create a temporary directory (default: uses result from Rust's std::env::temp_dir())"),
                                          zip = lformat!("Command to use to zip files (for EPUB/ODT)"),
+                                         check_external = lformat!("If set to true, try to reach external links and images (requires network access)"),
+                                         check_command = lformat!("Command to use to check external links (must accept the same arguments as curl)"),
                                          
                                          prf_html = lformat!("Output file name for HTML rendering with proofread features"),
                                          prf_html_dir = lformat!("Output directory name for HTML rendering with proofread features"),
@@ -479,6 +621,7 @@ impl BookOptions {
             return Err(Error::book_option(&self.source,
                                           lformat!("Expected a String as a key, found {:?}", key)));
         };
+        self.check_value(&key, &value)?;
 
         if self.valid_str_vecs.contains(&key.as_ref()) {
             // Value is a list of string
@@ -639,8 +782,98 @@ impl BookOptions {
             }
         } else {
             // key not recognized
-            Err(Error::book_option(self.source.clone(),
-                                   lformat!("unrecognized key '{key}'", key = &key)))
+            if let Some(suggestion) = self.closest_key(&key) {
+                Err(Error::book_option(self.source.clone(),
+                                       lformat!("unrecognized key '{key}', did you mean '{suggestion}'?",
+                                                key = &key,
+                                                suggestion = suggestion)))
+            } else {
+                Err(Error::book_option(self.source.clone(),
+                                       lformat!("unrecognized key '{key}'", key = &key)))
+            }
+        }
+    }
+
+    /// Returns the valid key that is the closest to an unrecognized one, if
+    /// it is close enough to be a probable typo.
+    fn closest_key(&self, key: &str) -> Option<&'static str> {
+        let max_distance = ::std::cmp::max(2, key.len() / 4);
+        self.valid_strings.iter()
+            .chain(self.valid_bools.iter())
+            .chain(self.valid_chars.iter())
+            .chain(self.valid_ints.iter())
+            .chain(self.valid_floats.iter())
+            .chain(self.valid_paths.iter())
+            .chain(self.valid_str_vecs.iter())
+            .map(|valid| (misc::levenshtein(key, valid), *valid))
+            .filter(|&(distance, _)| distance <= max_distance)
+            .min()
+            .map(|(_, valid)| valid)
+    }
+
+    /// Checks that the value of an option is in the range of accepted values,
+    /// for the options where it is restricted.
+    ///
+    /// Values of the wrong type are not checked here, since `set_yaml` will
+    /// report them anyway.
+    fn check_value(&self, key: &str, value: &Yaml) -> Result<()> {
+        let valid = match (key, value) {
+            ("epub.version", &Yaml::Integer(n)) => n == 2 || n == 3,
+            ("tex.font.size", &Yaml::Integer(n)) => n == 10 || n == 11 || n == 12,
+            ("tex.max_runs", &Yaml::Integer(n)) => n >= 1 && n <= 10,
+            ("rendering.num_depth", &Yaml::Integer(n)) => n >= 0 && n <= 6,
+            ("rendering.highlight", &Yaml::String(ref s)) => {
+                s == "syntect" || s == "highlight.js" || s == "none"
+            },
+            ("epub.profile", &Yaml::String(ref s)) => s == "default" || s == "kindle",
+            ("epub.layout", &Yaml::String(ref s)) => s == "reflowable" || s == "pre-paginated",
+            ("epub.layout.pages", &Yaml::String(ref s)) => s == "image" || s == "chapter",
+            ("epub.layout.spread", &Yaml::String(ref s)) => {
+                s == "none" || s == "landscape" || s == "both" || s == "auto"
+            },
+            ("lang", &Yaml::String(ref s)) => LangTag::parse(s).is_some(),
+            ("rendering.direction", &Yaml::String(ref s)) => s == "auto" || s == "ltr" || s == "rtl",
+            ("tex.pdfx", &Yaml::String(ref s)) => s == "x-1a" || s == "x-3" || s == "x-4",
+            ("pdf.engine", &Yaml::String(ref s)) => s == "latex" || s == "typst" || s == "builtin",
+            ("tex.margin.gutter", &Yaml::String(ref s)) |
+            ("tex.trim.width", &Yaml::String(ref s)) |
+            ("tex.trim.height", &Yaml::String(ref s)) |
+            ("tex.bleed", &Yaml::String(ref s)) => misc::tex_length(s).is_some(),
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            let accepted = match key {
+                "epub.version" => "2, 3",
+                "tex.font.size" => "10, 11, 12",
+                "tex.max_runs" => "1-10",
+                "rendering.num_depth" => "0-6",
+                "rendering.highlight" => "syntect, highlight.js, none",
+                "epub.profile" => "default, kindle",
+                "epub.layout" => "reflowable, pre-paginated",
+                "epub.layout.pages" => "image, chapter",
+                "epub.layout.spread" => "none, landscape, both, auto",
+                "lang" => "a BCP 47 language tag, e.g. en, pt-BR or sr-Latn",
+                "rendering.direction" => "auto, ltr, rtl",
+                "tex.pdfx" => "x-1a, x-3, x-4",
+                "pdf.engine" => "latex, typst, builtin",
+                "tex.margin.gutter" | "tex.trim.width" | "tex.trim.height" | "tex.bleed" => {
+                    "a length in pt, bp, mm, cm, in or pc"
+                }
+                _ => unreachable!(),
+            };
+            let value = match *value {
+                Yaml::Integer(n) => format!("{}", n),
+                Yaml::String(ref s) => s.clone(),
+                _ => unreachable!(),
+            };
+            Err(Error::book_option(&self.source,
+                                   lformat!("invalid value '{value}' for key '{key}', accepted \
+                                             values are: {accepted}",
+                                            value = value,
+                                            key = key,
+                                            accepted = accepted)))
         }
     }
 
@@ -766,6 +999,10 @@ impl BookOptions {
             "output.pdf" |
             "output.tex" |
             "output.odt" |
+            "output.docx" |
+            "output.typst" |
+            "output.txt" |
+            "output.md" |
             "output.proofread.html" |
             "output.proofread.html.dir" |
             "output.proofread.pdf" |
@@ -27,6 +27,9 @@ pub struct Chapter {
     pub filename: String,
     /// The (already parsed) content of this chapter
     pub content: Vec<Token>,
+    /// The line of the source file where each top-level token of `content`
+    /// starts (empty if unknown)
+    pub lines: Vec<u32>,
 }
 
 impl Chapter {
@@ -41,6 +44,7 @@ impl Chapter {
             number: number,
             filename: filename.into(),
             content: content,
+            lines: vec![],
         }
     }
 }
@@ -0,0 +1,356 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+// Checks a book for problems that would otherwise only be detected
+// (or not at all) when rendering it.
+
+use book::{Book, compile_str};
+use error::{Error, Result, Source};
+use token::Token;
+use resource_handler::ResourceHandler;
+use latex::{FONT_OPTIONS, is_unicode_engine};
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Templates that are compiled with mustache by (at least) one renderer
+static COMPILED_TEMPLATES: &'static [&'static str] = &["epub.chapter.xhtml",
+                                                        "epub.css",
+                                                        "html.css",
+                                                        "html.standalone.template",
+                                                        "html.standalone.js",
+                                                        "html.dir.template",
+                                                        "html.if.js",
+                                                        "tex.template",
+                                                        "typst.template"];
+
+/// Templates that are only included as is
+static RAW_TEMPLATES: &'static [&'static str] = &["html.css.colours",
+                                                   "html.css.print",
+                                                   "html.js",
+                                                   "html.highlight.js",
+                                                   "html.highlight.css",
+                                                   "html.if.new_game"];
+
+/// Options containing an inline mustache template
+static INLINE_TEMPLATES: &'static [&'static str] = &["rendering.chapter.template",
+                                                      "rendering.part.template",
+                                                      "html.chapter.template",
+                                                      "html.part.template"];
+
+impl Book {
+    /// Checks the book without rendering it.
+    ///
+    /// This verifies that custom templates exist and compile, that fonts
+    /// used for PDF output are installed, and runs `check_links`.
+    ///
+    /// If "keep going" mode is set (see `set_keep_going`), all problems are
+    /// recorded and can be retrieved with `take_errors`; else, the first one
+    /// is returned as an error.
+    pub fn check(&self) -> Result<()> {
+        self.check_templates()?;
+        self.check_fonts()?;
+        self.check_links()
+    }
+
+    /// Checks that the fonts set by `tex.font.*` options are installed on
+    /// the system, using `fc-list`.
+    ///
+    /// Fonts are only checked if `tex.command` is xelatex or lualatex,
+    /// since they are ignored else. Font file names (e.g. `foo.otf`) are
+    /// not checked, as they can be found in the TeX distribution.
+    pub fn check_fonts(&self) -> Result<()> {
+        if !is_unicode_engine(self.options.get_str("tex.command").unwrap()) {
+            return Ok(());
+        }
+        for key in FONT_OPTIONS {
+            let font = match self.options.get_str(key) {
+                Ok(font) => font,
+                Err(_) => continue,
+            };
+            let lower = font.to_lowercase();
+            if lower.ends_with(".otf") || lower.ends_with(".ttf") {
+                continue;
+            }
+            match font_exists(font) {
+                Some(true) => (),
+                Some(false) => {
+                    self.keep_going_or(Error::book_option(&self.source,
+                                                          lformat!("font '{font}' (set by \
+                                                                    {key}) could not be found \
+                                                                    on this system",
+                                                                   font = font,
+                                                                   key = key)))?
+                }
+                None => {
+                    warn!("{}",
+                          lformat!("could not run fc-list to check that font '{font}' is \
+                                    installed",
+                                   font = font));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that templates can be found and compiled
+    fn check_templates(&self) -> Result<()> {
+        for template in COMPILED_TEMPLATES.iter().chain(RAW_TEMPLATES.iter()) {
+            if self.options.get_path(template).is_err() {
+                // Built-in template, no need to check it
+                continue;
+            }
+            match self.get_template(template) {
+                Ok(content) => {
+                    if COMPILED_TEMPLATES.contains(template) {
+                        if let Err(err) = compile_str(content.as_ref(), &self.source, template) {
+                            self.keep_going_or(err)?;
+                        }
+                    }
+                },
+                Err(err) => self.keep_going_or(err)?,
+            }
+        }
+        for template in INLINE_TEMPLATES {
+            if let Ok(content) = self.options.get_str(template) {
+                if let Err(err) = compile_str(content, &self.source, template) {
+                    self.keep_going_or(err)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks links and images of all chapters.
+    ///
+    /// This verifies that local images exist, that links to other chapters
+    /// (or to anchors, which must match the `link-N` id that renderers give
+    /// to the N-th header of the book) point to something that exists, and
+    /// warns about links to Markdown files that are not included in the
+    /// book. If `check.links.external` is set, also tries to reach external
+    /// URLs.
+    pub fn check_links(&self) -> Result<()> {
+        self.check_links_or(true)
+    }
+
+    /// Checks links and images of all chapters when rendering the book.
+    ///
+    /// This performs the same verifications as `check_links`, except for
+    /// external URLs, but only displays problems as warnings so they don't
+    /// prevent the book from being rendered.
+    #[doc(hidden)]
+    pub fn warn_links(&self) {
+        // Can't fail since errors are only displayed
+        self.check_links_or(false).unwrap();
+    }
+
+    /// Checks links and images, reporting problems as errors if `strict`
+    /// is set, or as warnings else
+    fn check_links_or(&self, strict: bool) -> Result<()> {
+        let mut n = 0;
+        let anchors: Vec<Vec<String>> = self.chapters
+            .iter()
+            .map(|c| {
+                let mut anchors = vec![];
+                collect_anchors(&c.content, &mut n, &mut anchors);
+                anchors
+            })
+            .collect();
+        let mut external = vec![];
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            let source = Source::new(chapter.filename.as_str());
+            self.check_tokens(&source, strict, i, &anchors, &chapter.content, &mut external)?;
+        }
+
+        if strict && self.options.get_bool("check.links.external").unwrap() {
+            external.sort();
+            external.dedup_by(|a, b| a.0 == b.0);
+            let command = self.options.get_str("check.links.command").unwrap();
+            for &(ref url, ref source) in &external {
+                if let Err(err) = check_external_link(command, url) {
+                    warn!("{}", lformat!("{source}: could not reach {url}: {error}",
+                                         source = source,
+                                         url = url.as_str(),
+                                         error = err));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records or returns a link error if `strict` is set, or displays it
+    /// as a warning else
+    fn link_error(&self, strict: bool, err: Error) -> Result<()> {
+        if strict {
+            self.keep_going_or(err)
+        } else {
+            warn!("{}", err);
+            Ok(())
+        }
+    }
+
+    /// Checks images and links in a list of tokens
+    fn check_tokens(&self,
+                    source: &Source,
+                    strict: bool,
+                    current: usize,
+                    anchors: &[Vec<String>],
+                    tokens: &[Token],
+                    external: &mut Vec<(String, String)>)
+                    -> Result<()> {
+        for token in tokens {
+            match *token {
+                Token::Image(ref url, _, _) |
+                Token::StandaloneImage(ref url, _, _) => {
+                    if !ResourceHandler::is_local(url) {
+                        external.push((url.clone(), format!("{}", source)));
+                    } else if fs::metadata(url).is_err() {
+                        self.link_error(strict,
+                                        Error::file_not_found(source,
+                                                              lformat!("image"),
+                                                              url.clone()))?;
+                    }
+                },
+                Token::Link(ref url, _, _) => {
+                    if !ResourceHandler::is_local(url) {
+                        external.push((url.clone(), format!("{}", source)));
+                    } else if !url.starts_with("mailto:") {
+                        self.check_local_link(source, strict, current, anchors, url)?;
+                    }
+                },
+                _ => {},
+            }
+            if let Some(inner) = token.inner() {
+                self.check_tokens(source, strict, current, anchors, inner, external)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a local link points to an existing chapter (and anchor)
+    /// or file
+    fn check_local_link(&self,
+                        source: &Source,
+                        strict: bool,
+                        current: usize,
+                        anchors: &[Vec<String>],
+                        url: &str)
+                        -> Result<()> {
+        let mut parts = url.splitn(2, '#');
+        let file = parts.next().unwrap();
+        let anchor = parts.next();
+
+        let chapter = if file.is_empty() {
+            Some(current)
+        } else {
+            let path = Path::new(file);
+            let md_path = path.with_extension("md");
+            self.chapters
+                .iter()
+                .position(|c| Path::new(&c.filename) == path
+                          || Path::new(&c.filename) == md_path)
+        };
+
+        match (chapter, anchor) {
+            (Some(n), Some(anchor)) => {
+                if !anchor.is_empty() && !anchors[n].iter().any(|a| a == anchor) {
+                    self.link_error(strict,
+                                    Error::render(source,
+                                                  lformat!("broken link to {url}: anchor \
+                                                            '{anchor}' does not match any \
+                                                            header",
+                                                           url = url,
+                                                           anchor = anchor)))?;
+                }
+            },
+            (Some(_), None) => {},
+            (None, _) => {
+                let path = self.root.join(file);
+                if fs::metadata(&path).is_err() {
+                    self.link_error(strict,
+                                    Error::render(source,
+                                                  lformat!("broken link to {url}",
+                                                           url = url)))?;
+                } else if path.extension().map(|e| e == "md").unwrap_or(false) {
+                    warn!("{}", lformat!("{source}: link to {url} points to a file that is not \
+                                          included in the book",
+                                         source = source,
+                                         url = url));
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Collects the anchors of all headers in a list of tokens.
+///
+/// Renderers number headers in the order they appear in the book, so `n`
+/// is the number of headers found in previous chapters.
+fn collect_anchors(tokens: &[Token], n: &mut u32, anchors: &mut Vec<String>) {
+    for token in tokens {
+        if let Token::Header(..) = *token {
+            *n += 1;
+            anchors.push(format!("link-{}", n));
+        } else if let Some(inner) = token.inner() {
+            collect_anchors(inner, n, anchors);
+        }
+    }
+}
+
+/// Tries to reach an external URL, using `command` (e.g. `curl`)
+fn check_external_link(command: &str, url: &str) -> Result<()> {
+    let output = Command::new(command)
+        .args(&["--silent", "--head", "--location", "--fail", "--max-time", "10", "--output"])
+        .arg(if cfg!(windows) { "NUL" } else { "/dev/null" })
+        .arg(url)
+        .output()
+        .map_err(|e| {
+            Error::default(Source::empty(),
+                           lformat!("failed to run command '{command}': {error}",
+                                    command = command,
+                                    error = e))
+        })?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::default(Source::empty(),
+                           lformat!("command '{command}' returned an error status",
+                                    command = command)))
+    }
+}
+
+/// Checks whether a font family is installed, using `fc-list`.
+///
+/// Returns None if `fc-list` can't be run.
+fn font_exists(family: &str) -> Option<bool> {
+    // Escape characters that have a special meaning in fontconfig patterns
+    let mut pattern = String::from(":family=");
+    for c in family.chars() {
+        if c == '\\' || c == '-' || c == ':' || c == ',' {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    Command::new("fc-list")
+        .arg(pattern)
+        .arg("family")
+        .output()
+        .ok()
+        .map(|output| output.status.success() && !output.stdout.is_empty())
+}
@@ -0,0 +1,111 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+// People who contributed to a book, with their roles
+
+/// Roles that have a name, with their MARC relator code
+static ROLES: &'static [(&'static str, &'static str)] = &[("author", "aut"),
+                                                          ("editor", "edt"),
+                                                          ("translator", "trl"),
+                                                          ("illustrator", "ill"),
+                                                          ("cover designer", "cov"),
+                                                          ("cover artist", "cov"),
+                                                          ("narrator", "nrt"),
+                                                          ("photographer", "pht"),
+                                                          ("designer", "dsr"),
+                                                          ("contributor", "ctb")];
+
+/// A contributor to a book
+#[derive(Debug, PartialEq, Clone)]
+pub struct Contributor {
+    /// Name, as displayed
+    pub name: String,
+    /// Role, as a MARC relator code (e.g. `trl` for a translator)
+    pub role: String,
+    /// Name used for sorting (e.g. `Doe, Jane`), if set
+    pub file_as: Option<String>,
+}
+
+impl Contributor {
+    /// Parses a contributor of the form `Name [File-as] (role)`, where role is
+    /// either a name (e.g. `translator`) or a MARC relator code (e.g.
+    /// `trl`). If the role is omitted, it is `ctb` (contributor).
+    pub fn parse(s: &str) -> Contributor {
+        Contributor::parse_with_role(s, "ctb")
+    }
+
+    /// Parses an author of the form `Name [File-as] (role)`. This is the same
+    /// as `parse`, except that the role defaults to `aut` (author).
+    pub fn parse_author(s: &str) -> Contributor {
+        Contributor::parse_with_role(s, "aut")
+    }
+
+    fn parse_with_role(s: &str, default_role: &str) -> Contributor {
+        let mut s = s.trim();
+        let mut role = String::from(default_role);
+        let mut file_as = None;
+        if s.ends_with(')') {
+            if let Some(i) = s.rfind('(') {
+                role = relator_code(&s[i + 1..s.len() - 1]);
+                s = s[..i].trim();
+            }
+        }
+        if s.ends_with(']') {
+            if let Some(i) = s.rfind('[') {
+                file_as = Some(s[i + 1..s.len() - 1].trim().to_owned());
+                s = s[..i].trim();
+            }
+        }
+        Contributor {
+            name: s.to_owned(),
+            role: role,
+            file_as: file_as,
+        }
+    }
+
+    /// Returns the name used for sorting, which is the displayed name
+    /// if it is not set
+    pub fn file_as(&self) -> &str {
+        self.file_as.as_ref().map(|s| s.as_str()).unwrap_or(&self.name)
+    }
+}
+
+/// Joins a list of names for display, e.g. "A, B and C"
+pub fn join_names(names: &[String], and: &str) -> String {
+    match names.len() {
+        0 => String::new(),
+        1 => names[0].clone(),
+        n => format!("{} {} {}", names[..n - 1].join(", "), and, names[n - 1]),
+    }
+}
+
+/// Returns the MARC relator code corresponding to a role.
+///
+/// Unknown roles of three letters are assumed to already be codes,
+/// others are mapped to `ctb` (contributor).
+pub fn relator_code(role: &str) -> String {
+    let role = role.trim().to_lowercase();
+    if let Some(&(_, code)) = ROLES.iter().find(|&&(name, _)| name == role) {
+        code.to_owned()
+    } else if role.len() == 3 && role.chars().all(|c| c.is_alphabetic()) {
+        role
+    } else {
+        warn!("{}", lformat!("unknown contributor role '{role}', using 'contributor' instead",
+                             role = role));
+        String::from("ctb")
+    }
+}
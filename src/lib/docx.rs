@@ -0,0 +1,785 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received ba copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+use token::Token;
+use book::{Book, compile_str};
+use error::{Error, Result, Source};
+use templates::docx;
+use zipper::Zipper;
+use book_renderer::BookRenderer;
+use resource_handler::ResourceHandler;
+use text_view::{view_as_text, is_block, TextNumbering};
+use misc;
+
+use crowbook_text_processing::escape;
+use mime_guess::guess_mime_type_opt;
+
+use std::io::{Read, Write};
+use std::fs::File;
+use std::path::Path;
+use std::mem;
+
+/// Width of the text area of a page (see `document.xml`), in twentieths of a point
+const TEXT_WIDTH_TWIPS: u64 = 9026;
+/// Width of the text area of a page, in EMUs (used for images)
+const TEXT_WIDTH: u64 = TEXT_WIDTH_TWIPS * 635;
+/// Maximal height of an image, in EMUs
+const MAX_IMAGE_HEIGHT: u64 = 8000000;
+/// Number of EMUs in a pixel, at 96 DPI
+const EMU_PER_PIXEL: u64 = 9525;
+
+const REL_HYPERLINK: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+const REL_IMAGE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+
+/// Namespaces used in `document.xml` and `footnotes.xml`
+const NAMESPACES: &str = "xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\" \
+                          xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\" \
+                          xmlns:wp=\"http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing\" \
+                          xmlns:a=\"http://schemas.openxmlformats.org/drawingml/2006/main\" \
+                          xmlns:pic=\"http://schemas.openxmlformats.org/drawingml/2006/picture\"";
+
+/// A relationship from a part of the document to an image or an external link
+struct Relationship {
+    kind: &'static str,
+    target: String,
+    external: bool,
+}
+
+/// A list, as declared in `numbering.xml`
+struct Numbering {
+    ordered: bool,
+    level: usize,
+    start: usize,
+}
+
+/// Renderer for DOCX (Office Open XML) files
+pub struct DocxRenderer<'a> {
+    book: &'a Book,
+    source: Source,
+    numbering: TextNumbering<'a>,
+    /// Index of the current chapter, whose bookmark hasn't been written yet
+    bookmark: Option<usize>,
+    /// Style of the paragraphs currently rendered (e.g. `BlockText` in a blockquote)
+    paragraph_style: &'static str,
+    /// Style of the paragraphs of the table cells currently rendered
+    cell_style: &'static str,
+    /// Character styles of the current run, innermost last
+    run_styles: Vec<&'static str>,
+    /// Lists currently rendered, as indices in `numberings`
+    lists: Vec<usize>,
+    /// List numbering (numId and level) of the next paragraph, which starts a list item
+    pending_numbering: Option<(usize, usize)>,
+    numberings: Vec<Numbering>,
+    handler: ResourceHandler,
+    /// Images to embed: path in the archive and content
+    media: Vec<(String, Vec<u8>)>,
+    relationships: Vec<Relationship>,
+    footnote_relationships: Vec<Relationship>,
+    /// Whether a footnote is currently rendered (relationships are per part)
+    in_footnote: bool,
+    footnotes: Vec<String>,
+    image_number: u32,
+}
+
+impl<'a> DocxRenderer<'a> {
+    /// Creates a new DocxRenderer
+    pub fn new(book: &'a Book) -> DocxRenderer {
+        let mut handler = ResourceHandler::new();
+        handler.set_images_mapping(true);
+        DocxRenderer {
+            book: book,
+            source: Source::empty(),
+            numbering: TextNumbering::new(book),
+            bookmark: None,
+            paragraph_style: "BodyText",
+            cell_style: "Compact",
+            run_styles: vec![],
+            lists: vec![],
+            pending_numbering: None,
+            numberings: vec![],
+            handler: handler,
+            media: vec![],
+            relationships: vec![],
+            footnote_relationships: vec![],
+            in_footnote: false,
+            footnotes: vec![],
+            image_number: 0,
+        }
+    }
+
+    /// Renders a full book
+    ///
+    /// This will try to generate a DOCX file according to self.book options.
+    ///
+    /// # Returns
+    /// * `Ok(s)` where `s` contains the output of the `zip` command
+    ///   used to create the DOCX file.
+    /// * An error if there was some problem during either the rendering to
+    ///   DOCX format, or the generation of the DOCX file itself.
+    pub fn render_book(&mut self, to: &mut Write) -> Result<String> {
+        let content = self.render_content()?;
+        let document = self.render_template(docx::DOCUMENT, "document.xml", content)?;
+        let styles = match self.book.options.get_path("docx.reference") {
+            Ok(reference) => self.reference_styles(&reference)?,
+            Err(_) => self.render_template(docx::STYLES, "styles.xml", String::new())?,
+        };
+        let settings = self.render_template(docx::SETTINGS, "settings.xml", String::new())?;
+        let core = self.render_template(docx::CORE, "core.xml", String::new())?;
+
+        let mut zipper =
+            Zipper::new(&self.book.options.get_path("crowbook.temp_dir").unwrap())?;
+        zipper.write("[Content_Types].xml", self.content_types().as_bytes(), false)?;
+        zipper.write("_rels/.rels", ROOT_RELS.as_bytes(), false)?;
+        zipper.write("docProps/core.xml", core.as_bytes(), false)?;
+        zipper.write("word/document.xml", document.as_bytes(), false)?;
+        zipper.write("word/styles.xml", styles.as_bytes(), false)?;
+        zipper.write("word/settings.xml", settings.as_bytes(), false)?;
+        zipper.write("word/numbering.xml", self.numbering().as_bytes(), false)?;
+        zipper.write("word/footnotes.xml", self.footnotes().as_bytes(), false)?;
+        zipper.write("word/_rels/document.xml.rels",
+                     render_relationships(DOCUMENT_RELS, &self.relationships).as_bytes(),
+                     false)?;
+        zipper.write("word/_rels/footnotes.xml.rels",
+                     render_relationships("", &self.footnote_relationships).as_bytes(),
+                     false)?;
+        for &(ref path, ref data) in &self.media {
+            zipper.write(format!("word/{}", path), data, false)?;
+        }
+
+        // Zip and copy
+        zipper.generate_docx(self.book.options.get_str("crowbook.zip.command").unwrap(),
+                             to)
+    }
+
+    /// Renders one of the XML files of the archive
+    fn render_template(&self, template: &str, name: &str, content: String) -> Result<String> {
+        let template = compile_str(template, &self.book.source, name)?;
+        let lang = self.book.options.get_str("lang").unwrap().replace('_', "-");
+        let toc = self.book.options.get_bool("rendering.inline_toc").unwrap();
+        let mut data = self.book.get_metadata(|s| Ok(s.to_owned()))?
+            .insert_str("content", content)
+            .insert_str("docx_lang", lang)
+            .insert_bool("docx_toc", toc)
+            .insert_str("docx_toc_depth",
+                        format!("{}", self.book.options.get_i32("rendering.num_depth").unwrap().max(1)));
+        if toc {
+            let name = self.book.options.get_str("rendering.inline_toc.name").unwrap();
+            let name = compile_str(name, &self.book.source, "rendering.inline_toc.name")?;
+            let mut res = vec![];
+            name.render_data(&mut res, &self.book.get_metadata(|s| Ok(s.to_owned()))?.build())?;
+            data = data.insert_str("docx_toc_name", String::from_utf8_lossy(&res).into_owned());
+        }
+        let data = data.build();
+
+        let mut res: Vec<u8> = vec![];
+        template.render_data(&mut res, &data)?;
+        match String::from_utf8(res) {
+            Err(_) => panic!(lformat!("generated {file} was not utf-8 valid", file = name)),
+            Ok(res) => Ok(res),
+        }
+    }
+
+    /// Reads the styles of the reference document set by `docx.reference`
+    fn reference_styles(&self, reference: &str) -> Result<String> {
+        let mut data = vec![];
+        File::open(reference)
+            .and_then(|mut f| f.read_to_end(&mut data))
+            .map_err(|_| {
+                Error::file_not_found(&self.book.source,
+                                      lformat!("DOCX reference document"),
+                                      reference.to_owned())
+            })?;
+        let mut zipper =
+            Zipper::new(&self.book.options.get_path("crowbook.temp_dir").unwrap())?;
+        zipper.write("reference.docx", &data, false)?;
+        zipper.unzip("reference.docx")?;
+        let mut styles = String::new();
+        File::open(zipper.path().join("word").join("styles.xml"))
+            .and_then(|mut f| f.read_to_string(&mut styles))
+            .map_err(|_| {
+                Error::render(&self.book.source,
+                              lformat!("DOCX reference document {file} does not contain \
+                                        word/styles.xml",
+                                       file = reference))
+            })?;
+        Ok(styles)
+    }
+
+    /// Render the body of document.xml
+    fn render_content(&mut self) -> Result<String> {
+        let mut content = String::new();
+        let book = self.book;
+
+        for (i, chapter) in book.chapters.iter().enumerate() {
+            self.handler.add_link(chapter.filename.as_str(), format!("chapter-{}", i));
+        }
+
+        for (i, chapter) in book.chapters.iter().enumerate() {
+            self.source = Source::new(chapter.filename.as_str());
+            self.numbering.start_chapter(chapter.number);
+            self.bookmark = Some(i);
+
+            content.push_str(&self.render_vec(&chapter.content)?);
+        }
+        self.source = Source::empty();
+        Ok(content)
+    }
+
+    /// Transform a vector of `Token`s to DOCX format
+    #[doc(hidden)]
+    pub fn render_vec(&mut self, tokens: &[Token]) -> Result<String> {
+        let mut res = String::new();
+
+        for token in tokens {
+            res.push_str(&self.render_token(token)?);
+        }
+        Ok(res)
+    }
+
+    /// Renders tokens that can be either blocks or inline elements (e.g. in
+    /// list items, table cells or footnotes), wrapping the latter in
+    /// paragraphs with the given style
+    fn render_blocks(&mut self, tokens: &[Token], style: &'static str) -> Result<String> {
+        let previous = mem::replace(&mut self.paragraph_style, style);
+        let mut res = String::new();
+        let mut inline = String::new();
+        for token in tokens {
+            if is_block(token) {
+                if !inline.is_empty() {
+                    res.push_str(&self.paragraph(style, &inline));
+                }
+                inline.clear();
+                res.push_str(&self.render_token(token)?);
+            } else {
+                inline.push_str(&self.render_token(token)?);
+            }
+        }
+        if !inline.is_empty() || res.is_empty() {
+            // Some elements must contain at least a paragraph
+            res.push_str(&self.paragraph(style, &inline));
+        }
+        self.paragraph_style = previous;
+        Ok(res)
+    }
+
+    /// Returns a paragraph with the given style and runs, which starts a list
+    /// item if one is pending
+    fn paragraph(&mut self, style: &str, runs: &str) -> String {
+        let numbering = match self.pending_numbering.take() {
+            Some((id, level)) => {
+                format!("<w:numPr><w:ilvl w:val=\"{}\"/><w:numId w:val=\"{}\"/></w:numPr>",
+                        level,
+                        id)
+            }
+            None => String::new(),
+        };
+        format!("<w:p><w:pPr><w:pStyle w:val=\"{}\"/>{}</w:pPr>{}</w:p>\n",
+                style,
+                numbering,
+                runs)
+    }
+
+    /// Returns a run containing some (already escaped) text, with the current
+    /// character styles
+    fn run(&self, text: &str) -> String {
+        format!("<w:r>{}<w:t xml:space=\"preserve\">{}</w:t></w:r>",
+                self.run_properties(),
+                text)
+    }
+
+    /// Returns the properties of the current run.
+    ///
+    /// A run can only have one character style, so the outer ones are
+    /// applied as direct formatting (in the order required by the schema).
+    fn run_properties(&self) -> String {
+        let last = match self.run_styles.last() {
+            Some(last) => *last,
+            None => return String::new(),
+        };
+        let has = |style: &str| style != last && self.run_styles.contains(&style);
+        let mut res = format!("<w:rPr><w:rStyle w:val=\"{}\"/>", last);
+        if has("VerbatimChar") {
+            res.push_str("<w:rFonts w:ascii=\"Courier New\" w:hAnsi=\"Courier New\"/>");
+        }
+        if has("Strong") {
+            res.push_str("<w:b/>");
+        }
+        if has("Emphasis") {
+            res.push_str("<w:i/>");
+        }
+        if has("Hyperlink") {
+            res.push_str("<w:color w:val=\"1F4E79\"/><w:u w:val=\"single\"/>");
+        }
+        if let Some(style) = self.run_styles
+            .iter()
+            .rev()
+            .find(|s| **s == "Superscript" || **s == "Subscript") {
+            if *style != last {
+                res.push_str(&format!("<w:vertAlign w:val=\"{}\"/>", style.to_lowercase()));
+            }
+        }
+        res.push_str("</w:rPr>");
+        res
+    }
+
+    /// Renders tokens with an additional character style
+    fn render_styled(&mut self, style: &'static str, tokens: &[Token]) -> Result<String> {
+        self.run_styles.push(style);
+        let res = self.render_vec(tokens);
+        self.run_styles.pop();
+        res
+    }
+
+    /// Adds a relationship to the part currently rendered and returns its id
+    fn add_relationship(&mut self, kind: &'static str, target: String, external: bool) -> String {
+        let relationships = if self.in_footnote {
+            &mut self.footnote_relationships
+        } else {
+            &mut self.relationships
+        };
+        if let Some(i) = relationships.iter()
+            .position(|r| r.kind == kind && r.target == target) {
+            return format!("rId{}", i + 10);
+        }
+        relationships.push(Relationship {
+            kind: kind,
+            target: target,
+            external: external,
+        });
+        format!("rId{}", relationships.len() + 9)
+    }
+
+    /// Renders a header, numbered according to the chapter or part template
+    /// if needed
+    fn render_header(&mut self, n: i32, vec: &[Token]) -> Result<String> {
+        let style = if n == 1 && self.numbering.is_part() {
+            String::from("Part")
+        } else {
+            format!("Heading{}", n.min(6))
+        };
+        // Header templates produce plain text, which is put in a single run
+        let mut runs = match self.numbering.numbered_header(n, vec)? {
+            Some(text) => self.run(&escape::html(text)),
+            None => self.render_vec(vec)?,
+        };
+        if n == 1 {
+            if let Some(bookmark) = self.bookmark() {
+                runs = format!("{}{}", bookmark, runs);
+            }
+        }
+        Ok(self.paragraph(&style, &runs))
+    }
+
+    /// Returns the bookmark of the current chapter, if it hasn't been
+    /// written yet, so links to this chapter can point to it
+    fn bookmark(&mut self) -> Option<String> {
+        self.bookmark.take().map(|i| {
+            format!("<w:bookmarkStart w:id=\"{i}\" w:name=\"chapter-{i}\"/>\
+                     <w:bookmarkEnd w:id=\"{i}\"/>",
+                    i = i)
+        })
+    }
+
+    fn render_token(&mut self, token: &Token) -> Result<String> {
+        let res = match *token {
+            Token::Str(ref text) => {
+                let text = escape::html(self.book.clean(text.as_str(), false)).into_owned();
+                self.run(&text)
+            }
+            Token::Paragraph(ref vec) => {
+                let runs = self.render_vec(vec)?;
+                let style = self.paragraph_style;
+                self.paragraph(style, &runs)
+            }
+            Token::Header(n, ref vec) => {
+                if n == 1 && self.numbering.is_hidden() {
+                    // Links to this chapter still need a bookmark
+                    return Ok(self.bookmark().unwrap_or_default());
+                }
+                self.render_header(n, vec)?
+            }
+            Token::Emphasis(ref vec) => self.render_styled("Emphasis", vec)?,
+            Token::Strong(ref vec) => self.render_styled("Strong", vec)?,
+            Token::Code(ref vec) => self.render_styled("VerbatimChar", vec)?,
+            Token::Superscript(ref vec) => self.render_styled("Superscript", vec)?,
+            Token::Subscript(ref vec) => self.render_styled("Subscript", vec)?,
+            Token::List(ref vec) => self.render_list(false, 1, vec)?,
+            Token::OrderedList(start, ref vec) => self.render_list(true, start, vec)?,
+            Token::Item(ref vec) => {
+                let id = *self.lists.last().unwrap();
+                self.pending_numbering = Some((id + 1, self.numberings[id].level));
+                let res = self.render_blocks(vec, "Compact")?;
+                self.pending_numbering = None;
+                res
+            }
+            Token::Link(ref url, _, ref vec) => {
+                if ResourceHandler::is_local(url) && !url.starts_with("mailto:") {
+                    if self.handler.contains_link(url) {
+                        let anchor = self.handler.get_link(url).to_owned();
+                        format!("<w:hyperlink w:anchor=\"{}\">{}</w:hyperlink>",
+                                escape::html(anchor.as_str()),
+                                self.render_styled("Hyperlink", vec)?)
+                    } else {
+                        // Only links to chapters are supported in the document
+                        warn!("{}", lformat!("DOCX ({source}): link to {url} is not a link to a \
+                                              chapter and is displayed as text",
+                                             source = self.source,
+                                             url = url));
+                        self.render_vec(vec)?
+                    }
+                } else {
+                    let id = self.add_relationship(REL_HYPERLINK, url.clone(), true);
+                    format!("<w:hyperlink r:id=\"{}\">{}</w:hyperlink>",
+                            id,
+                            self.render_styled("Hyperlink", vec)?)
+                }
+            }
+            Token::BlockQuote(ref vec) => self.render_blocks(vec, "BlockText")?,
+            Token::CodeBlock(_, ref vec) => {
+                let code = view_as_text(vec);
+                let mut res = String::new();
+                for line in code.trim_right_matches('\n').lines() {
+                    let text = escape::html(line)
+                        .replace('\t', "</w:t><w:tab/><w:t xml:space=\"preserve\">");
+                    let run = format!("<w:r><w:t xml:space=\"preserve\">{}</w:t></w:r>", text);
+                    res.push_str(&self.paragraph("SourceCode", &run));
+                }
+                res
+            }
+            Token::SoftBreak => self.run(" "),
+            Token::HardBreak => String::from("<w:r><w:br/></w:r>"),
+            Token::Rule => {
+                let run = self.run("* * *");
+                self.paragraph("Rule", &run)
+            }
+            Token::Image(ref url, ref title, ref alt) => self.render_image(url, title, alt, false)?,
+            Token::StandaloneImage(ref url, ref title, ref alt) => {
+                let image = self.render_image(url, title, alt, true)?;
+                self.paragraph("Figure", &image)
+            }
+            Token::Table(_, ref vec) => {
+                // Number of columns, from the first row
+                let columns = vec.iter()
+                    .filter_map(|row| row.inner())
+                    .next()
+                    .map(|cells| cells.len())
+                    .unwrap_or(1)
+                    .max(1) as u64;
+                let mut grid = String::new();
+                for _ in 0..columns {
+                    grid.push_str(&format!("<w:gridCol w:w=\"{}\"/>", TEXT_WIDTH_TWIPS / columns));
+                }
+                format!("<w:tbl><w:tblPr><w:tblStyle w:val=\"Table\"/>\
+                         <w:tblW w:w=\"5000\" w:type=\"pct\"/></w:tblPr>\
+                         <w:tblGrid>{}</w:tblGrid>\n{}</w:tbl>\n",
+                        grid,
+                        self.render_vec(vec)?)
+            }
+            Token::TableHead(ref vec) => {
+                self.cell_style = "TableHeading";
+                let row = self.render_vec(vec);
+                self.cell_style = "Compact";
+                format!("<w:tr><w:trPr><w:tblHeader/></w:trPr>\n{}</w:tr>\n", row?)
+            }
+            Token::TableRow(ref vec) => format!("<w:tr>\n{}</w:tr>\n", self.render_vec(vec)?),
+            Token::TableCell(ref vec) => {
+                let style = self.cell_style;
+                let content = if vec.iter().any(is_block) {
+                    self.render_blocks(vec, style)?
+                } else {
+                    // The parser keeps the spaces around the text of a cell
+                    self.render_blocks(&trim_inline(vec), style)?
+                };
+                format!("<w:tc>{}</w:tc>\n", content)
+            }
+            Token::Footnote(ref vec) => {
+                // Footnotes are rendered in their own part, with their own styles
+                let run_styles = mem::replace(&mut self.run_styles, vec![]);
+                let lists = mem::replace(&mut self.lists, vec![]);
+                let pending = self.pending_numbering.take();
+                self.in_footnote = true;
+                let content = self.render_blocks(vec, "FootnoteText");
+                self.in_footnote = false;
+                self.run_styles = run_styles;
+                self.lists = lists;
+                self.pending_numbering = pending;
+
+                let id = self.footnotes.len() + 1;
+                let content = content?.replacen("</w:pPr>",
+                                                "</w:pPr><w:r><w:rPr><w:rStyle \
+                                                 w:val=\"FootnoteReference\"/></w:rPr>\
+                                                 <w:footnoteRef/></w:r><w:r><w:t \
+                                                 xml:space=\"preserve\"> </w:t></w:r>",
+                                                1);
+                self.footnotes.push(format!("<w:footnote w:id=\"{}\">{}</w:footnote>\n",
+                                            id,
+                                            content));
+                format!("<w:r><w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr>\
+                         <w:footnoteReference w:id=\"{}\"/></w:r>",
+                        id)
+            }
+            Token::Annotation(_, ref vec) => self.render_vec(vec)?,
+            Token::__NonExhaustive => unreachable!(),
+        };
+        Ok(res)
+    }
+
+    /// Renders a (possibly ordered) list, declaring a new numbering so that
+    /// each list starts at its own number
+    fn render_list(&mut self, ordered: bool, start: usize, items: &[Token]) -> Result<String> {
+        let mut res = String::new();
+        if self.pending_numbering.is_some() {
+            // List item that starts directly with a sublist
+            res.push_str(&self.paragraph("Compact", ""));
+        }
+        let level = self.lists.len().min(8);
+        self.numberings.push(Numbering {
+            ordered: ordered,
+            level: level,
+            start: start,
+        });
+        self.lists.push(self.numberings.len() - 1);
+        let items = self.render_vec(items);
+        self.lists.pop();
+        res.push_str(&items?);
+        Ok(res)
+    }
+
+    /// Embeds an image in the `media` directory, and returns the run
+    /// displaying it
+    fn render_image(&mut self, url: &str, title: &str, alt: &[Token], standalone: bool) -> Result<String> {
+        if !ResourceHandler::is_local(url) {
+            warn!("{}", lformat!("DOCX: image {url} is not a local file and can't be embedded, \
+                                  displaying its alternative text instead",
+                                 url = url));
+            return self.render_vec(alt);
+        }
+        let dest = self.handler.map_image(&self.source, url)?.into_owned();
+        let path = format!("media/{}", dest.trim_left_matches("images/"));
+        if !self.media.iter().any(|&(ref p, _)| p == &path) {
+            let mut data = vec![];
+            File::open(url)
+                .and_then(|mut f| f.read_to_end(&mut data))
+                .map_err(|_| Error::file_not_found(&self.source, lformat!("image"), url.to_owned()))?;
+            self.media.push((path.clone(), data));
+        }
+        let size = self.media
+            .iter()
+            .find(|&&(ref p, _)| p == &path)
+            .and_then(|&(_, ref data)| misc::image_size(data));
+
+        // Images are displayed at 96 DPI, or at 80% of the text width for
+        // standalone ones (as in HTML), and must fit in a page
+        let (mut width, mut height) = match size {
+            Some((w, h)) if w > 0 && h > 0 => {
+                let (w, h) = (w as u64 * EMU_PER_PIXEL, h as u64 * EMU_PER_PIXEL);
+                if standalone {
+                    (TEXT_WIDTH * 4 / 5, h * TEXT_WIDTH * 4 / 5 / w)
+                } else {
+                    (w, h)
+                }
+            }
+            _ => {
+                warn!("{}", lformat!("DOCX: could not read the size of image {url}",
+                                     url = url));
+                (TEXT_WIDTH * 4 / 5, TEXT_WIDTH * 3 / 5)
+            }
+        };
+        if width > TEXT_WIDTH {
+            height = height * TEXT_WIDTH / width;
+            width = TEXT_WIDTH;
+        }
+        if height > MAX_IMAGE_HEIGHT {
+            width = width * MAX_IMAGE_HEIGHT / height;
+            height = MAX_IMAGE_HEIGHT;
+        }
+
+        let id = self.add_relationship(REL_IMAGE, path.clone(), false);
+        self.image_number += 1;
+        let name = Path::new(&path)
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(format!("<w:r><w:drawing><wp:inline distT=\"0\" distB=\"0\" distL=\"0\" distR=\"0\">\
+                    <wp:extent cx=\"{width}\" cy=\"{height}\"/>\
+                    <wp:docPr id=\"{n}\" name=\"Image {n}\" descr=\"{alt}\" title=\"{title}\"/>\
+                    <a:graphic><a:graphicData \
+                    uri=\"http://schemas.openxmlformats.org/drawingml/2006/picture\"><pic:pic>\
+                    <pic:nvPicPr><pic:cNvPr id=\"{n}\" name=\"{name}\"/><pic:cNvPicPr/></pic:nvPicPr>\
+                    <pic:blipFill><a:blip r:embed=\"{id}\"/><a:stretch><a:fillRect/></a:stretch>\
+                    </pic:blipFill><pic:spPr><a:xfrm><a:off x=\"0\" y=\"0\"/>\
+                    <a:ext cx=\"{width}\" cy=\"{height}\"/></a:xfrm><a:prstGeom prst=\"rect\">\
+                    <a:avLst/></a:prstGeom></pic:spPr></pic:pic></a:graphicData></a:graphic>\
+                    </wp:inline></w:drawing></w:r>",
+                   width = width,
+                   height = height,
+                   n = self.image_number,
+                   alt = escape::html(view_as_text(alt)),
+                   title = escape::html(title),
+                   name = escape::html(name),
+                   id = id))
+    }
+
+    /// Returns the content of `numbering.xml`, with one bulleted and one
+    /// numbered abstract list, and an instance for each list of the book
+    fn numbering(&self) -> String {
+        let mut res = format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<w:numbering {}>\n",
+                              NAMESPACES);
+        for &(id, ordered) in &[(0, false), (1, true)] {
+            res.push_str(&format!("<w:abstractNum w:abstractNumId=\"{}\">\
+                                   <w:multiLevelType w:val=\"multilevel\"/>\n",
+                                  id));
+            for level in 0..9 {
+                let (format, text) = if ordered {
+                    ("decimal", format!("%{}.", level + 1))
+                } else {
+                    ("bullet", String::from(["•", "◦", "▪"][level % 3]))
+                };
+                res.push_str(&format!("<w:lvl w:ilvl=\"{level}\"><w:start w:val=\"1\"/>\
+                                       <w:numFmt w:val=\"{format}\"/><w:lvlText w:val=\"{text}\"/>\
+                                       <w:lvlJc w:val=\"left\"/><w:pPr><w:ind w:left=\"{left}\" \
+                                       w:hanging=\"360\"/></w:pPr></w:lvl>\n",
+                                      level = level,
+                                      format = format,
+                                      text = text,
+                                      left = 720 * (level + 1)));
+            }
+            res.push_str("</w:abstractNum>\n");
+        }
+        for (i, numbering) in self.numberings.iter().enumerate() {
+            res.push_str(&format!("<w:num w:numId=\"{}\"><w:abstractNumId w:val=\"{}\"/>\
+                                   <w:lvlOverride w:ilvl=\"{}\"><w:startOverride w:val=\"{}\"/>\
+                                   </w:lvlOverride></w:num>\n",
+                                  i + 1,
+                                  if numbering.ordered { 1 } else { 0 },
+                                  numbering.level,
+                                  numbering.start));
+        }
+        res.push_str("</w:numbering>\n");
+        res
+    }
+
+    /// Returns the content of `footnotes.xml`
+    fn footnotes(&self) -> String {
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<w:footnotes {}>
+<w:footnote w:type=\"separator\" w:id=\"-1\"><w:p><w:r><w:separator/></w:r></w:p></w:footnote>
+<w:footnote w:type=\"continuationSeparator\" w:id=\"0\"><w:p><w:r><w:continuationSeparator/></w:r></w:p></w:footnote>
+{}</w:footnotes>\n",
+                NAMESPACES,
+                self.footnotes.concat())
+    }
+
+    /// Returns the content of `[Content_Types].xml`
+    fn content_types(&self) -> String {
+        let mut res = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">
+ <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>
+ <Default Extension=\"xml\" ContentType=\"application/xml\"/>
+");
+        let mut extensions: Vec<String> = vec![];
+        for &(ref path, _) in &self.media {
+            let extension = match Path::new(path).extension() {
+                Some(extension) => extension.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            if extensions.contains(&extension) {
+                continue;
+            }
+            let media_type = guess_mime_type_opt(path)
+                .map(|m| m.to_string())
+                .unwrap_or_else(|| String::from("application/octet-stream"));
+            res.push_str(&format!(" <Default Extension=\"{}\" ContentType=\"{}\"/>\n",
+                                  extension,
+                                  media_type));
+            extensions.push(extension);
+        }
+        res.push_str(CONTENT_TYPES_OVERRIDES);
+        res.push_str("</Types>\n");
+        res
+    }
+}
+
+/// Removes the spaces at the beginning and the end of inline content
+fn trim_inline(tokens: &[Token]) -> Vec<Token> {
+    let mut tokens = tokens.to_vec();
+    if let Some(&mut Token::Str(ref mut text)) = tokens.first_mut() {
+        *text = text.trim_left().to_owned();
+    }
+    if let Some(&mut Token::Str(ref mut text)) = tokens.last_mut() {
+        *text = text.trim_right().to_owned();
+    }
+    tokens
+}
+
+/// Returns the content of a relationships part
+fn render_relationships(fixed: &str, relationships: &[Relationship]) -> String {
+    let mut res = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">
+");
+    res.push_str(fixed);
+    for (i, relationship) in relationships.iter().enumerate() {
+        res.push_str(&format!(" <Relationship Id=\"rId{}\" Type=\"{}\" Target=\"{}\"{}/>\n",
+                              i + 10,
+                              relationship.kind,
+                              escape::html(relationship.target.as_str()),
+                              if relationship.external {
+                                  " TargetMode=\"External\""
+                              } else {
+                                  ""
+                              }));
+    }
+    res.push_str("</Relationships>\n");
+    res
+}
+
+const ROOT_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">
+ <Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"word/document.xml\"/>
+ <Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties\" Target=\"docProps/core.xml\"/>
+</Relationships>
+";
+
+const DOCUMENT_RELS: &str = " <Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" Target=\"styles.xml\"/>
+ <Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering\" Target=\"numbering.xml\"/>
+ <Relationship Id=\"rId3\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/footnotes\" Target=\"footnotes.xml\"/>
+ <Relationship Id=\"rId4\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/settings\" Target=\"settings.xml\"/>
+";
+
+const CONTENT_TYPES_OVERRIDES: &str = " <Override PartName=\"/word/document.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml\"/>
+ <Override PartName=\"/word/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml\"/>
+ <Override PartName=\"/word/settings.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.settings+xml\"/>
+ <Override PartName=\"/word/numbering.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml\"/>
+ <Override PartName=\"/word/footnotes.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.footnotes+xml\"/>
+ <Override PartName=\"/docProps/core.xml\" ContentType=\"application/vnd.openxmlformats-package.core-properties+xml\"/>
+";
+
+
+pub struct Docx {}
+
+impl BookRenderer for Docx {
+    fn auto_path(&self, book_name: &str) -> Result<String> {
+        Ok(format!("{}.docx", book_name))
+    }
+
+    fn render(&self, book: &Book, to: &mut Write) -> Result<()> {
+        DocxRenderer::new(book)
+            .render_book(to)?;
+        Ok(())
+    }
+}
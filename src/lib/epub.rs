@@ -28,6 +28,11 @@ use parser::Parser;
 use lang;
 use book_renderer::BookRenderer;
 use text_view::view_as_text;
+use zipper::Zipper;
+use epub_check;
+use epub_patch::{self, EpubPatch};
+use font::FontFile;
+use contributor::{self, Contributor};
 
 use mustache::Template;
 use crowbook_text_processing::escape;
@@ -38,12 +43,14 @@ use epub_builder::ZipCommand;
 use epub_builder::ReferenceType;
 
 use std::io::Write;
+use std::fmt::Write as FmtWrite;
 use std::convert::{AsRef, AsMut};
 use std::fs;
 use std::fs::File;
 use std::path::Path;
 use std::borrow::Cow;
 use std::mem;
+use std::collections::HashMap;
 use mime_guess::guess_mime_type_opt;
 
 /// Renderer for Epub
@@ -54,6 +61,18 @@ pub struct EpubRenderer<'a> {
     html: HtmlRenderer<'a>,
     chapter_title: String,
     chapter_title_raw: String,
+    /// Page breaks inserted for the page list, with the file they are in
+    pages: Vec<(String, usize)>,
+    /// Number of characters since the last page break
+    page_chars: usize,
+    current_file: String,
+    /// Whether an image without alternative text was found
+    missing_alt: bool,
+    /// Notes that have not been rendered yet: number, file of the note
+    /// reference and content
+    notes: Vec<(u32, String, String)>,
+    /// Embedded fonts, relative to the OPF file
+    fonts: Vec<String>,
 }
 
 impl<'a> EpubRenderer<'a> {
@@ -70,6 +89,12 @@ impl<'a> EpubRenderer<'a> {
             toc: vec![],
             chapter_title: String::new(),
             chapter_title_raw: String::new(),
+            pages: vec![],
+            page_chars: 0,
+            current_file: String::new(),
+            missing_alt: false,
+            notes: vec![],
+            fonts: vec![],
         })
     }
 
@@ -84,10 +109,21 @@ impl<'a> EpubRenderer<'a> {
             maker.epub_version(EpubVersion::V30);
         }
         
+        if self.html.book.options.get_str("epub.layout").unwrap() == "pre-paginated" &&
+           !is_fixed_layout(self.html.book) {
+            warn!("{}", lformat!("EPUB: fixed layout requires EPUB 3, ignoring epub.layout"));
+        }
+
         let lang = self.html.book.options.get_str("lang").unwrap();
         let toc_extras = self.html.book.options.get_bool("epub.toc.extras").unwrap();
         maker.metadata("lang", lang)?;
-        maker.metadata("author", escape::html(self.html.book.options.get_str("author").unwrap()))?;
+        let authors: Vec<String> = self.html.book
+            .authors()
+            .into_iter()
+            .map(|a| a.name)
+            .collect();
+        maker.metadata("author", escape::html(contributor::join_names(&authors,
+                                                                      &lang::get_str(lang, "and"))))?;
         maker.metadata("title", escape::html(self.html.book.options.get_str("title").unwrap()))?;
         maker.metadata("generator", "crowbook")?;
         maker.metadata("toc_name", lang::get_str(lang,
@@ -150,7 +186,9 @@ impl<'a> EpubRenderer<'a> {
             maker.add_content(content)?;
         }
 
-        if self.html.book.options.get_bool("rendering.inline_toc").unwrap() {
+        // Kindle needs an HTML table of contents
+        if self.html.book.options.get_bool("rendering.inline_toc").unwrap() ||
+           is_kindle(self.html.book) {
             maker.inline_toc();
         }
 
@@ -165,29 +203,85 @@ impl<'a> EpubRenderer<'a> {
             let n = chapter.number;
             let v = &chapter.content;
             self.html.chapter_config(i, n, filenamer(i));
+            self.current_file = filenamer(i);
             let this_chapter = self.render_chapter(v, &template_chapter)?;
             rendered.push(this_chapter);
         }
 
-        for (i, (rendered_chapter, raw_title)) in rendered.into_iter().enumerate() {
-            let mut content = EpubContent::new(filenamer(i), rendered_chapter.as_bytes());
-            if i == 0 {
-                content = content.reftype(ReferenceType::Text);
-            }
+        for (i, (pages, raw_title)) in rendered.into_iter().enumerate() {
+            for (j, page) in pages.into_iter().enumerate() {
+                let mut content = EpubContent::new(page_filenamer(i, j), page.as_bytes());
+                if i == 0 && j == 0 {
+                    content = content.reftype(ReferenceType::Text);
+                }
+                if j > 0 {
+                    // Only the first page of a chapter appears in the toc
+                    maker.add_content(content)?;
+                    continue;
+                }
 
-            // horrible hack to add subtoc of this chapter to epub's toc
-            // todo: find cleaner way
-            for element in &self.html.toc.elements {
-                if element.url.contains(&filenamer(i)) {
-                    content = content.title(escape::html(raw_title));
-                    content.toc.children = element.children.clone();
-                    break;
+                // horrible hack to add subtoc of this chapter to epub's toc
+                // todo: find cleaner way
+                for element in &self.html.toc.elements {
+                    if element.url.contains(&filenamer(i)) {
+                        content = content.title(escape::html(raw_title.as_str()));
+                        content.toc.children = element.children.clone();
+                        break;
+                    }
                 }
+                maker.add_content(content)?;
             }
-            maker.add_content(content)?;
         }
         self.html.source = Source::empty();
 
+        // Write the notes document, if notes are gathered at the end of the book
+        let notes_file = !self.notes.is_empty();
+        if notes_file {
+            let (notes, title) = self.render_notes_file(&template_chapter)?;
+            maker.add_content(EpubContent::new(NOTES_FILE, notes.as_bytes())
+                              .title(escape::html(title))
+                              .reftype(ReferenceType::Notes))?;
+        }
+
+        // Embed fonts
+        let mut font_faces = String::new();
+        if let Ok(list) = self.html.book.options.get_str_vec("epub.fonts") {
+            let epub3 = self.html.book.options.get_i32("epub.version").unwrap() == 3;
+            let base_path_files =
+                self.html.book.options.get_path("resources.base_path.files").unwrap();
+            for entry in list {
+                let font = FontFile::parse(entry);
+                let media_type = font.media_type(epub3)
+                    .ok_or_else(|| {
+                        Error::render(&self.html.book.source,
+                                      lformat!("font {file} is not in a supported format (otf, \
+                                                ttf, woff or woff2)",
+                                               file = font.file))
+                    })?;
+                let abs_path = Path::new(&base_path_files).join(&font.file);
+                let f = File::open(&abs_path)
+                    .map_err(|_| {
+                        Error::file_not_found(&self.html.book.source,
+                                              lformat!("font"),
+                                              abs_path.to_string_lossy().into_owned())
+                    })?;
+                let file_name = Path::new(&font.file)
+                    .file_name()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                // Fonts from different directories can have the same name
+                let mut dest = format!("fonts/{}", file_name);
+                let mut n = 1;
+                while self.fonts.contains(&dest) {
+                    n += 1;
+                    dest = format!("fonts/{}-{}", n, file_name);
+                }
+                maker.add_resource(&dest, &f, media_type)?;
+                font_faces.push_str(&font.css(&dest));
+                self.fonts.push(dest);
+            }
+        }
+
         // Render the CSS file and write it
         let template_css =
             compile_str(self.html.book.get_template("epub.css").unwrap().as_ref(),
@@ -200,10 +294,17 @@ impl<'a> EpubRenderer<'a> {
         if let Ok(epub_css_add) = self.html.book.options.get_str("epub.css.add") {
             data = data.insert_str("additional_code", epub_css_add);
         }
+        if is_fixed_layout(self.html.book) {
+            data = data.insert_bool("fixed_layout", true)
+                .insert_str("page_width",
+                            format!("{}", self.html.book.options.get_i32("epub.layout.width").unwrap()))
+                .insert_str("page_height",
+                            format!("{}", self.html.book.options.get_i32("epub.layout.height").unwrap()));
+        }
         let data = data.build();
         let mut res: Vec<u8> = vec![];
         template_css.render_data(&mut res, &data)?;
-        let css = String::from_utf8_lossy(&res);
+        let css = format!("{}{}", font_faces, String::from_utf8_lossy(&res));
         maker.stylesheet(css.as_bytes())?;
 
         // Write all images (including cover)
@@ -225,6 +326,7 @@ impl<'a> EpubRenderer<'a> {
         }
 
         // Write additional resources
+        let mut resources = vec![];
         if let Ok(list) = self.html.book.options.get_str_vec("resources.files") {
             let base_path_files =
                 self.html.book.options.get_path("resources.base_path.files").unwrap();
@@ -239,15 +341,322 @@ impl<'a> EpubRenderer<'a> {
                                           lformat!("additional resource from resources.files"),
                                           abs_path.to_string_lossy().into_owned())
                 })?;
-                maker.add_resource(data_path.join(&path), &f, self.get_format(path.as_ref()))?;
+                let dest = data_path.join(&path);
+                resources.push(format!("{}", dest.display()));
+                maker.add_resource(dest, &f, self.get_format(path.as_ref()))?;
             }
         }
 
-        maker.generate(to)?;
-    
+        let patch = self.build_patch(notes_file);
+        let mut buffer = vec![];
+        maker.generate(&mut buffer)?;
+        if !patch.is_empty() {
+            // Unzip the archive to modify it
+            let mut zipper =
+                Zipper::new(&self.html.book.options.get_path("crowbook.temp_dir").unwrap())?;
+            zipper.write("result.epub", &buffer, false)?;
+            zipper.unzip("result.epub")?;
+            patch.apply(zipper.path())?;
+            buffer.clear();
+            zipper.generate_epub(self.html.book.options.get_str("crowbook.zip.command").unwrap(),
+                                 &mut buffer)?;
+        }
+        // Validate the archive before writing it so nothing is written if it fails
+        if self.html.book.options.get_bool("epub.validate").unwrap() {
+            self.validate(&buffer, &resources)?;
+        }
+        to.write_all(&buffer)
+            .map_err(|e| Error::render(&self.html.book.source,
+                                       lformat!("could not write EPUB file: {error}",
+                                                error = e)))?;
+
         Ok(String::new())
     }
 
+    /// Returns the modifications to apply to the archive generated by
+    /// epub-builder: Dublin Core and accessibility metadata, landmarks, page
+    /// list and font obfuscation. `notes_file` tells whether there is a
+    /// document gathering all notes.
+    fn build_patch(&self, notes_file: bool) -> EpubPatch {
+        let book = self.html.book;
+        let epub3 = book.options.get_i32("epub.version").unwrap() == 3;
+        let lang = book.options.get_str("lang").unwrap();
+        let mut patch = EpubPatch::default();
+
+        // Dublin Core metadata that epub-builder doesn't handle
+        if let Ok(identifier) = book.options.get_str("identifier") {
+            patch.identifier = Some(epub_patch::normalize_identifier(identifier));
+        }
+        if let Ok(publisher) = book.options.get_str("publisher") {
+            patch.metadata.push(format!("<dc:publisher>{}</dc:publisher>", escape::html(publisher)));
+        }
+        if let Ok(rights) = book.options.get_str("rights") {
+            patch.metadata.push(format!("<dc:rights>{}</dc:rights>", escape::html(rights)));
+        }
+        if let Ok(date) = book.options.get_str("date.published") {
+            patch.date = Some(if epub3 {
+                format!("<dc:date>{}</dc:date>", escape::html(date))
+            } else {
+                format!("<dc:date opf:event=\"publication\">{}</dc:date>", escape::html(date))
+            });
+        }
+        if let Ok(date) = book.options.get_str("date.modified") {
+            if epub3 {
+                // dcterms:modified must contain a time
+                patch.modified = Some(if date.len() == 10 {
+                    format!("{}T00:00:00Z", date)
+                } else {
+                    date.to_owned()
+                });
+            } else {
+                patch.metadata.push(format!("<dc:date opf:event=\"modification\">{}</dc:date>",
+                                            escape::html(date)));
+            }
+        }
+        if book.options.get_str_vec("authors").is_ok() {
+            for (i, author) in book.authors().iter().enumerate() {
+                if epub3 {
+                    let mut creator = format!("<dc:creator id=\"creator-{}\">{}</dc:creator>
+<meta refines=\"#creator-{}\" property=\"role\" scheme=\"marc:relators\">{}</meta>",
+                                              i,
+                                              escape::html(author.name.as_str()),
+                                              i,
+                                              author.role);
+                    if let Some(ref file_as) = author.file_as {
+                        creator.push_str(&format!("\n<meta refines=\"#creator-{}\" \
+                                                   property=\"file-as\">{}</meta>",
+                                                  i,
+                                                  escape::html(file_as.as_str())));
+                    }
+                    patch.creators.push(creator);
+                } else {
+                    let file_as = match author.file_as {
+                        Some(ref file_as) => {
+                            format!(" opf:file-as=\"{}\"",
+                                    escape::quotes(escape::html(file_as.as_str())))
+                        },
+                        None => String::new(),
+                    };
+                    patch.creators.push(format!("<dc:creator opf:role=\"{}\"{}>{}</dc:creator>",
+                                                author.role,
+                                                file_as,
+                                                escape::html(author.name.as_str())));
+                }
+            }
+        }
+        if let Ok(contributors) = book.options.get_str_vec("contributors") {
+            for (i, contributor) in contributors.iter().enumerate() {
+                let contributor = Contributor::parse(contributor);
+                if epub3 {
+                    patch.metadata.push(format!("<dc:contributor id=\"contributor-{}\">{}</dc:contributor>
+<meta refines=\"#contributor-{}\" property=\"role\" scheme=\"marc:relators\">{}</meta>",
+                                                i,
+                                                escape::html(contributor.name.as_str()),
+                                                i,
+                                                contributor.role));
+                } else {
+                    patch.metadata.push(format!("<dc:contributor opf:role=\"{}\">{}</dc:contributor>",
+                                                contributor.role,
+                                                escape::html(contributor.name.as_str())));
+                }
+            }
+        }
+        if let Ok(series) = book.options.get_str("series") {
+            let index = book.options.get_str("series.index").ok();
+            if epub3 {
+                patch.metadata.push(format!("<meta property=\"belongs-to-collection\" id=\"series\">{}</meta>
+<meta refines=\"#series\" property=\"collection-type\">series</meta>",
+                                            escape::html(series)));
+                if let Some(index) = index {
+                    patch.metadata.push(format!("<meta refines=\"#series\" \
+                                                 property=\"group-position\">{}</meta>",
+                                                escape::html(index)));
+                }
+            } else {
+                // EPUB 2 has no standard way to declare series, but this is
+                // what most tools use
+                patch.metadata.push(epub_patch::meta(false, "calibre:series", series));
+                if let Some(index) = index {
+                    patch.metadata.push(epub_patch::meta(false, "calibre:series_index", index));
+                }
+            }
+        }
+
+        if is_fixed_layout(book) {
+            let spread = book.options.get_str("epub.layout.spread").unwrap();
+            patch.metadata.push(epub_patch::meta(true, "rendition:layout", "pre-paginated"));
+            patch.metadata.push(epub_patch::meta(true, "rendition:orientation", "auto"));
+            patch.metadata.push(epub_patch::meta(true, "rendition:spread", spread));
+            patch.viewport = Some(format!("width={}, height={}",
+                                          book.options.get_i32("epub.layout.width").unwrap(),
+                                          book.options.get_i32("epub.layout.height").unwrap()));
+            patch.page_spreads = spread != "none";
+        }
+
+        if epub3 && book.is_rtl() {
+            patch.page_progression = Some(String::from("rtl"));
+        }
+
+        if book.options.get_bool("epub.accessibility").unwrap() {
+            let mut features = book.options
+                .get_str_vec("epub.accessibility.features")
+                .unwrap()
+                .to_vec();
+            let mut automatic = vec![];
+            if !self.pages.is_empty() {
+                automatic.push("pageBreakMarkers");
+                automatic.push("pageNavigation");
+            }
+            if !self.missing_alt && !self.html.handler.images_mapping().is_empty() {
+                automatic.push("alternativeText");
+            }
+            for feature in automatic {
+                if !features.iter().any(|f| f == feature) {
+                    features.push(feature.to_owned());
+                }
+            }
+            for (key, property) in vec![("epub.accessibility.modes", "schema:accessMode"),
+                                        ("epub.accessibility.modes_sufficient",
+                                         "schema:accessModeSufficient"),
+                                        ("epub.accessibility.hazards", "schema:accessibilityHazard")] {
+                for value in book.options.get_str_vec(key).unwrap() {
+                    patch.metadata.push(epub_patch::meta(epub3, property, value));
+                }
+            }
+            for feature in &features {
+                patch.metadata.push(epub_patch::meta(epub3, "schema:accessibilityFeature", feature));
+            }
+            if let Ok(summary) = book.options.get_str("epub.accessibility.summary") {
+                patch.metadata.push(epub_patch::meta(epub3, "schema:accessibilitySummary", summary));
+            } else {
+                warn!("{}", lformat!("EPUB: epub.accessibility is set but \
+                                      epub.accessibility.summary is not"));
+            }
+            if let Ok(conformance) = book.options.get_str("epub.accessibility.conforms_to") {
+                patch.metadata.push(epub_patch::meta(epub3, "dcterms:conformsTo", conformance));
+            }
+            if let Ok(certifier) = book.options.get_str("epub.accessibility.certified_by") {
+                patch.metadata.push(epub_patch::meta(epub3, "a11y:certifiedBy", certifier));
+            }
+        }
+
+        let inline_toc = book.options.get_bool("rendering.inline_toc").unwrap() || is_kindle(book);
+        if epub3 && (book.options.get_bool("epub.accessibility").unwrap() || is_kindle(book)) {
+            if book.options.get_path("cover").is_ok() {
+                patch.landmarks.push((String::from("cover"),
+                                      String::from("cover.xhtml"),
+                                      lang::get_str(lang, "cover")));
+            }
+            patch.landmarks.push((String::from("titlepage"),
+                                  String::from("title_page.xhtml"),
+                                  lang::get_str(lang, "title")));
+            if inline_toc {
+                patch.landmarks.push((String::from("toc"),
+                                      String::from("toc.xhtml"),
+                                      lang::get_str(lang, "toc")));
+            }
+            if !book.chapters.is_empty() {
+                patch.landmarks.push((String::from("bodymatter"),
+                                      filenamer(0),
+                                      lang::get_str(lang, "start")));
+            }
+            if notes_file {
+                patch.landmarks.push((String::from("endnotes"),
+                                      String::from(NOTES_FILE),
+                                      lang::get_str(lang, "notes")));
+            }
+        }
+        // Kindle conversion also uses the (EPUB 2) guide to find the cover,
+        // the table of contents and where to start reading
+        if is_kindle(book) {
+            if book.options.get_path("cover").is_ok() {
+                patch.guide.push((String::from("cover"),
+                                  String::from("cover.xhtml"),
+                                  lang::get_str(lang, "cover")));
+            }
+            patch.guide.push((String::from("toc"),
+                              String::from("toc.xhtml"),
+                              lang::get_str(lang, "toc")));
+            if !book.chapters.is_empty() {
+                patch.guide.push((String::from("text"),
+                                  filenamer(0),
+                                  lang::get_str(lang, "start")));
+            }
+        }
+
+        if book.options.get_bool("epub.fonts.obfuscate").unwrap() {
+            if epub3 {
+                patch.obfuscated = self.fonts.clone();
+            } else {
+                warn!("{}", lformat!("EPUB: font obfuscation is only supported for EPUB 3, \
+                                      epub.fonts.obfuscate is ignored"));
+            }
+        }
+
+        if !self.pages.is_empty() {
+            let mut nav = format!("<nav epub:type=\"page-list\" hidden=\"hidden\">\n<h2>{}</h2>\n<ol>\n",
+                                  lang::get_str(lang, "pages"));
+            for &(ref file, page) in &self.pages {
+                nav.push_str(&format!("<li><a href=\"{}#page-{}\">{}</a></li>\n", file, page, page));
+            }
+            nav.push_str("</ol>\n</nav>");
+            patch.nav.push(nav);
+        }
+        patch
+    }
+
+    /// Returns a page break marker if the page list is enabled and the
+    /// current page is full, then counts the characters of a paragraph
+    fn page_break(&mut self, chars: usize) -> String {
+        let book = self.html.book;
+        if !book.options.get_bool("epub.page_list").unwrap() ||
+           book.options.get_i32("epub.version").unwrap() != 3 {
+            return String::new();
+        }
+        let page_size = book.options.get_i32("epub.page_list.chars").unwrap().max(1) as usize;
+        let mut marker = String::new();
+        if self.pages.is_empty() || self.page_chars >= page_size {
+            let page = self.pages.len() + 1;
+            marker = format!("<span epub:type = \"pagebreak\" role = \"doc-pagebreak\" \
+                              id = \"page-{}\" aria-label = \"{}\"></span>",
+                             page,
+                             page);
+            self.pages.push((self.current_file.clone(), page));
+            self.page_chars = 0;
+        }
+        self.page_chars += chars;
+        marker
+    }
+
+    /// Validates the EPUB archive, reporting problems with the source file of
+    /// the chapter they were found in.
+    ///
+    /// In "keep going" mode, problems are recorded; otherwise, they are
+    /// displayed and an error is returned if there was any.
+    fn validate(&self, archive: &[u8], resources: &[String]) -> Result<()> {
+        let book = self.html.book;
+        let mut sources = HashMap::new();
+        for (i, chapter) in book.chapters.iter().enumerate() {
+            if !chapter.filename.is_empty() {
+                sources.insert(filenamer(i), Source::new(chapter.filename.as_str()));
+            }
+        }
+        let errors = epub_check::validate_epub(archive, &sources, resources, &book.source);
+        let mut n = 0;
+        for err in errors {
+            if let Err(err) = book.keep_going_or(err) {
+                error!("{}", err);
+                n += 1;
+            }
+        }
+        if n > 0 {
+            Err(Error::render(&book.source,
+                              lformat!("EPUB validation failed with {n} error(s)", n = n)))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Render the titlepgae
     fn render_titlepage(&mut self) -> Result<String> {
         let epub3 = self.html.book.options.get_i32("epub.version").unwrap() == 3;
@@ -299,16 +708,91 @@ impl<'a> EpubRenderer<'a> {
     }
 
 
+    /// Renders a list of notes: as asides with `epub:type = "footnote"` for
+    /// EPUB 3, so e-readers can display them as popups, and as divs for
+    /// EPUB 2.
+    ///
+    /// If `in_chapter` is false, notes are in their own document, so links
+    /// back to the note references must include the chapter file.
+    #[doc(hidden)]
+    pub fn render_notes(&self, notes: &[(u32, String, String)], in_chapter: bool) -> String {
+        let epub3 = self.html.book.options.get_i32("epub.version").unwrap() == 3;
+        let mut res = String::new();
+        for &(number, ref file, ref note) in notes {
+            let back = format!("{}#note-source-{}", if in_chapter { "" } else { file.as_str() }, number);
+            if epub3 {
+                res.push_str(&format!("<aside class = \"note\" epub:type = \"footnote\" \
+                                       role = \"doc-footnote\" id = \"note-dest-{}\">
+<p class = \"note-number\"><a href = \"{}\" role = \"doc-backlink\">[{}]</a></p>
+{}
+</aside>\n",
+                                      number,
+                                      back,
+                                      number,
+                                      note));
+            } else {
+                res.push_str(&format!("<div class = \"note\" id = \"note-dest-{}\">
+<p class = \"note-number\"><a href = \"{}\">[{}]</a></p>
+{}
+</div>\n",
+                                      number,
+                                      back,
+                                      number,
+                                      note));
+            }
+        }
+        res
+    }
+
+    /// Renders the document gathering all the notes of the book, if
+    /// `epub.notes.single_file` is set
+    ///
+    /// Returns the content of the document and its title
+    #[doc(hidden)]
+    pub fn render_notes_file(&mut self, template: &Template) -> Result<(String, String)> {
+        let title = lang::get_str(self.html.book.options.get_str("lang").unwrap(), "notes");
+        let notes = mem::replace(&mut self.notes, vec![]);
+        let content = format!("<h1>{}</h1>\n{}", escape::html(title.as_str()), self.render_notes(&notes, false));
+        let data = self.html
+            .book
+            .get_metadata(|s| self.render_vec(&Parser::new().parse_inline(s)?))?
+            .insert_str("content", content)
+            .insert_str("body_type", "backmatter")
+            .insert_str("epub_type", "endnotes")
+            .insert_str("chapter_title_raw", title.clone())
+            .insert_str("chapter_title", escape::html(title.as_str()).into_owned())
+            .build();
+        let mut res: Vec<u8> = vec![];
+        template.render_data(&mut res, &data)?;
+        Ok((String::from_utf8(res)?, title))
+    }
+
     /// Render a chapter
     ///
-    /// Return chapter content and raw title
-    pub fn render_chapter(&mut self, v: &[Token], template: &Template) -> Result<(String, String)> {
+    /// Return chapter content, as a list of pages (there is only one, unless
+    /// the book has a fixed layout with a page per image), and raw title
+    pub fn render_chapter(&mut self, v: &[Token], template: &Template) -> Result<(Vec<String>, String)> {
         let mut content = String::new();
 
         for token in v {
             content.push_str(&self.render_token(token)?);
             self.html.render_side_notes(&mut content);
         }
+        if !self.html.book.options.get_bool("epub.notes.single_file").unwrap() &&
+           !self.notes.is_empty() {
+            let notes = mem::replace(&mut self.notes, vec![]);
+            let epub3 = self.html.book.options.get_i32("epub.version").unwrap() == 3;
+            write!(content,
+                   "{}\n <h2 class = \"notes\">{}</h2>\n{}{}\n",
+                   if epub3 {
+                       "<section class = \"notes\" epub:type = \"endnotes\" role = \"doc-endnotes\">"
+                   } else {
+                       "<div class = \"notes\">"
+                   },
+                   lang::get_str(self.html.book.options.get_str("lang").unwrap(), "notes"),
+                   self.render_notes(&notes, true),
+                   if epub3 { "</section>" } else { "</div>" })?;
+        }
         self.html.render_end_notes(&mut content);
 
         if self.chapter_title.is_empty() && self.html.current_numbering >= 1 {
@@ -337,22 +821,47 @@ impl<'a> EpubRenderer<'a> {
         }
         self.toc.push(self.chapter_title.clone());
 
-        let data = self.html
-            .book
-            .get_metadata(|s| self.render_vec(&Parser::new().parse_inline(s)?))?
-            .insert_str("content", content)
-            .insert_str("chapter_title_raw",
-                        self.chapter_title_raw.clone())
-            .insert_str("chapter_title",
-                        mem::replace(&mut self.chapter_title, String::new()))
-            .build();
-        self.chapter_title = String::new();
-        let mut res: Vec<u8> = vec![];
-        template.render_data(&mut res, &data)?;
-        match String::from_utf8(res) {
-            Err(_) => panic!(lformat!("generated HTML was not utf-8 valid")),
-            Ok(res) => Ok((res, mem::replace(&mut self.chapter_title_raw, String::new())))
+        // With a fixed layout, each standalone image starts a new page, with
+        // the content that follows it overlaid
+        let mut pages = vec![];
+        for (j, page) in content.split(PAGE_BREAK).enumerate() {
+            if j == 0 {
+                if !page.trim().is_empty() || !content.contains(PAGE_BREAK) {
+                    pages.push(page.to_owned());
+                }
+                continue;
+            }
+            let mut parts = page.splitn(2, PAGE_TEXT);
+            let image = parts.next().unwrap_or("");
+            let text = parts.next().unwrap_or("");
+            let mut page = format!("<div class = \"page-image\">\n{}\n</div>\n", image);
+            if !text.trim().is_empty() {
+                page.push_str(&format!("<div class = \"page-text\">\n{}\n</div>\n", text));
+            }
+            pages.push(page);
         }
+
+        let chapter_title = mem::replace(&mut self.chapter_title, String::new());
+        let mut rendered = vec![];
+        for page in pages {
+            let data = self.html
+                .book
+                .get_metadata(|s| self.render_vec(&Parser::new().parse_inline(s)?))?
+                .insert_str("content", page)
+                .insert_str("body_type", "bodymatter")
+                .insert_str("epub_type", if self.html.current_part { "part" } else { "chapter" })
+                .insert_str("chapter_title_raw",
+                            self.chapter_title_raw.clone())
+                .insert_str("chapter_title", chapter_title.clone())
+                .build();
+            let mut res: Vec<u8> = vec![];
+            template.render_data(&mut res, &data)?;
+            match String::from_utf8(res) {
+                Err(_) => panic!(lformat!("generated HTML was not utf-8 valid")),
+                Ok(res) => rendered.push(res),
+            }
+        }
+        Ok((rendered, mem::replace(&mut self.chapter_title_raw, String::new())))
     }
 
     /// Renders the header section of the book, finding the title of the chapter
@@ -435,6 +944,10 @@ impl<'a> EpubRenderer<'a> {
     where T: AsMut<EpubRenderer<'a>>+AsRef<EpubRenderer<'a>> +
         AsMut<HtmlRenderer<'a>>+AsRef<HtmlRenderer<'a>> + Renderer
     {
+        let kindle = {
+            let html: &HtmlRenderer = this.as_ref();
+            is_kindle(html.book)
+        };
         match *token {
             Token::Str(ref text) => {
                 let html: &mut HtmlRenderer = this.as_mut();
@@ -480,36 +993,94 @@ impl<'a> EpubRenderer<'a> {
                 }
                 HtmlRenderer::static_render_token(this, token)
             }
+            Token::Paragraph(ref vec) => {
+                let marker = {
+                    let epub: &mut EpubRenderer = this.as_mut();
+                    epub.page_break(view_as_text(vec).chars().count())
+                };
+                let content = HtmlRenderer::static_render_token(this, token)?;
+                Ok(format!("{}{}", marker, content))
+            }
+            Token::Image(ref url, _, ref alt) |
+            Token::StandaloneImage(ref url, _, ref alt) => {
+                if view_as_text(alt).trim().is_empty() {
+                    let epub: &mut EpubRenderer = this.as_mut();
+                    epub.missing_alt = true;
+                    warn!("{}", lformat!("EPUB ({source}): image {url} has no alternative text",
+                                         source = epub.html.source,
+                                         url = url));
+                }
+                let content = HtmlRenderer::static_render_token(this, token)?;
+                let new_page = {
+                    let html: &HtmlRenderer = this.as_ref();
+                    !token.is_image() && is_fixed_layout(html.book) &&
+                    html.book.options.get_str("epub.layout.pages").unwrap() == "image"
+                };
+                if new_page {
+                    Ok(format!("{}{}{}", PAGE_BREAK, content, PAGE_TEXT))
+                } else {
+                    Ok(content)
+                }
+            }
             Token::Footnote(ref vec) => {
-                let epub3 = (this.as_ref() as &HtmlRenderer)
-                    .book
-                    .options
-                    .get_i32("epub.version")
-                    .unwrap() == 3;
+                let (epub3, single_file) = {
+                    let html: &HtmlRenderer = this.as_ref();
+                    (html.book.options.get_i32("epub.version").unwrap() == 3,
+                     html.book.options.get_bool("epub.notes.single_file").unwrap())
+                };
                 let inner_content = this.render_vec(vec)?;
-                let html: &mut HtmlRenderer = this.as_mut();
-                html.footnote_number += 1;
-                let number = html.footnote_number;
-                let note_number = format!("<p class = \"note-number\">
+                let number = {
+                    let html: &mut HtmlRenderer = this.as_mut();
+                    html.footnote_number += 1;
+                    html.footnote_number
+                };
+                // Kindle doesn't handle side notes, so notes are always
+                // rendered at the end of the chapter
+                if epub3 || single_file || kindle {
+                    let epub: &mut EpubRenderer = this.as_mut();
+                    let file = epub.current_file.clone();
+                    epub.notes.push((number, file, inner_content));
+                } else {
+                    let html: &mut HtmlRenderer = this.as_mut();
+                    let note_number = format!("<p class = \"note-number\">
   <a href = \"#note-source-{}\">[{}]</a>
 </p>\n",
-                                          number,
-                                          number);
-                let inner = if epub3 {
-                    format!("<aside epub:type = \"footnote\" id = \"note-dest-{}\">{}</aside>",
-                            number,
-                            inner_content)
+                                              number,
+                                              number);
+                    html.add_footnote(note_number,
+                                      format!("<a id = \"note-dest-{}\" />{}",
+                                              number,
+                                              inner_content));
+                }
+
+                let noteref = if epub3 {
+                    "epub:type = \"noteref\" role = \"doc-noteref\" "
                 } else {
-                    format!("<a id = \"note-dest-{}\" />{}", number, inner_content)
+                    ""
                 };
-                html.add_footnote(note_number, inner);
-
-                Ok(format!("<a {} href = \"#note-dest-{}\"><sup id = \
-                            \"note-source-{}\">[{}]</sup></a>",
-                           if epub3 { "epub:type = \"noteref\"" } else { "" },
-                           number,
-                           number,
-                           number))
+                let file = if single_file { NOTES_FILE } else { "" };
+                if kindle {
+                    // Kindle renders <sup> in links poorly
+                    Ok(format!("<a {}class = \"noteref\" id = \"note-source-{}\" \
+                                href = \"{}#note-dest-{}\">[{}]</a>",
+                               noteref,
+                               number,
+                               file,
+                               number,
+                               number))
+                } else {
+                    Ok(format!("<a {}href = \"{}#note-dest-{}\"><sup id = \
+                                \"note-source-{}\">[{}]</sup></a>",
+                               noteref,
+                               file,
+                               number,
+                               number,
+                               number))
+                }
+            }
+            Token::Table(_, ref vec) if kindle => {
+                // Kindle doesn't like tables nested in other blocks
+                Ok(format!("<table>\n{}\n</table>\n", this.render_vec(vec)?))
             }
             _ => HtmlRenderer::static_render_token(this, token),
         }
@@ -517,11 +1088,40 @@ impl<'a> EpubRenderer<'a> {
 }
 
 
+/// Name of the document gathering all notes, if `epub.notes.single_file` is set
+const NOTES_FILE: &'static str = "notes.xhtml";
+
 /// Generate a file name given an int
 fn filenamer(i: usize) -> String {
     format!("chapter_{:03}.xhtml", i)
 }
 
+/// Generate the file name of a page of a chapter, for fixed-layout EPUB
+/// files (the first page keeps the chapter's file name, so links still work)
+fn page_filenamer(i: usize, j: usize) -> String {
+    if j == 0 {
+        filenamer(i)
+    } else {
+        format!("chapter_{:03}_{:03}.xhtml", i, j)
+    }
+}
+
+/// Marks the start of a new page, for fixed-layout EPUB files
+const PAGE_BREAK: &'static str = "<!-- crowbook:page -->";
+/// Marks the end of the image of a page, for fixed-layout EPUB files
+const PAGE_TEXT: &'static str = "<!-- crowbook:page-text -->";
+
+/// Returns true if the markup and CSS must be adjusted for Kindle conversion
+fn is_kindle(book: &Book) -> bool {
+    book.options.get_str("epub.profile").unwrap() == "kindle"
+}
+
+/// Returns true if the book uses a fixed layout, which is only possible with EPUB 3
+fn is_fixed_layout(book: &Book) -> bool {
+    book.options.get_i32("epub.version").unwrap() == 3 &&
+    book.options.get_str("epub.layout").unwrap() == "pre-paginated"
+}
+
 
 derive_html!{EpubRenderer<'a>, EpubRenderer::static_render_token}
 
@@ -0,0 +1,675 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+// Validation of a generated EPUB archive: well-formedness
+// of XML files, manifest and spine consistency, presence of referenced
+// files, uniqueness of ids and EPUB 3 navigation document.
+
+use error::{Error, Source};
+
+use zip::ZipArchive;
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+/// A (very) simplified XML element
+#[derive(Debug)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// Text directly contained in this element (entities are not decoded)
+    pub text: String,
+    pub line: usize,
+    /// Byte offsets of the element in the document, from its opening `<`
+    /// to the end of its closing tag
+    pub span: (usize, usize),
+    /// Byte offsets of the content of the element, between its tags
+    pub inner: (usize, usize),
+}
+
+impl Element {
+    /// Returns the value of an attribute
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|&&(ref n, _)| n == name)
+            .map(|&(_, ref v)| v.as_str())
+    }
+
+    /// Returns all elements of this tree (including this one), in document order
+    pub fn descendants(&self) -> Vec<&Element> {
+        let mut res = vec![self];
+        for child in &self.children {
+            res.extend(child.descendants());
+        }
+        res
+    }
+
+    /// Returns the elements of this tree with a given name
+    pub fn find_all(&self, name: &str) -> Vec<&Element> {
+        self.descendants()
+            .into_iter()
+            .filter(|e| e.name == name)
+            .collect()
+    }
+}
+
+/// Minimal XML parser, only checking that a document is well-formed
+/// and building its tree of elements.
+struct XmlParser<'a> {
+    s: &'a str,
+    pos: usize,
+    line: usize,
+    /// Entities declared in the internal subset of the DOCTYPE
+    entities: Vec<String>,
+}
+
+impl<'a> XmlParser<'a> {
+    fn error<S: AsRef<str>>(&self, msg: S) -> String {
+        lformat!("line {line}: {msg}", line = self.line, msg = msg.as_ref())
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn starts_with(&self, p: &str) -> bool {
+        self.rest().starts_with(p)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.s.len()
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.line += self.s[self.pos..self.pos + n].matches('\n').count();
+        self.pos += n;
+    }
+
+    fn skip_whitespace(&mut self) {
+        let n = self.rest().len() - self.rest().trim_left().len();
+        self.advance(n);
+    }
+
+    /// Skips everything until `end` (included)
+    fn skip_until(&mut self, end: &str) -> Result<(), String> {
+        match self.rest().find(end) {
+            Some(i) => {
+                self.advance(i + end.len());
+                Ok(())
+            },
+            None => Err(self.error(lformat!("unterminated '{end}'", end = end))),
+        }
+    }
+
+    /// Skips the DOCTYPE, collecting the entities declared in its internal subset
+    fn skip_doctype(&mut self) -> Result<(), String> {
+        let bracket = self.rest().find('[');
+        let end = self.rest().find('>');
+        match (bracket, end) {
+            (Some(b), Some(e)) if b < e => {
+                let start = self.pos;
+                self.skip_until("]>")?;
+                let subset = &self.s[start + b..self.pos];
+                for declaration in subset.split("<!ENTITY").skip(1) {
+                    let name = declaration.split_whitespace().next().unwrap_or("");
+                    // Parameter entities (`<!ENTITY % name ...>`) can't be used in the document
+                    if name != "%" && !name.is_empty() {
+                        self.entities.push(name.to_owned());
+                    }
+                }
+                Ok(())
+            },
+            _ => self.skip_until(">"),
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<String, String> {
+        let len = self.rest()
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=')
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error(lformat!("expected a name")));
+        }
+        let name = self.rest()[..len].to_owned();
+        self.advance(len);
+        Ok(name)
+    }
+
+    /// Checks that all `&` in a text start a valid entity
+    fn check_entities(&self, text: &str) -> Result<(), String> {
+        for (i, _) in text.match_indices('&') {
+            let entity = text[i + 1..].split(';').next().unwrap();
+            let valid = if !text[i + 1..].contains(';') {
+                false
+            } else if entity.starts_with("#x") {
+                entity.len() > 2 && entity[2..].chars().all(|c| c.is_digit(16))
+            } else if entity.starts_with('#') {
+                entity.len() > 1 && entity[1..].chars().all(|c| c.is_digit(10))
+            } else {
+                match entity {
+                    "amp" | "lt" | "gt" | "quot" | "apos" => true,
+                    // Other entities must be declared in the DOCTYPE, since
+                    // reading systems don't load external DTDs
+                    _ => self.entities.iter().any(|e| e == entity),
+                }
+            };
+            if !valid {
+                return Err(self.error(lformat!("invalid entity or unescaped '&'")));
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_document(&mut self) -> Result<Element, String> {
+        let mut root = None;
+        loop {
+            self.skip_whitespace();
+            if self.at_end() {
+                break;
+            }
+            if self.starts_with("<?") {
+                self.skip_until("?>")?;
+            } else if self.starts_with("<!--") {
+                self.skip_until("-->")?;
+            } else if self.starts_with("<!DOCTYPE") {
+                self.skip_doctype()?;
+            } else if self.starts_with("<") {
+                if root.is_some() {
+                    return Err(self.error(lformat!("document has more than one root element")));
+                }
+                root = Some(self.parse_element()?);
+            } else {
+                return Err(self.error(lformat!("text outside of root element")));
+            }
+        }
+        root.ok_or_else(|| lformat!("document has no root element"))
+    }
+
+    fn parse_element(&mut self) -> Result<Element, String> {
+        let line = self.line;
+        let start = self.pos;
+        self.advance(1); // '<'
+        let name = self.parse_name()?;
+        let mut attributes: Vec<(String, String)> = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.at_end() {
+                return Err(self.error(lformat!("unterminated tag <{name}>", name = name)));
+            } else if self.starts_with("/>") {
+                self.advance(2);
+                return Ok(Element {
+                    name: name,
+                    attributes: attributes,
+                    children: vec![],
+                    text: String::new(),
+                    line: line,
+                    span: (start, self.pos),
+                    inner: (self.pos, self.pos),
+                });
+            } else if self.starts_with(">") {
+                self.advance(1);
+                break;
+            }
+            let attr = self.parse_name()?;
+            self.skip_whitespace();
+            if !self.starts_with("=") {
+                return Err(self.error(lformat!("attribute '{attr}' has no value", attr = attr)));
+            }
+            self.advance(1);
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(c) if c == '"' || c == '\'' => c,
+                _ => {
+                    return Err(self.error(lformat!("value of attribute '{attr}' is not quoted",
+                                                   attr = attr)))
+                },
+            };
+            self.advance(1);
+            let len = self.rest()
+                .find(quote)
+                .ok_or_else(|| self.error(lformat!("unterminated value for attribute '{attr}'",
+                                                   attr = attr)))?;
+            let value = self.rest()[..len].to_owned();
+            if value.contains('<') {
+                return Err(self.error(lformat!("value of attribute '{attr}' contains '<'",
+                                               attr = attr)));
+            }
+            self.check_entities(&value)?;
+            self.advance(len + 1);
+            if attributes.iter().any(|&(ref a, _)| a == &attr) {
+                return Err(self.error(lformat!("duplicate attribute '{attr}'", attr = attr)));
+            }
+            attributes.push((attr, value));
+        }
+
+        let mut children = vec![];
+        let mut text = String::new();
+        let inner_start = self.pos;
+        loop {
+            if self.at_end() {
+                return Err(self.error(lformat!("element <{name}> opened at line {line} is not \
+                                                closed",
+                                               name = name,
+                                               line = line)));
+            } else if self.starts_with("</") {
+                let inner_end = self.pos;
+                self.advance(2);
+                let end = self.parse_name()?;
+                self.skip_whitespace();
+                if !self.starts_with(">") {
+                    return Err(self.error(lformat!("unterminated closing tag </{name}>",
+                                                   name = end)));
+                }
+                self.advance(1);
+                if end != name {
+                    return Err(self.error(lformat!("closing tag </{end}> does not match <{name}> \
+                                                    opened at line {line}",
+                                                   end = end,
+                                                   name = name,
+                                                   line = line)));
+                }
+                return Ok(Element {
+                    name: name,
+                    attributes: attributes,
+                    children: children,
+                    text: text,
+                    line: line,
+                    span: (start, self.pos),
+                    inner: (inner_start, inner_end),
+                });
+            } else if self.starts_with("<!--") {
+                self.skip_until("-->")?;
+            } else if self.starts_with("<![CDATA[") {
+                self.skip_until("]]>")?;
+            } else if self.starts_with("<?") {
+                self.skip_until("?>")?;
+            } else if self.starts_with("<") {
+                children.push(self.parse_element()?);
+            } else {
+                let len = self.rest().find('<').unwrap_or(self.rest().len());
+                let content = &self.rest()[..len];
+                self.check_entities(content)?;
+                text.push_str(content);
+                self.advance(len);
+            }
+        }
+    }
+}
+
+/// Decodes the predefined and numeric entities of an XML text
+pub fn decode_entities(text: &str) -> String {
+    let mut res = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        res.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let c = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity if entity.starts_with("#x") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(::std::char::from_u32)
+            },
+            entity if entity.starts_with('#') => {
+                entity[1..].parse().ok().and_then(::std::char::from_u32)
+            },
+            _ => None,
+        };
+        match c {
+            Some(c) => {
+                res.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Parses an XML document, returning an error message if it is not well-formed
+pub fn parse_xml(content: &str) -> Result<Element, String> {
+    let mut parser = XmlParser {
+        s: content,
+        pos: 0,
+        line: 1,
+        entities: vec![],
+    };
+    parser.parse_document()
+}
+
+/// Resolves a path relative to the directory `dir` of the archive, handling
+/// `.` and `..` components
+fn resolve(dir: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = if dir.is_empty() {
+        vec![]
+    } else {
+        dir.split('/').collect()
+    };
+    for part in href.split('/') {
+        match part {
+            "" | "." => {},
+            ".." => {
+                parts.pop();
+            },
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Returns the directory part of a path in the archive
+fn dirname(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+/// Returns true if a link is local to the archive
+fn is_local(url: &str) -> bool {
+    !(url.contains("://") || url.starts_with("mailto:") || url.starts_with("data:") ||
+      url.starts_with("tel:"))
+}
+
+/// Collects the problems found in an EPUB archive
+struct Report<'a> {
+    errors: Vec<Error>,
+    sources: &'a HashMap<String, Source>,
+    default_source: &'a Source,
+}
+
+impl<'a> Report<'a> {
+    /// Records a problem in a file of the archive, reporting it with the
+    /// source the file was generated from if it is known
+    fn error(&mut self, file: &str, msg: String) {
+        let name = file.rsplit('/').next().unwrap();
+        let source = self.sources.get(name).unwrap_or(self.default_source).clone();
+        let msg = if file.is_empty() {
+            lformat!("EPUB validation: {msg}", msg = msg)
+        } else {
+            lformat!("EPUB validation: {file}: {msg}", file = file, msg = msg)
+        };
+        self.errors.push(Error::render(source, msg));
+    }
+}
+
+/// Validates an EPUB archive.
+///
+/// * `archive` is the content of the EPUB (zip) file;
+/// * `sources` maps file names of XHTML documents (e.g. `chapter_001.xhtml`)
+/// to the source they were generated from, for error reporting;
+/// * `resources` lists the files (relative to the OPF file) that were added
+/// from `resources.files` and must be present;
+/// * `default_source` is used for errors in other files.
+///
+/// Returns the list of problems found.
+pub fn validate_epub(archive: &[u8],
+                     sources: &HashMap<String, Source>,
+                     resources: &[String],
+                     default_source: &Source)
+                     -> Vec<Error> {
+    let mut report = Report {
+        errors: vec![],
+        sources: sources,
+        default_source: default_source,
+    };
+
+    // Read the files of the archive
+    let mut zip = match ZipArchive::new(Cursor::new(archive)) {
+        Ok(zip) => zip,
+        Err(err) => {
+            report.error("", lformat!("could not read archive: {error}", error = err));
+            return report.errors;
+        },
+    };
+    let mut contents: HashMap<String, Vec<u8>> = HashMap::new();
+    for i in 0..zip.len() {
+        let mut file = match zip.by_index(i) {
+            Ok(file) => file,
+            Err(err) => {
+                report.error("", lformat!("could not read archive: {error}", error = err));
+                return report.errors;
+            },
+        };
+        if file.name().ends_with('/') {
+            // Directory
+            continue;
+        }
+        let mut content = vec![];
+        if let Err(err) = file.read_to_end(&mut content) {
+            report.error(file.name(), lformat!("could not be read: {error}", error = err));
+        }
+        contents.insert(file.name().to_owned(), content);
+    }
+    let mut files: Vec<String> = contents.keys().cloned().collect();
+    files.sort();
+    let read = |file: &str| -> Option<String> {
+        contents.get(file).map(|c| String::from_utf8_lossy(c).into_owned())
+    };
+
+    if !files.iter().any(|f| f == "mimetype") {
+        report.error("mimetype", lformat!("file is missing"));
+    }
+
+    // Find the OPF file
+    let container = match read("META-INF/container.xml").map(|c| parse_xml(&c)) {
+        Some(Ok(container)) => container,
+        Some(Err(msg)) => {
+            report.error("META-INF/container.xml", msg);
+            return report.errors;
+        },
+        None => {
+            report.error("META-INF/container.xml", lformat!("file is missing"));
+            return report.errors;
+        },
+    };
+    let opf_file = match container.find_all("rootfile").first().and_then(|e| e.attr("full-path")) {
+        Some(path) => path.to_owned(),
+        None => {
+            report.error("META-INF/container.xml", lformat!("no rootfile is declared"));
+            return report.errors;
+        },
+    };
+    let opf = match read(&opf_file).map(|c| parse_xml(&c)) {
+        Some(Ok(opf)) => opf,
+        Some(Err(msg)) => {
+            report.error(&opf_file, msg);
+            return report.errors;
+        },
+        None => {
+            report.error(&opf_file, lformat!("file is missing"));
+            return report.errors;
+        },
+    };
+    let opf_dir = dirname(&opf_file).to_owned();
+    let epub3 = opf.attr("version").map(|v| v.starts_with('3')).unwrap_or(false);
+
+    // Manifest
+    let mut manifest: HashMap<String, (String, String, String)> = HashMap::new();
+    for item in opf.find_all("item") {
+        let id = item.attr("id").unwrap_or("");
+        let href = item.attr("href").unwrap_or("");
+        if id.is_empty() || href.is_empty() || item.attr("media-type").is_none() {
+            report.error(&opf_file,
+                  lformat!("manifest item at line {line} must have an id, a href and a media-type",
+                           line = item.line));
+            continue;
+        }
+        let path = resolve(&opf_dir, href);
+        if !files.contains(&path) {
+            report.error(&opf_file,
+                  lformat!("manifest item '{id}' refers to missing file {file}",
+                           id = id,
+                           file = path));
+        }
+        if manifest.contains_key(id) {
+            report.error(&opf_file, lformat!("duplicate manifest id '{id}'", id = id));
+        }
+        manifest.insert(id.to_owned(),
+                        (path,
+                         item.attr("media-type").unwrap().to_owned(),
+                         item.attr("properties").unwrap_or("").to_owned()));
+    }
+    for file in &files {
+        if file == "mimetype" || file.starts_with("META-INF/") || file == &opf_file {
+            continue;
+        }
+        if !manifest.values().any(|&(ref path, _, _)| path == file) {
+            report.error(file, lformat!("file is not declared in the manifest"));
+        }
+    }
+    for file in resources {
+        let path = resolve(&opf_dir, file);
+        if !files.contains(&path) {
+            report.error(&path, lformat!("file from resources.files is missing"));
+        }
+    }
+
+    // Spine
+    let itemrefs = opf.find_all("itemref");
+    if itemrefs.is_empty() {
+        report.error(&opf_file, lformat!("spine is empty"));
+    }
+    for itemref in itemrefs {
+        let idref = itemref.attr("idref").unwrap_or("");
+        match manifest.get(idref) {
+            None => {
+                report.error(&opf_file,
+                      lformat!("spine refers to '{id}', which is not in the manifest",
+                               id = idref))
+            },
+            Some(&(_, ref media_type, _)) if media_type != "application/xhtml+xml" => {
+                report.error(&opf_file,
+                      lformat!("spine item '{id}' is not an XHTML document", id = idref))
+            },
+            _ => {},
+        }
+    }
+    if !epub3 {
+        let toc = opf.find_all("spine")
+            .first()
+            .and_then(|s| s.attr("toc"))
+            .map(|s| s.to_owned())
+            .unwrap_or_default();
+        if !manifest.contains_key(&toc) {
+            report.error(&opf_file, lformat!("spine does not refer to a NCX table of contents"));
+        }
+    }
+
+    // Content documents
+    let mut ids: HashMap<String, Vec<String>> = HashMap::new();
+    let mut fragments: Vec<(String, String, String)> = vec![];
+    let mut navs = vec![];
+    for &(ref path, ref media_type, ref properties) in manifest.values() {
+        if media_type != "application/xhtml+xml" && media_type != "application/x-dtbncx+xml" {
+            continue;
+        }
+        let doc = match read(path).map(|c| parse_xml(&c)) {
+            Some(Ok(doc)) => doc,
+            Some(Err(msg)) => {
+                report.error(path, lformat!("document is not well-formed: {msg}", msg = msg));
+                continue;
+            },
+            None => continue,
+        };
+        if properties.split_whitespace().any(|p| p == "nav") {
+            navs.push(path.clone());
+            let has_toc = doc.find_all("nav")
+                .into_iter()
+                .any(|nav| {
+                    nav.attr("epub:type").map(|t| t.split_whitespace().any(|t| t == "toc")).unwrap_or(false) &&
+                    !nav.find_all("ol").is_empty()
+                });
+            if !has_toc {
+                report.error(path,
+                      lformat!("navigation document must contain a <nav epub:type=\"toc\"> \
+                                element with an <ol> list"));
+            }
+        }
+        let mut doc_ids: Vec<String> = vec![];
+        let doc_dir = dirname(path).to_owned();
+        for element in doc.descendants() {
+            if let Some(id) = element.attr("id") {
+                if doc_ids.iter().any(|i| i == id) {
+                    report.error(path,
+                          lformat!("duplicate id '{id}' at line {line}",
+                                   id = id,
+                                   line = element.line));
+                } else {
+                    doc_ids.push(id.to_owned());
+                }
+            }
+            for attr in &["src", "href", "xlink:href"] {
+                let url = match element.attr(attr) {
+                    Some(url) if is_local(url) => url,
+                    _ => continue,
+                };
+                let mut parts = url.splitn(2, '#');
+                let file = parts.next().unwrap();
+                let target = if file.is_empty() {
+                    path.clone()
+                } else {
+                    resolve(&doc_dir, file)
+                };
+                if !files.contains(&target) {
+                    report.error(path,
+                          lformat!("broken reference to {url} at line {line}",
+                                   url = url,
+                                   line = element.line));
+                } else if element.name == "img" &&
+                          !manifest.values().any(|&(ref p, _, _)| p == &target) {
+                    report.error(path,
+                          lformat!("image {url} is not declared in the manifest", url = url));
+                }
+                if let Some(fragment) = parts.next() {
+                    if !fragment.is_empty() {
+                        fragments.push((path.clone(), target, fragment.to_owned()));
+                    }
+                }
+            }
+        }
+        ids.insert(path.clone(), doc_ids);
+    }
+    for (path, target, fragment) in fragments {
+        if let Some(doc_ids) = ids.get(&target) {
+            if !doc_ids.contains(&fragment) {
+                report.error(&path,
+                      lformat!("link to {target}#{fragment}: there is no element with this id",
+                               target = target,
+                               fragment = fragment));
+            }
+        }
+    }
+    if epub3 && navs.len() != 1 {
+        report.error(&opf_file,
+              lformat!("manifest must declare exactly one navigation document (found {n})",
+                       n = navs.len()));
+    }
+    report.errors
+}
@@ -0,0 +1,472 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+// Modifications of an (unzipped) EPUB archive generated by epub-builder,
+// for things it doesn't support: additional metadata in the OPF file, a
+// stable identifier, multiple creators, additional navigation and guide
+// elements, fixed-layout properties and font obfuscation.
+
+use error::{Error, Result, Source};
+use epub_check::{parse_xml, decode_entities, Element};
+
+use crowbook_text_processing::escape;
+use sha1::Sha1;
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Modifications to apply to an EPUB archive
+#[derive(Debug, Default)]
+pub struct EpubPatch {
+    /// Elements added at the end of the `<metadata>` element of the OPF file
+    pub metadata: Vec<String>,
+    /// Landmarks (`epub:type`, link relative to the OPF file, title),
+    /// replacing the ones of the navigation document
+    pub landmarks: Vec<(String, String, String)>,
+    /// References (type, link relative to the OPF file, title) added to the
+    /// `<guide>` element of the OPF file, unless it already has one of this type
+    pub guide: Vec<(String, String, String)>,
+    /// Elements added at the end of the body of the navigation document
+    /// (links must be relative to the OPF file, and will be adjusted)
+    pub nav: Vec<String>,
+    /// Fonts (relative to the OPF file) to obfuscate with the IDPF
+    /// algorithm, using the unique identifier of the book (EPUB 3 only)
+    pub obfuscated: Vec<String>,
+    /// Unique identifier replacing the random one set by epub-builder
+    pub identifier: Option<String>,
+    /// `<dc:date>` element replacing the one set by epub-builder
+    pub date: Option<String>,
+    /// Modification date replacing the one set by epub-builder (EPUB 3)
+    pub modified: Option<String>,
+    /// `<dc:creator>` elements (and the `<meta>` elements refining them)
+    /// replacing the one set by epub-builder
+    pub creators: Vec<String>,
+    /// Viewport (e.g. `width=1200, height=1600`) added to the content
+    /// documents of the spine, for fixed-layout EPUB files
+    pub viewport: Option<String>,
+    /// If true, pages of the spine are alternately set on the right and
+    /// left sides of two-page spreads
+    pub page_spreads: bool,
+    /// `page-progression-direction` attribute of the spine (EPUB 3), e.g. `rtl`
+    pub page_progression: Option<String>,
+}
+
+/// Returns a `<meta>` element for the OPF file, using the EPUB 3 syntax
+/// (`property`) or the EPUB 2 one (`name` and `content`)
+pub fn meta(epub3: bool, property: &str, value: &str) -> String {
+    if epub3 {
+        format!("<meta property=\"{}\">{}</meta>", property, escape::html(value))
+    } else {
+        format!("<meta name=\"{}\" content=\"{}\" />",
+                property,
+                escape::quotes(escape::html(value)))
+    }
+}
+
+fn read_file(dir: &Path, file: &str) -> Result<String> {
+    let mut content = String::new();
+    File::open(dir.join(file))
+        .and_then(|mut f| f.read_to_string(&mut content))
+        .map_err(|_| Error::file_not_found(Source::empty(),
+                                           lformat!("file from EPUB archive"),
+                                           file.to_owned()))?;
+    Ok(content)
+}
+
+fn write_file<C: AsRef<[u8]>>(dir: &Path, file: &str, content: C) -> Result<()> {
+    File::create(dir.join(file))
+        .and_then(|mut f| f.write_all(content.as_ref()))
+        .map_err(|e| Error::zipper(lformat!("could not write {file}: {error}",
+                                            file = file,
+                                            error = e)))
+}
+
+/// Obfuscates (or de-obfuscates) a font file with the IDPF algorithm: the
+/// first 1040 bytes are XORed with the SHA-1 digest of the unique
+/// identifier of the book (stripped of whitespace).
+pub fn obfuscate_font(data: &mut [u8], identifier: &str) {
+    let identifier: String = identifier.chars()
+        .filter(|c| !(*c == ' ' || *c == '\t' || *c == '\r' || *c == '\n'))
+        .collect();
+    let mut sha1 = Sha1::new();
+    sha1.update(identifier.as_bytes());
+    let key = sha1.digest().bytes();
+    for (i, byte) in data.iter_mut().take(1040).enumerate() {
+        *byte ^= key[i % key.len()];
+    }
+}
+
+/// Inserts `content` before the last occurrence of `tag` in `s`
+fn insert_before(file: &str, s: &mut String, tag: &str, content: &str) -> Result<()> {
+    match s.rfind(tag) {
+        Some(i) => {
+            s.insert_str(i, content);
+            Ok(())
+        },
+        None => {
+            Err(Error::zipper(lformat!("could not find {tag} in {file}",
+                                       tag = tag,
+                                       file = file)))
+        },
+    }
+}
+
+/// Replaces parts of a document, given by (non-overlapping) byte offsets
+fn replace_ranges(s: &str, mut ranges: Vec<((usize, usize), String)>) -> String {
+    ranges.sort_by_key(|&((start, _), _)| start);
+    let mut result = String::new();
+    let mut pos = 0;
+    for ((start, end), content) in ranges {
+        result.push_str(&s[pos..start]);
+        result.push_str(&content);
+        pos = end;
+    }
+    result.push_str(&s[pos..]);
+    result
+}
+
+/// Removes elements (parsed from `s`) from a document, with the line break
+/// following them
+fn remove_elements(s: &str, elements: &[&Element]) -> String {
+    let ranges = elements.iter()
+        .map(|e| {
+            let (start, mut end) = e.span;
+            if s[end..].starts_with('\n') {
+                end += 1;
+            }
+            ((start, end), String::new())
+        })
+        .collect();
+    replace_ranges(s, ranges)
+}
+
+/// Alternately sets the `page-spread-right` and `page-spread-left`
+/// properties on the `<itemref>` elements of the spine, starting with the
+/// right one.
+fn add_page_spreads(opf: &str) -> String {
+    let tag = "<itemref ";
+    let mut result = String::new();
+    let mut rest = opf;
+    let mut right = true;
+    while let Some(i) = rest.find(tag) {
+        result.push_str(&rest[..i + tag.len()]);
+        result.push_str(if right {
+            "properties=\"page-spread-right\" "
+        } else {
+            "properties=\"page-spread-left\" "
+        });
+        right = !right;
+        rest = &rest[i + tag.len()..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Sets the `page-progression-direction` attribute of the `<spine>` element,
+/// unless it is already set
+fn set_page_progression(opf: &str, direction: &str) -> String {
+    let tag = "<spine";
+    match opf.find(tag) {
+        Some(i) if !opf[i..].split('>').next().unwrap_or("").contains("page-progression-direction") => {
+            format!("{} page-progression-direction=\"{}\"{}",
+                    &opf[..i + tag.len()],
+                    direction,
+                    &opf[i + tag.len()..])
+        }
+        _ => opf.to_owned(),
+    }
+}
+
+/// Parses an XML file of the archive
+fn parse_file(file: &str, content: &str) -> Result<Element> {
+    parse_xml(content).map_err(|msg| Error::zipper(lformat!("{file} is not well-formed: {msg}",
+                                                        file = file,
+                                                        msg = msg)))
+}
+
+/// Returns the `<dc:identifier>` element containing the unique identifier
+/// declared in the OPF file
+fn unique_identifier<'a>(file: &str, package: &'a Element) -> Result<&'a Element> {
+    let id = package.attr("unique-identifier").unwrap_or("");
+    package.find_all("dc:identifier")
+        .into_iter()
+        .find(|e| e.attr("id") == Some(id))
+        .ok_or_else(|| Error::zipper(lformat!("could not find the unique identifier of the book \
+                                               in {file}",
+                                              file = file)))
+}
+
+/// Normalizes the identifier of a book: bare ISBNs and UUIDs are
+/// prefixed with `urn:isbn:` and `urn:uuid:`, other identifiers (URIs)
+/// are kept as is.
+pub fn normalize_identifier(identifier: &str) -> String {
+    let identifier = identifier.trim();
+    let digits: String = identifier.chars().filter(|c| *c != '-' && *c != ' ').collect();
+    let is_isbn = (digits.len() == 10 || digits.len() == 13) &&
+                  digits.chars()
+        .enumerate()
+        .all(|(i, c)| c.is_digit(10) || (i == 9 && digits.len() == 10 && (c == 'X' || c == 'x')));
+    let is_uuid = identifier.len() == 36 &&
+                  identifier.chars()
+        .enumerate()
+        .all(|(i, c)| if i == 8 || i == 13 || i == 18 || i == 23 {
+            c == '-'
+        } else {
+            c.is_digit(16)
+        });
+    if is_isbn {
+        format!("urn:isbn:{}", digits)
+    } else if is_uuid {
+        format!("urn:uuid:{}", identifier.to_lowercase())
+    } else {
+        identifier.to_owned()
+    }
+}
+
+impl EpubPatch {
+    /// Returns true if there is nothing to modify
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty() && self.landmarks.is_empty() && self.nav.is_empty() &&
+        self.guide.is_empty() && self.obfuscated.is_empty() && self.identifier.is_none() &&
+        self.date.is_none() && self.modified.is_none() && self.creators.is_empty() &&
+        self.viewport.is_none() && !self.page_spreads && self.page_progression.is_none()
+    }
+
+    /// Applies the modifications to the unzipped archive in `dir`
+    pub fn apply(&self, dir: &Path) -> Result<()> {
+        let container = read_file(dir, "META-INF/container.xml")?;
+        let opf_file = parse_xml(&container)
+            .ok()
+            .and_then(|c| {
+                c.find_all("rootfile")
+                    .first()
+                    .and_then(|e| e.attr("full-path"))
+                    .map(|s| s.to_owned())
+            })
+            .ok_or_else(|| Error::zipper(lformat!("could not find the OPF file in the EPUB \
+                                                   archive")))?;
+        let opf_dir = match opf_file.rfind('/') {
+            Some(i) => opf_file[..i + 1].to_owned(),
+            None => String::new(),
+        };
+        let original_opf = read_file(dir, &opf_file)?;
+        let mut opf = original_opf.clone();
+
+        if let Some(ref identifier) = self.identifier {
+            let package = parse_file(&opf_file, &opf)?;
+            let new = escape::html(identifier.as_str()).into_owned();
+            // The identifier also appears in the NCX table of contents
+            for item in package.find_all("item") {
+                if item.attr("media-type") == Some("application/x-dtbncx+xml") {
+                    let ncx_file = format!("{}{}", opf_dir, item.attr("href").unwrap_or(""));
+                    let ncx = read_file(dir, &ncx_file)?;
+                    let ranges = parse_file(&ncx_file, &ncx)?
+                        .find_all("meta")
+                        .into_iter()
+                        .filter(|e| e.attr("name") == Some("dtb:uid"))
+                        .map(|e| {
+                            (e.span,
+                             format!("<meta name=\"dtb:uid\" content=\"{}\"/>",
+                                     escape::quotes(new.as_str())))
+                        })
+                        .collect();
+                    write_file(dir, &ncx_file, replace_ranges(&ncx, ranges))?;
+                }
+            }
+            let element = unique_identifier(&opf_file, &package)?;
+            opf = replace_ranges(&opf, vec![(element.inner, new)]);
+        }
+
+        if let Some(ref date) = self.date {
+            opf = remove_elements(&opf, &parse_file(&opf_file, &opf)?.find_all("dc:date"));
+            insert_before(&opf_file, &mut opf, "</metadata>", &format!("{}\n", date))?;
+        }
+
+        if let Some(ref modified) = self.modified {
+            let ranges = parse_file(&opf_file, &opf)?
+                .find_all("meta")
+                .into_iter()
+                .filter(|e| e.attr("property") == Some("dcterms:modified"))
+                .map(|e| (e.inner, escape::html(modified.as_str()).into_owned()))
+                .collect();
+            opf = replace_ranges(&opf, ranges);
+        }
+
+        if !self.creators.is_empty() {
+            let package = parse_file(&opf_file, &opf)?;
+            let creators = package.find_all("dc:creator");
+            let refines: Vec<String> = creators.iter()
+                .filter_map(|e| e.attr("id"))
+                .map(|id| format!("#{}", id))
+                .collect();
+            let mut elements = package.find_all("meta")
+                .into_iter()
+                .filter(|e| e.attr("refines").map_or(false, |r| refines.iter().any(|id| id == r)))
+                .collect::<Vec<_>>();
+            elements.extend(creators);
+            opf = remove_elements(&opf, &elements);
+        }
+
+        if let Some(ref viewport) = self.viewport {
+            let package = parse_file(&opf_file, &opf)?;
+            let items = package.find_all("item");
+            for itemref in package.find_all("itemref") {
+                let href = items.iter()
+                    .find(|item| item.attr("id").is_some() && item.attr("id") == itemref.attr("idref"))
+                    .and_then(|item| item.attr("href"));
+                if let Some(href) = href {
+                    let file = format!("{}{}", opf_dir, href);
+                    let mut doc = read_file(dir, &file)?;
+                    if !doc.contains("name=\"viewport\"") {
+                        insert_before(&file,
+                                      &mut doc,
+                                      "</head>",
+                                      &format!("<meta name=\"viewport\" content=\"{}\" />\n",
+                                               viewport))?;
+                        write_file(dir, &file, &doc)?;
+                    }
+                }
+            }
+        }
+        if self.page_spreads {
+            opf = add_page_spreads(&opf);
+        }
+        if let Some(ref direction) = self.page_progression {
+            opf = set_page_progression(&opf, direction);
+        }
+
+        if !self.guide.is_empty() {
+            let existing: Vec<String> = parse_file(&opf_file, &opf)?
+                .find_all("reference")
+                .iter()
+                .filter_map(|e| e.attr("type"))
+                .map(|t| t.to_owned())
+                .collect();
+            let mut content = String::new();
+            for &(ref kind, ref href, ref title) in &self.guide {
+                if !existing.contains(kind) {
+                    content.push_str(&format!("<reference type=\"{}\" href=\"{}\" title=\"{}\" />\n",
+                                              kind,
+                                              href,
+                                              escape::quotes(escape::html(title.as_str()))));
+                }
+            }
+            if !content.is_empty() {
+                if opf.contains("</guide>") {
+                    insert_before(&opf_file, &mut opf, "</guide>", &content)?;
+                } else {
+                    insert_before(&opf_file,
+                                  &mut opf,
+                                  "</package>",
+                                  &format!("<guide>\n{}</guide>\n", content))?;
+                }
+            }
+        }
+
+        if !self.landmarks.is_empty() || !self.nav.is_empty() {
+            let nav_href = parse_file(&opf_file, &opf)?
+                .find_all("item")
+                .into_iter()
+                .find(|item| {
+                    item.attr("properties")
+                        .map(|p| p.split_whitespace().any(|p| p == "nav"))
+                        .unwrap_or(false)
+                })
+                .and_then(|item| item.attr("href"))
+                .map(|s| s.to_owned())
+                .ok_or_else(|| Error::zipper(lformat!("could not find the navigation document \
+                                                       in the EPUB archive")))?;
+            let nav_file = format!("{}{}", opf_dir, nav_href);
+            // Links in the navigation document are relative to it, not to the OPF file
+            let prefix = "../".repeat(nav_href.matches('/').count());
+            let mut nav = read_file(dir, &nav_file)?;
+
+            let mut content = String::new();
+            if !self.landmarks.is_empty() {
+                // epub-builder always writes a landmarks element, which only
+                // lists files it knows the type of: replace it
+                let document = parse_file(&nav_file, &nav)?;
+                let landmarks = document.find_all("nav")
+                    .into_iter()
+                    .filter(|e| e.attr("epub:type") == Some("landmarks"))
+                    .collect::<Vec<_>>();
+                nav = remove_elements(&nav, &landmarks);
+                content.push_str("<nav epub:type=\"landmarks\" hidden=\"hidden\">\n<ol>\n");
+                for &(ref epub_type, ref href, ref title) in &self.landmarks {
+                    content.push_str(&format!("<li><a epub:type=\"{}\" href=\"{}{}\">{}</a></li>\n",
+                                              epub_type,
+                                              prefix,
+                                              href,
+                                              escape::html(title.as_str())));
+                }
+                content.push_str("</ol>\n</nav>\n");
+            }
+            for element in &self.nav {
+                content.push_str(&element.replace("href=\"", &format!("href=\"{}", prefix)));
+                content.push('\n');
+            }
+            insert_before(&nav_file, &mut nav, "</body>", &content)?;
+            write_file(dir, &nav_file, &nav)?;
+        }
+
+        if !self.obfuscated.is_empty() {
+            let package = parse_file(&opf_file, &opf)?;
+            let identifier = decode_entities(unique_identifier(&opf_file, &package)?.text.trim());
+            let mut encryption = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<encryption xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\"
+            xmlns:enc=\"http://www.w3.org/2001/04/xmlenc#\">
+");
+            for font in &self.obfuscated {
+                let file = format!("{}{}", opf_dir, font);
+                let mut data = vec![];
+                File::open(dir.join(&file))
+                    .and_then(|mut f| f.read_to_end(&mut data))
+                    .map_err(|_| Error::file_not_found(Source::empty(),
+                                                       lformat!("font"),
+                                                       file.clone()))?;
+                obfuscate_font(&mut data, &identifier);
+                write_file(dir, &file, &data)?;
+                encryption.push_str(&format!("  <enc:EncryptedData>
+    <enc:EncryptionMethod Algorithm=\"http://www.idpf.org/2008/embedding\" />
+    <enc:CipherData>
+      <enc:CipherReference URI=\"{}\" />
+    </enc:CipherData>
+  </enc:EncryptedData>
+",
+                                             file));
+            }
+            encryption.push_str("</encryption>\n");
+            write_file(dir, "META-INF/encryption.xml", &encryption)?;
+        }
+
+        if !self.creators.is_empty() || !self.metadata.is_empty() {
+            let mut content = self.creators
+                .iter()
+                .chain(self.metadata.iter())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n");
+            content.push('\n');
+            insert_before(&opf_file, &mut opf, "</metadata>", &content)?;
+        }
+        if opf != original_opf {
+            write_file(dir, &opf_file, &opf)?;
+        }
+        Ok(())
+    }
+}
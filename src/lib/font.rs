@@ -0,0 +1,116 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+// Font files embedded in documents
+
+use std::path::Path;
+
+/// A font file, with the family, weight and style it provides
+#[derive(Debug, PartialEq, Clone)]
+pub struct FontFile {
+    pub file: String,
+    pub family: String,
+    pub weight: String,
+    pub style: String,
+}
+
+/// Weights that can be guessed from a font file name, longest names first
+static WEIGHTS: &'static [(&'static str, &'static str)] = &[("extralight", "200"),
+                                                            ("ultralight", "200"),
+                                                            ("semibold", "600"),
+                                                            ("demibold", "600"),
+                                                            ("extrabold", "800"),
+                                                            ("ultrabold", "800"),
+                                                            ("medium", "500"),
+                                                            ("black", "900"),
+                                                            ("heavy", "900"),
+                                                            ("light", "300"),
+                                                            ("thin", "100"),
+                                                            ("bold", "bold")];
+
+impl FontFile {
+    /// Parses a font entry, which is either a file name, in which case the
+    /// family, weight and style are guessed from it (e.g. `Lora-BoldItalic.ttf`
+    /// is Lora, bold, italic), or of the form `file; family; weight; style`,
+    /// where weight and style can be omitted.
+    pub fn parse(entry: &str) -> FontFile {
+        let parts: Vec<_> = entry.split(';').map(|s| s.trim()).collect();
+        let file = parts[0].to_owned();
+        let stem = Path::new(&file)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut split = stem.splitn(2, |c| c == '-' || c == '_');
+        let guessed_family = split.next().unwrap_or("").to_owned();
+        let variant = split.next().unwrap_or("").to_lowercase();
+        let guessed_weight = WEIGHTS.iter()
+            .find(|&&(name, _)| variant.contains(name))
+            .map(|&(_, weight)| weight)
+            .unwrap_or("normal");
+        let guessed_style = if variant.contains("italic") || variant.contains("oblique") {
+            "italic"
+        } else {
+            "normal"
+        };
+
+        let get = |i: usize, default: &str| -> String {
+            match parts.get(i) {
+                Some(s) if !s.is_empty() => s.to_string(),
+                _ => default.to_owned(),
+            }
+        };
+        FontFile {
+            family: get(1, &guessed_family),
+            weight: get(2, guessed_weight),
+            style: get(3, guessed_style),
+            file: file,
+        }
+    }
+
+    /// Returns the media type of this font, or `None` if it isn't a
+    /// supported font format
+    pub fn media_type(&self, epub3: bool) -> Option<&'static str> {
+        let extension = Path::new(&self.file)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match (extension.as_ref(), epub3) {
+            ("otf", true) => Some("font/otf"),
+            ("ttf", true) => Some("font/ttf"),
+            ("otf", false) | ("ttf", false) => Some("application/vnd.ms-opentype"),
+            ("woff", true) => Some("font/woff"),
+            ("woff", false) => Some("application/font-woff"),
+            ("woff2", _) => Some("font/woff2"),
+            _ => None,
+        }
+    }
+
+    /// Returns the `@font-face` CSS rule for this font, located at `url`
+    pub fn css(&self, url: &str) -> String {
+        format!("@font-face {{
+    font-family: \"{}\";
+    font-weight: {};
+    font-style: {};
+    src: url(\"{}\");
+}}
+",
+                self.family.replace('"', "\\\""),
+                self.weight,
+                self.style,
+                url)
+    }
+}
@@ -417,6 +417,13 @@ impl<'a> HtmlRenderer<'a> {
         where T: AsMut<HtmlRenderer<'a>> + AsRef<HtmlRenderer<'a>> + Renderer
     {
         match *token {
+            Token::Annotation(Data::Lang(ref lang), ref v) => {
+                let content = this.as_mut().render_vec(v)?;
+                // xml:lang is needed for XHTML (EPUB), and allowed in HTML if it matches lang
+                Ok(format!("<span lang = \"{lang}\" xml:lang = \"{lang}\">{content}</span>",
+                           lang = escape::quotes(lang.as_str()),
+                           content = content))
+            }
             Token::Annotation(ref annotation, ref v) => {
                 let content = this.as_mut().render_vec(v)?;
                 if this.as_ref().proofread {
@@ -647,7 +654,7 @@ impl<'a> HtmlRenderer<'a> {
 {
     "@context": "http://schema.org/",
     "@type": "Book",
-    "author": "{{{author}}}",
+    "author": [{{#authors}}{{^first}}, {{/first}}{"@type": "Person", "name": "{{{name_raw}}}"}{{/authors}}],
     "name": "{{{title}}}",
     {{#has_version}}"version": "{{{version}}}",{{/has_version}}
     {{#has_subtitle}}"alternateName": "{{{subtitle}}}",{{/has_subtitle}}
@@ -25,6 +25,8 @@ use resource_handler;
 use renderer::Renderer;
 use parser::Parser;
 use book_renderer::BookRenderer;
+use contributor;
+use lang;
 use text_view::view_as_text;
 
 use std::io;
@@ -306,11 +308,18 @@ impl<'a> HtmlDirRenderer<'a> {
             String::new()
         };
 
+        let lang = self.html.book.options.get_str("lang").unwrap();
+        let authors: Vec<String> = self.html.book
+            .authors()
+            .into_iter()
+            .map(|a| a.name)
+            .collect();
         content = format!("<h2 class = 'author'>{author}</h2>
 <h1 class = 'title'>{title}</h1>
 <h2 class = 'subtitle'>{subtitle}</h2>
 {content}",
-                          author = self.html.book.options.get_str("author")?,
+                          author = contributor::join_names(&authors,
+                                                           &lang::get_str(lang, "and")),
                           title = self.html.book.options.get_str("title")?,
                           content = content,
                           subtitle = self.html.book.options.get_str("subtitle")
@@ -0,0 +1,248 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Parsing of BCP 47 language tags, and their equivalents for LaTeX
+//! language packages
+
+/// Languages, with their babel and polyglossia names.
+///
+/// An empty string means the language is not supported by this package.
+static LANGUAGES: &'static [(&'static str, &'static str, &'static str)] = &[
+    ("af", "afrikaans", "afrikaans"),
+    ("am", "", "amharic"),
+    ("ar", "arabic", "arabic"),
+    ("ast", "asturian", "asturian"),
+    ("be", "belarusian", "belarusian"),
+    ("bg", "bulgarian", "bulgarian"),
+    ("bn", "", "bengali"),
+    ("bo", "", "tibetan"),
+    ("br", "breton", "breton"),
+    ("ca", "catalan", "catalan"),
+    ("cs", "czech", "czech"),
+    ("cy", "welsh", "welsh"),
+    ("da", "danish", "danish"),
+    ("de", "ngerman", "german"),
+    ("dsb", "lowersorbian", "sorbian"),
+    ("dv", "", "divehi"),
+    ("el", "greek", "greek"),
+    ("en", "english", "english"),
+    ("eo", "esperanto", "esperanto"),
+    ("es", "spanish", "spanish"),
+    ("et", "estonian", "estonian"),
+    ("eu", "basque", "basque"),
+    ("fa", "", "persian"),
+    ("fi", "finnish", "finnish"),
+    ("fr", "french", "french"),
+    ("fur", "friulan", "friulian"),
+    ("ga", "irish", "irish"),
+    ("gd", "scottish", "scottish"),
+    ("gl", "galician", "galician"),
+    ("grc", "greek", "greek"),
+    ("he", "hebrew", "hebrew"),
+    ("hi", "", "hindi"),
+    ("hr", "croatian", "croatian"),
+    ("hsb", "uppersorbian", "sorbian"),
+    ("hu", "magyar", "hungarian"),
+    ("hy", "", "armenian"),
+    ("ia", "interlingua", "interlingua"),
+    ("id", "indonesian", "bahasai"),
+    ("is", "icelandic", "icelandic"),
+    ("it", "italian", "italian"),
+    ("ja", "", "japanese"),
+    ("ka", "", "georgian"),
+    ("km", "", "khmer"),
+    ("kn", "", "kannada"),
+    ("ko", "", "korean"),
+    ("ku", "kurmanji", "kurdish"),
+    ("la", "latin", "latin"),
+    ("lo", "", "lao"),
+    ("lt", "lithuanian", "lithuanian"),
+    ("lv", "latvian", "latvian"),
+    ("mk", "macedonian", "macedonian"),
+    ("ml", "", "malayalam"),
+    ("mn", "mongolian", "mongolian"),
+    ("mr", "", "marathi"),
+    ("ms", "malay", "bahasam"),
+    ("nb", "norsk", "norwegian"),
+    ("nl", "dutch", "dutch"),
+    ("nn", "nynorsk", "norwegian"),
+    ("no", "norsk", "norwegian"),
+    ("oc", "occitan", "occitan"),
+    ("pl", "polish", "polish"),
+    ("pms", "piedmontese", "piedmontese"),
+    ("pt", "portuguese", "portuguese"),
+    ("rm", "romansh", "romansh"),
+    ("ro", "romanian", "romanian"),
+    ("ru", "russian", "russian"),
+    ("sa", "", "sanskrit"),
+    ("se", "samin", "sami"),
+    ("sk", "slovak", "slovak"),
+    ("sl", "slovene", "slovenian"),
+    ("sq", "albanian", "albanian"),
+    ("sr", "serbian", "serbian"),
+    ("sv", "swedish", "swedish"),
+    ("syr", "", "syriac"),
+    ("ta", "", "tamil"),
+    ("te", "", "telugu"),
+    ("th", "thai", "thai"),
+    ("tk", "turkmen", "turkmen"),
+    ("tr", "turkish", "turkish"),
+    ("uk", "ukrainian", "ukrainian"),
+    ("ur", "", "urdu"),
+    ("vi", "vietnamese", "vietnamese"),
+    ("zh", "", "chinese"),
+];
+
+/// Scripts that are written from right to left
+static RTL_SCRIPTS: &'static [&'static str] = &["Arab", "Hebr", "Syrc", "Thaa", "Nkoo", "Adlm",
+                                                  "Samr", "Mand"];
+
+/// Languages that are written from right to left (with their default script)
+static RTL_LANGUAGES: &'static [&'static str] = &["ar", "he", "fa", "ur", "ps", "yi", "dv", "ug",
+                                                    "sd", "ckb", "syr", "iw"];
+
+/// A BCP 47 language tag, e.g. `pt-BR` or `sr-Latn`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LangTag {
+    /// Language subtag, in lowercase (e.g. `pt`)
+    pub language: String,
+    /// Script subtag, in title case (e.g. `Latn`), if any
+    pub script: Option<String>,
+    /// Region subtag, in uppercase (e.g. `BR`), if any
+    pub region: Option<String>,
+}
+
+impl LangTag {
+    /// Parses a language tag.
+    ///
+    /// Underscores are accepted as separators (e.g. `pt_BR`). Variants and
+    /// extensions are accepted but ignored. Returns `None` if the tag isn't
+    /// well-formed.
+    pub fn parse(tag: &str) -> Option<LangTag> {
+        let mut subtags = tag.trim().split(|c| c == '-' || c == '_');
+        let language = match subtags.next() {
+            Some(s) if s.len() >= 2 && s.len() <= 8 && is_alpha(s) => s.to_lowercase(),
+            _ => return None,
+        };
+        let mut res = LangTag {
+            language: language,
+            script: None,
+            region: None,
+        };
+        // Position of the next expected subtag: 0 for script, 1 for region, 2 for variants
+        let mut position = 0;
+        for subtag in subtags {
+            if subtag.is_empty() || subtag.len() > 8 ||
+               !subtag.chars().all(|c| c.is_alphanumeric() && (c as u32) < 128) {
+                return None;
+            }
+            if subtag.len() == 1 {
+                // Extensions and private use: ignore the rest of the tag
+                break;
+            } else if position == 0 && subtag.len() == 4 && is_alpha(subtag) {
+                let mut script = subtag[..1].to_uppercase();
+                script.push_str(&subtag[1..].to_lowercase());
+                res.script = Some(script);
+                position = 1;
+            } else if position <= 1 &&
+                      ((subtag.len() == 2 && is_alpha(subtag)) ||
+                       (subtag.len() == 3 && subtag.chars().all(|c| c.is_digit(10)))) {
+                res.region = Some(subtag.to_uppercase());
+                position = 2;
+            } else if position == 0 && subtag.len() == 3 && is_alpha(subtag) {
+                // Extended language subtag (e.g. `zh-yue`): ignored
+            } else {
+                position = 2;
+            }
+        }
+        Some(res)
+    }
+
+    /// Returns true if the language is written from right to left
+    pub fn is_rtl(&self) -> bool {
+        match self.script {
+            Some(ref script) => RTL_SCRIPTS.contains(&script.as_str()),
+            None => RTL_LANGUAGES.contains(&self.language.as_str()),
+        }
+    }
+
+    /// Returns the name of this language for babel, if it is supported
+    pub fn babel(&self) -> Option<&'static str> {
+        let region = self.region.as_ref().map(|s| s.as_str());
+        let script = self.script.as_ref().map(|s| s.as_str());
+        let name = match (self.language.as_str(), region, script) {
+            ("en", Some("US"), _) => "american",
+            ("en", Some("GB"), _) => "british",
+            ("en", Some("AU"), _) => "australian",
+            ("en", Some("CA"), _) => "canadian",
+            ("en", Some("NZ"), _) => "newzealand",
+            ("pt", Some("BR"), _) => "brazilian",
+            ("de", Some("AT"), _) => "naustrian",
+            ("de", Some("CH"), _) => "nswissgerman",
+            ("fr", Some("CA"), _) => "canadien",
+            ("sr", _, Some("Cyrl")) => "serbianc",
+            _ => {
+                match self.names() {
+                    Some(&(_, babel, _)) if !babel.is_empty() => babel,
+                    _ => return None,
+                }
+            }
+        };
+        Some(name)
+    }
+
+    /// Returns the name of this language for polyglossia, with the options
+    /// to pass to it (e.g. `variant=brazilian`), if it is supported
+    pub fn polyglossia(&self) -> Option<(&'static str, &'static str)> {
+        let name = match self.names() {
+            Some(&(_, _, polyglossia)) => polyglossia,
+            None => return None,
+        };
+        let region = self.region.as_ref().map(|s| s.as_str());
+        let script = self.script.as_ref().map(|s| s.as_str());
+        let options = match (self.language.as_str(), region, script) {
+            ("en", Some("US"), _) => "variant=american",
+            ("en", Some("GB"), _) => "variant=british",
+            ("en", Some("AU"), _) => "variant=australian",
+            ("en", Some("CA"), _) => "variant=canadian",
+            ("en", Some("NZ"), _) => "variant=newzealand",
+            ("pt", Some("BR"), _) => "variant=brazilian",
+            ("de", Some("AT"), _) => "variant=austrian",
+            ("de", Some("CH"), _) => "variant=swiss",
+            ("nn", _, _) => "variant=nynorsk",
+            ("hsb", _, _) => "variant=upper",
+            ("dsb", _, _) => "variant=lower",
+            ("grc", _, _) => "variant=ancient",
+            ("sr", _, Some("Latn")) => "script=Latin",
+            ("zh", Some("TW"), _) | ("zh", Some("HK"), _) | ("zh", _, Some("Hant")) => {
+                "variant=traditional"
+            }
+            _ => "",
+        };
+        Some((name, options))
+    }
+
+    /// Returns the entry of the language in the `LANGUAGES` table
+    fn names(&self) -> Option<&'static (&'static str, &'static str, &'static str)> {
+        LANGUAGES.iter().find(|&&(code, _, _)| code == self.language)
+    }
+}
+
+/// Returns true if a subtag only contains ASCII letters
+fn is_alpha(s: &str) -> bool {
+    s.chars().all(|c| c.is_alphabetic() && (c as u32) < 128)
+}
@@ -26,6 +26,11 @@ use renderer::Renderer;
 use parser::Parser;
 use book_renderer::BookRenderer;
 use syntax::Syntax;
+use lang_tag::LangTag;
+use misc;
+use typst::TypstRenderer;
+#[cfg(feature = "pdf")]
+use pdf::PdfRenderer;
 
 use crowbook_text_processing::escape;
 
@@ -36,6 +41,7 @@ use std::io;
 use std::io::Read;
 use std::fmt::Write;
 use std::borrow::Cow;
+use std::path::Path;
 
 
 /// LaTeX renderer
@@ -52,6 +58,14 @@ pub struct LatexRenderer<'a> {
     syntax: Option<Syntax>,
     hyperref: bool,
     enum_level: usize,
+    /// Line of the generated LaTeX file where the output of each Markdown
+    /// block starts, with the source of this block
+    source_map: Vec<(u32, Source)>,
+    /// Scripts whose runs of text use a fallback font
+    scripts: Vec<Script>,
+    /// Languages of passages in another language than the main one, with
+    /// their babel or polyglossia name and options
+    languages: Vec<(&'static str, &'static str)>,
 }
 
 impl<'a> LatexRenderer<'a> {
@@ -75,11 +89,14 @@ impl<'a> LatexRenderer<'a> {
             escape: true,
             first_letter: false,
             first_paragraph: true,
-            is_short: book.options.get_str("tex.class").unwrap() == "article",
+            is_short: is_short_class(book.options.get_str("tex.class").unwrap()),
             proofread: false,
             syntax: syntax,
             hyperref: book.options.get_bool("tex.hyperref").unwrap(),
             enum_level: 0,
+            source_map: vec![],
+            scripts: fallback_scripts(book),
+            languages: vec![],
         }
     }
 
@@ -92,6 +109,7 @@ impl<'a> LatexRenderer<'a> {
 
     /// Render pdf to a file
     pub fn render_pdf(&mut self, to: &mut io::Write) -> Result<String> {
+        self.book.check_fonts()?;
         let content = self.render_book()?;
         debug!("{}", lformat!("Attempting to run LaTeX on generated file"));
         let mut zipper = Zipper::new(&self.book.options.get_path("crowbook.temp_dir")
@@ -115,8 +133,14 @@ impl<'a> LatexRenderer<'a> {
         }
 
 
+        // Keep the source map along the LaTeX file, to make sense of it
+        zipper.write("result.map", self.source_map_content().as_bytes(), false)?;
+
+        let source_map = &self.source_map;
         zipper.generate_pdf(self.book.options.get_str("tex.command").unwrap(),
                             "result.tex",
+                            self.book.options.get_i32("tex.max_runs").unwrap() as u32,
+                            |line| source_of_line(source_map, line),
                             to)
     }
 
@@ -136,81 +160,71 @@ impl<'a> LatexRenderer<'a> {
             content.push_str("\\tableofcontents\n");
         }
 
+        self.languages.clear();
         for (i, chapter) in self.book.chapters.iter().enumerate() {
             self.handler.add_link(chapter.filename.as_ref(), format!("chapter-{}", i));
         }
         
+        // Offset in `content` of the output of each Markdown block, with its source
+        let mut offsets: Vec<(usize, Source)> = vec![];
         for (i, chapter) in self.book.chapters.iter().enumerate() {
             let n = chapter.number;
             self.current_chapter = n;
             let v = &chapter.content;
             self.source = Source::new(chapter.filename.as_str());
+            // Lines are only known if the chapter wasn't modified after parsing
+            let has_lines = chapter.lines.len() == v.len();
+            let source_of = |j: usize| {
+                let mut source = Source::new(chapter.filename.as_str());
+                if has_lines {
+                    source.set_line(chapter.lines[j]);
+                }
+                source
+            };
             let mut offset = 0;
             if !v.is_empty() && v[0].is_header() {
+                offsets.push((content.len(), source_of(0)));
                 content.push_str(&self.render_token(&v[0])?);
                 offset = 1;
             }
             write!(content,
                    "\\label{{chapter-{}}}\n",
                    i)?;
-            content.push_str(&self.render_vec(&v[offset..])?);
+            for (j, token) in v.iter().enumerate().skip(offset) {
+                offsets.push((content.len(), source_of(j)));
+                content.push_str(&self.render_token(token)?);
+            }
         }
         self.source = Source::empty();
 
 
-        let tex_lang = String::from(match self.book.options.get_str("lang").unwrap() {
-            "af" => "afrikaans",
-            "sq" => "albanian",
-            "eu" => "basque",
-            "bg" => "bulgarian",
-            "ca" => "catalan",
-            "hr" => "croatian",
-            "cs" => "czech",
-            "da" => "danish",
-            "nl" => "dutch",
-            "en" => "english",
-            "eo" => "esperanto",
-            "et" => "estonian",
-            "fi" => "finnish",
-            "fr" => "francais",
-            "gl" => "galician",
-            "el" => "greek",
-            "de" => "ngerman",
-            "he" => "hebrew",
-            "hu" => "hungarian",
-            "it" => "italian",
-            "is" => "icelandic",
-            "id" => "indonesian",
-            "ga" => "irish",
-            "la" => "latin",
-            "ms" => "malay",
-            "nn" => "norsk",
-            "pl" => "polish",
-            "pt" => "portuguese",
-            "ro" => "romanian",
-            "ru" => "russian",
-            "gd" => "scottish",
-            "sr" => "serbian",
-            "sk" => "slovak",
-            "sl" => "slovene",
-            "es" => "spanish",
-            "sw" => "swedish",
-            "tr" => "turkish",
-            "uk" => "ukrainian",
-            "cy" => "welsh",
-            _ => {
-                warn!("{}", lformat!("LaTeX: can't find a tex equivalent for lang '{lang}', \
-                                      fallbacking on english",
-                                     lang = self.book.options.get_str("lang").unwrap()));
-                "english"
-            }
-        });
+        // Main language, and whether the engine uses polyglossia or babel
+        let command = self.book.options.get_str("tex.command").unwrap();
+        let unicode = is_unicode_engine(command);
+        let lang = self.book.options.get_str("lang").unwrap();
+        let tag = LangTag::parse(lang);
+        let babel = tag.as_ref().and_then(|t| t.babel());
+        let polyglossia = tag.as_ref().and_then(|t| t.polyglossia());
+        if (unicode && polyglossia.is_none()) || (!unicode && babel.is_none()) {
+            warn!("{}", lformat!("LaTeX: can't find a tex equivalent for lang '{lang}', \
+                                  fallbacking on english",
+                                 lang = lang));
+        }
+        // Polyglossia sets the direction itself for right-to-left languages,
+        // but it must be forced if `rendering.direction` is set to `rtl`
+        let rtl = self.book.is_rtl();
+        let rtl_lang = tag.as_ref().map_or(false, |t| t.is_rtl());
+        if rtl && !unicode {
+            warn!("{}", lformat!("LaTeX: right-to-left text requires xelatex or lualatex"));
+        }
+        let tex_lang = babel.unwrap_or("english");
+        let (polyglossia_lang, polyglossia_options) = polyglossia.unwrap_or(("english", ""));
 
         let template = compile_str(self.book.get_template("tex.template")?.as_ref(),
                                    &self.book.source,
                                    "tex.template")?;
         let mut data = self.book.get_metadata(|s| self.render_vec(&Parser::new().parse_inline(s)?))?
-            .insert_str("content", content)
+            .insert_str("content", content.clone())
             .insert_str("class", self.book.options.get_str("tex.class").unwrap())
             .insert_bool("tex_title", self.book.options.get_bool("tex.title").unwrap())
             .insert_str("papersize", self.book.options.get_str("tex.paper.size").unwrap())
@@ -220,6 +234,133 @@ impl<'a> LatexRenderer<'a> {
             .insert_bool("use_codeblocks", self.book.features.codeblock)
             .insert_bool("use_images", self.book.features.image)
             .insert_str("tex_lang", tex_lang);
+
+        // Other languages used in the document
+        let mut babel_languages = String::new();
+        let mut other_languages = String::new();
+        for &(name, options) in &self.languages {
+            if unicode {
+                if options.is_empty() {
+                    other_languages.push_str(&format!("\\setotherlanguage{{{}}}\n", name));
+                } else {
+                    other_languages.push_str(&format!("\\setotherlanguage[{}]{{{}}}\n",
+                                                      options,
+                                                      name));
+                }
+            } else {
+                babel_languages.push_str(name);
+                babel_languages.push(',');
+            }
+        }
+        babel_languages.push_str(tex_lang);
+        data = data.insert_str("babel_languages", babel_languages)
+            .insert_str("other_languages", other_languages)
+            .insert_bool("has_polyglossia_options", !polyglossia_options.is_empty())
+            .insert_str("polyglossia_options", polyglossia_options)
+            .insert_bool("force_rtl", rtl && !rtl_lang && unicode);
+
+        // Class options: KOMA-Script and memoir classes handle paper size
+        // themselves, instead of using the geometry package
+        let class = self.book.options.get_str("tex.class").unwrap();
+        let family = ClassFamily::from_class(class);
+        let paper_size = self.book.options.get_str("tex.paper.size").unwrap();
+        let layout = PrintLayout::new(self.book)?;
+        let gutter = self.book.options.get_str("tex.margin.gutter").ok();
+        let mut class_options = vec![];
+        if let Ok(tex_font_size) = self.book.options.get_i32("tex.font.size") {
+            class_options.push(format!("{}pt", tex_font_size));
+        }
+        let typearea = family == ClassFamily::Koma &&
+                       ["left", "right", "inside", "outside"].iter()
+                           .all(|m| self.book.options.get_str(&format!("tex.margin.{}", m)).is_err());
+        match family {
+            ClassFamily::Koma => {
+                match layout.custom_trim {
+                    Some((width, height)) => class_options.push(format!("paper={}:{}", width, height)),
+                    None => class_options.push(format!("paper={}", paper_size.trim_right_matches("paper"))),
+                }
+                if typearea {
+                    class_options.push(String::from("DIV=calc"));
+                    if let Some(gutter) = gutter {
+                        class_options.push(format!("BCOR={}", gutter));
+                    }
+                }
+            }
+            ClassFamily::Memoir => {
+                if layout.custom_trim.is_none() {
+                    class_options.push(paper_size.to_owned());
+                }
+            }
+            ClassFamily::Standard => (),
+        }
+        data = data.insert_bool("has_class_options", !class_options.is_empty())
+            .insert_str("class_options", class_options.join(","))
+            .insert_bool("standard", family == ClassFamily::Standard)
+            .insert_bool("koma", family == ClassFamily::Koma)
+            .insert_bool("memoir", family == ClassFamily::Memoir)
+            .insert_bool("geometry", family == ClassFamily::Standard || (family == ClassFamily::Koma && !typearea))
+            .insert_str("chapter_style", self.book.options.get_str("tex.chapter.style").unwrap());
+
+        // Print layout: trim size, gutter, bleed and crop marks
+        data = match layout.custom_trim {
+            Some((width, height)) => {
+                data.insert_bool("custom_trim", true)
+                    .insert_str("trim_width", width)
+                    .insert_str("trim_height", height)
+                    .insert_str("geometry_paper", format!("paperwidth={},paperheight={}", width, height))
+            }
+            None => data.insert_str("geometry_paper", paper_size),
+        };
+        if let Some(gutter) = gutter {
+            data = data.insert_bool("has_gutter", true)
+                .insert_str("gutter", gutter);
+        }
+        if let Some(crop_options) = layout.crop_options() {
+            data = data.insert_bool("crop", true)
+                .insert_str("crop_options", crop_options)
+                .insert_str("page_boxes", layout.page_boxes())
+                .insert_str("page_dict", format!("<< {} >>", layout.page_boxes()));
+        }
+
+        // PDF/X output, using the pdfx package
+        if let Ok(pdfx) = self.book.options.get_str("tex.pdfx") {
+            data = data.insert_bool("has_pdfx", true)
+                .insert_str("pdfx", pdfx);
+            if let Ok(profile) = self.book.options.get_path("tex.pdfx.color_profile") {
+                let intent = match self.book.options.get_str("tex.pdfx.output_intent") {
+                    Ok(intent) => intent.to_owned(),
+                    Err(_) => Path::new(&profile).file_stem()
+                        .map(|s| s.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                };
+                data = data.insert_bool("has_color_profile", true)
+                    .insert_str("color_profile", profile.replace('\\', "/"))
+                    .insert_str("output_intent", intent);
+            }
+        }
+
+        // Running headers and footers; by default, only the page number is displayed
+        let positions = ["header.left", "header.center", "header.right",
+                         "footer.left", "footer.center", "footer.right"];
+        let custom = positions.iter()
+            .any(|p| self.book.options.get_str(&format!("tex.{}", p)).is_ok());
+        for position in &positions {
+            let value = match self.book.options.get_str(&format!("tex.{}", position)) {
+                Ok(value) => value,
+                Err(_) if !custom && *position == "header.center" => "\\thepage",
+                Err(_) => "",
+            };
+            data = data.insert_str(position.replace('.', "_"), value);
+        }
+        // Multiple authors are separated by \and, which LaTeX handles in \maketitle
+        if self.book.options.get_str_vec("authors").is_ok() {
+            let mut authors = vec![];
+            for author in self.book.authors() {
+                authors.push(self.render_vec(&Parser::new().parse_inline(&author.name)?)?);
+            }
+            data = data.insert_str("author", authors.join(" \\and "));
+            data = data.insert_str("xmp_author", authors.join("\\sep "));
+        }
         if let Ok(tex_tmpl_add) = self.book.options.get_str("tex.template.add") {
             data = data.insert_str("additional_code", tex_tmpl_add);
         }
@@ -231,13 +372,20 @@ impl<'a> LatexRenderer<'a> {
 
         // If class isn't book, set open_any to true, so margins are symetric.
         let mut book = false;
-        if self.book.options.get_str("tex.class").unwrap() == "book" {
+        if is_book_class(class) {
             data = data.insert_bool("book", true);
             book = true;
         }
+        // Inside and outside margins take precedence over left and right ones
+        let margin_left = self.book.options.get_str("tex.margin.inside")
+            .or_else(|_| self.book.options.get_str("tex.margin.left"))
+            .unwrap_or(if book { "1.5cm" } else { "2cm" });
+        let margin_right = self.book.options.get_str("tex.margin.outside")
+            .or_else(|_| self.book.options.get_str("tex.margin.right"))
+            .unwrap_or(if book { "2cm" } else { "2cm" });
         data = data
-            .insert_str("margin_left", self.book.options.get_str("tex.margin.left").unwrap_or(if book { "1.5cm" } else { "2cm" }))
-            .insert_str("margin_right", self.book.options.get_str("tex.margin.right").unwrap_or(if book { "2cm" } else { "2cm" }))
+            .insert_str("margin_left", margin_left)
+            .insert_str("margin_right", margin_right)
             .insert_str("margin_bottom", self.book.options.get_str("tex.margin.bottom").unwrap())
             .insert_str("margin_top", self.book.options.get_str("tex.margin.top").unwrap());
 
@@ -246,16 +394,372 @@ impl<'a> LatexRenderer<'a> {
             data = data.insert_bool("initials", true);
         }
         // Insert xelatex if tex.command is set to xelatex
-        if self.book.options.get_str("tex.command") == Ok("xelatex") {
+        if tex_engine(command) == "xelatex" {
             data = data.insert_bool("xelatex", true);
         }
+        if tex_engine(command) == "lualatex" {
+            data = data.insert_bool("lualatex", true);
+        }
+        if unicode {
+            data = data.insert_bool("fontspec", true)
+                .insert_str("polyglossia_lang", polyglossia_lang);
+            for key in FONT_OPTIONS {
+                if let Ok(font) = self.book.options.get_str(key) {
+                    let name = key.replace('.', "_")["tex_".len()..].to_owned();
+                    data = data.insert_bool(format!("has_{}", name), true)
+                        .insert_str(name, font);
+                }
+            }
+        } else if FONT_OPTIONS.iter().any(|key| self.book.options.get_str(key).is_ok()) {
+            warn!("{}", lformat!("LaTeX: font options are ignored unless tex.command is \
+                                  xelatex or lualatex"));
+        }
         let data = data.build();
         let mut res: Vec<u8> = vec![];
         template.render_data(&mut res, &data)?;
-        match String::from_utf8(res) {
+        let res = match String::from_utf8(res) {
             Err(_) => panic!(lformat!("generated LaTeX was not valid utf-8")),
-            Ok(res) => Ok(res),
+            Ok(res) => res,
+        };
+
+        // Find where each block ends up in the generated file
+        self.source_map.clear();
+        if let Some(start) = res.find(&content) {
+            let mut line = res[..start].matches('\n').count() as u32 + 1;
+            let mut previous = 0;
+            for (offset, source) in offsets {
+                line += content[previous..offset].matches('\n').count() as u32;
+                previous = offset;
+                self.source_map.push((line, source));
+            }
         }
+        Ok(res)
+    }
+
+    /// Renders a passage in another language, so it is hyphenated correctly
+    fn render_lang(&mut self, lang: &str, content: String) -> String {
+        let tag = match LangTag::parse(lang) {
+            Some(tag) => tag,
+            None => {
+                warn!("{}", lformat!("LaTeX: invalid language tag '{lang}'", lang = lang));
+                return content;
+            }
+        };
+        let command = self.book.options.get_str("tex.command").unwrap();
+        if tag.is_rtl() && !is_unicode_engine(command) {
+            warn!("{}", lformat!("LaTeX: right-to-left languages require xelatex or lualatex"));
+        }
+        let name = if is_unicode_engine(command) {
+            tag.polyglossia()
+        } else {
+            tag.babel().map(|name| (name, ""))
+        };
+        match name {
+            Some((name, options)) => {
+                // Arabic is already loaded if a fallback font is set for it
+                let loaded = name == "arabic" && is_unicode_engine(command) &&
+                             self.book.options.get_str("tex.font.arabic").is_ok();
+                if !loaded && !self.languages.contains(&(name, options)) {
+                    self.languages.push((name, options));
+                }
+                if !is_unicode_engine(command) {
+                    format!("\\foreignlanguage{{{}}}{{{}}}", name, content)
+                } else if options.is_empty() {
+                    format!("\\text{}{{{}}}", name, content)
+                } else {
+                    format!("\\text{}[{}]{{{}}}", name, options, content)
+                }
+            }
+            None => {
+                warn!("{}", lformat!("LaTeX: can't find a tex equivalent for lang '{lang}'",
+                                     lang = lang));
+                content
+            }
+        }
+    }
+
+    /// Returns the source map of the last rendered book, with a line for
+    /// each Markdown block: the line in the generated LaTeX file where its
+    /// output starts, and its source (e.g. `chapter_03.md:42`)
+    pub fn source_map_content(&self) -> String {
+        let mut res = String::new();
+        for &(line, ref source) in &self.source_map {
+            res.push_str(&format!("{}\t{}\n", line, source));
+        }
+        res
+    }
+}
+
+/// Families of LaTeX classes, which set the page layout and the running
+/// headers in different ways
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassFamily {
+    /// Standard classes (`book`, `article`, `report`), using `geometry` and `fancyhdr`
+    Standard,
+    /// KOMA-Script classes (`scrbook`, `scrartcl`, `scrreprt`), using
+    /// `typearea` and `scrlayer-scrpage`
+    Koma,
+    /// The `memoir` class, which has its own commands for both
+    Memoir,
+}
+
+impl ClassFamily {
+    fn from_class(class: &str) -> ClassFamily {
+        match class {
+            "scrbook" | "scrartcl" | "scrreprt" => ClassFamily::Koma,
+            "memoir" => ClassFamily::Memoir,
+            _ => ClassFamily::Standard,
+        }
+    }
+}
+
+/// Room left around the bleed for crop marks, in points (10mm)
+const CROP_MARKS_ROOM: f32 = 28.45;
+
+/// Dimensions of the page when printing: trim size, bleed and crop marks
+struct PrintLayout<'a> {
+    /// Trim size, if set by `tex.trim.width` and `tex.trim.height`
+    custom_trim: Option<(&'a str, &'a str)>,
+    /// Trim size, in points, if it is known
+    trim: Option<(f32, f32)>,
+    /// Bleed on each side, in points
+    bleed: f32,
+    crop_marks: bool,
+}
+
+impl<'a> PrintLayout<'a> {
+    fn new(book: &'a Book) -> Result<PrintLayout<'a>> {
+        let custom_trim = match (book.options.get_str("tex.trim.width"),
+                                 book.options.get_str("tex.trim.height")) {
+            (Ok(width), Ok(height)) => Some((width, height)),
+            (Err(_), Err(_)) => None,
+            _ => {
+                return Err(Error::book_option(&book.source,
+                                              lformat!("tex.trim.width and tex.trim.height \
+                                                        must be set together")));
+            }
+        };
+        let trim = match custom_trim {
+            Some((width, height)) => {
+                match (misc::tex_length(width), misc::tex_length(height)) {
+                    (Some(width), Some(height)) => Some((width, height)),
+                    _ => None,
+                }
+            }
+            None => paper_dimensions(book.options.get_str("tex.paper.size").unwrap()),
+        };
+        let bleed = book.options.get_str("tex.bleed")
+            .ok()
+            .and_then(misc::tex_length)
+            .unwrap_or(0.0);
+        let crop_marks = book.options.get_bool("tex.crop_marks").unwrap();
+        if trim.is_none() && (bleed > 0.0 || crop_marks) {
+            return Err(Error::book_option(&book.source,
+                                          lformat!("unknown dimensions for paper size '{size}': \
+                                                    set tex.trim.width and tex.trim.height to \
+                                                    use bleed or crop marks",
+                                                   size = book.options.get_str("tex.paper.size").unwrap())));
+        }
+        Ok(PrintLayout {
+            custom_trim: custom_trim,
+            trim: trim,
+            bleed: bleed,
+            crop_marks: crop_marks,
+        })
+    }
+
+    /// Distance between the edges of the media and the trimmed page, in points
+    fn offset(&self) -> f32 {
+        self.bleed + if self.crop_marks { CROP_MARKS_ROOM } else { 0.0 }
+    }
+
+    /// Options of the `crop` package, used to center the trimmed page on a
+    /// larger media, or None if there is no bleed nor crop marks
+    fn crop_options(&self) -> Option<String> {
+        let (width, height) = match self.trim {
+            Some(trim) if self.bleed > 0.0 || self.crop_marks => trim,
+            _ => return None,
+        };
+        Some(format!("{},center,width={:.2}pt,height={:.2}pt",
+                     if self.crop_marks { "cam" } else { "off" },
+                     width + 2.0 * self.offset(),
+                     height + 2.0 * self.offset()))
+    }
+
+    /// TrimBox and BleedBox entries for the PDF pages, in big points
+    fn page_boxes(&self) -> String {
+        let (width, height) = self.trim.unwrap_or((0.0, 0.0));
+        let bp = |pt: f32| pt * 72.0 / 72.27;
+        let offset = self.offset();
+        format!("/TrimBox [{:.2} {:.2} {:.2} {:.2}] /BleedBox [{:.2} {:.2} {:.2} {:.2}]",
+                bp(offset),
+                bp(offset),
+                bp(offset + width),
+                bp(offset + height),
+                bp(offset - self.bleed),
+                bp(offset - self.bleed),
+                bp(offset + width + self.bleed),
+                bp(offset + height + self.bleed))
+    }
+}
+
+/// Returns the dimensions (width and height, in points) of a LaTeX paper size
+pub fn paper_dimensions(paper: &str) -> Option<(f32, f32)> {
+    let (width, height) = match paper {
+        "a4paper" => ("210mm", "297mm"),
+        "a5paper" => ("148mm", "210mm"),
+        "a6paper" => ("105mm", "148mm"),
+        "b5paper" => ("176mm", "250mm"),
+        "b6paper" => ("125mm", "176mm"),
+        "letterpaper" => ("8.5in", "11in"),
+        "legalpaper" => ("8.5in", "14in"),
+        "executivepaper" => ("7.25in", "10.5in"),
+        _ => return None,
+    };
+    match (misc::tex_length(width), misc::tex_length(height)) {
+        (Some(width), Some(height)) => Some((width, height)),
+        _ => None,
+    }
+}
+
+/// Options setting the fonts used with xelatex or lualatex
+pub const FONT_OPTIONS: &'static [&'static str] = &["tex.font.main",
+                                                    "tex.font.sans",
+                                                    "tex.font.mono",
+                                                    "tex.font.cjk",
+                                                    "tex.font.arabic",
+                                                    "tex.font.cyrillic"];
+
+/// Returns the name of the LaTeX engine run by a command, e.g. `xelatex`
+/// for `/usr/bin/xelatex`
+pub fn tex_engine(command: &str) -> Cow<str> {
+    Path::new(command)
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or(Cow::Borrowed(command))
+}
+
+/// Returns true if the command runs an engine supporting `fontspec`
+/// (xelatex or lualatex)
+pub fn is_unicode_engine(command: &str) -> bool {
+    let engine = tex_engine(command);
+    engine == "xelatex" || engine == "lualatex"
+}
+
+/// Scripts that can have a fallback font, when they are not the script of
+/// the main language
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Script {
+    Arabic,
+    Cyrillic,
+}
+
+impl Script {
+    /// Returns true if a character belongs to this script
+    fn contains(&self, c: char) -> bool {
+        let c = c as u32;
+        match *self {
+            Script::Arabic => {
+                (c >= 0x0600 && c <= 0x06FF) || (c >= 0x0750 && c <= 0x077F) ||
+                (c >= 0x08A0 && c <= 0x08FF) || (c >= 0xFB50 && c <= 0xFDFF) ||
+                (c >= 0xFE70 && c <= 0xFEFF)
+            }
+            Script::Cyrillic => {
+                (c >= 0x0400 && c <= 0x052F) || (c >= 0x1C80 && c <= 0x1C8F) ||
+                (c >= 0x2DE0 && c <= 0x2DFF) || (c >= 0xA640 && c <= 0xA69F)
+            }
+        }
+    }
+
+    /// The template command used for runs of text in this script
+    fn command(&self) -> &'static str {
+        match *self {
+            Script::Arabic => "mdarabic",
+            Script::Cyrillic => "mdcyrillic",
+        }
+    }
+}
+
+/// Returns the scripts whose runs of text must be marked, because a
+/// fallback font is set for them
+fn fallback_scripts(book: &Book) -> Vec<Script> {
+    let mut scripts = vec![];
+    if !is_unicode_engine(book.options.get_str("tex.command").unwrap()) {
+        return scripts;
+    }
+    let lang = book.options.get_str("lang").unwrap();
+    let lang = lang.split(|c| c == '-' || c == '_').next().unwrap_or("");
+    if book.options.get_str("tex.font.arabic").is_ok() &&
+       !["ar", "fa", "ur", "ps"].contains(&lang) {
+        scripts.push(Script::Arabic);
+    }
+    if book.options.get_str("tex.font.cyrillic").is_ok() &&
+       !["ru", "uk", "bg", "sr", "be", "mk", "kk"].contains(&lang) {
+        scripts.push(Script::Cyrillic);
+    }
+    scripts
+}
+
+/// Wraps runs of text in one of the given scripts in the corresponding
+/// command, e.g. `\mdarabic{...}`. Spaces and punctuation between two
+/// words of the same script belong to the run.
+fn wrap_scripts(text: &str, scripts: &[Script]) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut current: Option<Script> = None;
+    // Neutral characters following a run, that may or may not belong to it
+    let mut pending = String::new();
+    for c in text.chars() {
+        match scripts.iter().find(|s| s.contains(c)) {
+            Some(&script) => {
+                if current != Some(script) {
+                    if current.is_some() {
+                        res.push('}');
+                    }
+                    res.push_str(&pending);
+                    res.push_str(&format!("\\{}{{", script.command()));
+                    current = Some(script);
+                } else {
+                    res.push_str(&pending);
+                }
+                pending.clear();
+                res.push(c);
+            }
+            None if current.is_some() && !c.is_alphanumeric() && c != '\\' && c != '{' &&
+                    c != '}' => pending.push(c),
+            None => {
+                if current.is_some() {
+                    res.push('}');
+                    current = None;
+                }
+                res.push_str(&pending);
+                pending.clear();
+                res.push(c);
+            }
+        }
+    }
+    if current.is_some() {
+        res.push('}');
+    }
+    res.push_str(&pending);
+    res
+}
+
+/// Returns true if the class has no chapters
+fn is_short_class(class: &str) -> bool {
+    class == "article" || class == "scrartcl"
+}
+
+/// Returns true if the class is meant for two-sided books
+pub fn is_book_class(class: &str) -> bool {
+    class == "book" || class == "scrbook" || class == "memoir"
+}
+
+/// Returns the source corresponding to a line of the generated LaTeX file,
+/// i.e. the Markdown block it belongs to
+fn source_of_line(source_map: &[(u32, Source)], line: u32) -> Source {
+    match source_map.iter().rev().find(|&&(start, _)| start <= line) {
+        Some(&(_, ref source)) => source.clone(),
+        None => Source::new("tex.template"),
     }
 }
 
@@ -264,7 +768,12 @@ impl<'a> Renderer for LatexRenderer<'a> {
         match *token {
             Token::Str(ref text) => {
                 let content = if self.escape {
-                    self.book.clean(escape::tex(text.as_ref()), true)
+                    let content = self.book.clean(escape::tex(text.as_ref()), true);
+                    if self.scripts.is_empty() {
+                        content
+                    } else {
+                        Cow::Owned(wrap_scripts(&content, &self.scripts))
+                    }
                 } else {
                     Cow::Borrowed(text.as_ref())
                 };
@@ -501,6 +1010,10 @@ impl<'a> Renderer for LatexRenderer<'a> {
                 Ok(res)
             }
             Token::TableCell(ref vec) => self.render_vec(vec),
+            Token::Annotation(Data::Lang(ref lang), ref vec) => {
+                let content = self.render_vec(vec)?;
+                Ok(self.render_lang(lang, content))
+            }
             Token::Annotation(ref annotation, ref vec) => {
                 let content = self.render_vec(vec)?;
                 if self.proofread {
@@ -576,12 +1089,35 @@ impl BookRenderer for Pdf {
     }
     
     fn render(&self, book: &Book, to: &mut io::Write) -> Result<()> {
-        LatexRenderer::new(book)
-            .render_pdf(to)?;
+        match book.options.get_str("pdf.engine").unwrap() {
+            "typst" => {
+                TypstRenderer::new(book)
+                    .render_pdf(to)?;
+            }
+            "builtin" => render_builtin_pdf(book, to)?,
+            _ => {
+                LatexRenderer::new(book)
+                    .render_pdf(to)?;
+            }
+        }
         Ok(())
     }
 }
 
+/// Renders a PDF file with the builtin engine
+#[cfg(feature = "pdf")]
+fn render_builtin_pdf(book: &Book, to: &mut io::Write) -> Result<()> {
+    PdfRenderer::new(book).render_pdf(to)
+}
+
+/// Fails, since the builtin engine is not available
+#[cfg(not(feature = "pdf"))]
+fn render_builtin_pdf(book: &Book, _: &mut io::Write) -> Result<()> {
+    Err(Error::render(&book.source,
+                      lformat!("pdf.engine is set to builtin, but Crowbook was compiled \
+                                without the pdf feature")))
+}
+
 impl BookRenderer for ProofPdf {
     fn auto_path(&self, book_name: &str) -> Result<String> {
         Ok(format!("{}.proof.pdf", book_name))
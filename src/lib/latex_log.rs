@@ -0,0 +1,121 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received ba copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Parsing of the log files written by LaTeX engines
+
+/// An error reported in a LaTeX log
+#[derive(Debug, PartialEq, Clone)]
+pub struct LatexError {
+    /// Line of the `.tex` file where the error occured, if known
+    pub line: Option<u32>,
+    /// Error message, e.g. "Undefined control sequence."
+    pub message: String,
+    /// The TeX code where LaTeX stopped, if any
+    pub context: Option<String>,
+}
+
+/// The relevant content of a LaTeX log
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LatexLog {
+    /// Errors, in the order they were reported
+    pub errors: Vec<LatexError>,
+    /// Whether LaTeX (or a package) asks to be run again
+    pub rerun: bool,
+    /// Whether biblatex asks for biber to be run
+    pub biber: bool,
+}
+
+/// Messages indicating that another LaTeX run is needed
+const RERUN_MESSAGES: &[&str] = &["Rerun to get cross-references right",
+                                  "Rerun to get outlines right",
+                                  "Rerun to get citations correct",
+                                  "Label(s) may have changed",
+                                  "Please rerun LaTeX",
+                                  "has changed. Rerun",
+                                  "Rerun LaTeX"];
+
+/// Parses the content of a LaTeX log.
+///
+/// Errors can either be in the default format:
+///
+/// ```text
+/// ! Undefined control sequence.
+/// l.42 \foo
+/// ```
+///
+/// or in the `-file-line-error` one (`./result.tex:42: Undefined control sequence.`).
+pub fn parse(log: &str) -> LatexLog {
+    let mut res = LatexLog::default();
+    // Logs are wrapped at 79 characters, which can split rerun messages
+    let unwrapped = log.replace("\n", "");
+    res.rerun = RERUN_MESSAGES.iter().any(|m| unwrapped.contains(m));
+    res.biber = unwrapped.contains("Please (re)run Biber") ||
+                unwrapped.contains("Please rerun Biber");
+
+    let lines: Vec<&str> = log.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        let (message, mut number) = if line.starts_with("! ") {
+            (line[2..].trim().to_owned(), None)
+        } else if let Some((number, message)) = parse_file_line_error(line) {
+            (message, Some(number))
+        } else {
+            continue;
+        };
+        // Look for the "l.42 ..." line telling where TeX stopped
+        let mut context = None;
+        for next in lines.iter().skip(i + 1).take(20) {
+            if next.starts_with("l.") {
+                let rest = &next[2..];
+                let digits: String = rest.chars().take_while(|c| c.is_digit(10)).collect();
+                if let Ok(n) = digits.parse::<u32>() {
+                    if number.is_none() {
+                        number = Some(n);
+                    }
+                    let code = rest[digits.len()..].trim();
+                    if !code.is_empty() {
+                        context = Some(code.to_owned());
+                    }
+                }
+                break;
+            }
+            if next.starts_with("! ") || parse_file_line_error(next).is_some() {
+                break;
+            }
+        }
+        res.errors.push(LatexError {
+            line: number,
+            message: message,
+            context: context,
+        });
+    }
+    res
+}
+
+/// Parses a line of the form `./file.tex:42: message`
+fn parse_file_line_error(line: &str) -> Option<(u32, String)> {
+    let rest = match line.find(".tex:") {
+        Some(start) => &line[start + 5..],
+        None => return None,
+    };
+    let end = match rest.find(": ") {
+        Some(end) => end,
+        None => return None,
+    };
+    rest[..end].parse::<u32>()
+        .ok()
+        .map(|number| (number, rest[end + 2..].trim().to_owned()))
+}
@@ -116,6 +116,8 @@ extern crate crowbook_intl_runtime;
 extern crate numerals;
 extern crate epub_builder;
 extern crate uuid;
+extern crate zip;
+extern crate sha1;
 extern crate indicatif;
 #[macro_use]
 extern crate log;
@@ -145,6 +147,9 @@ pub use renderer::Renderer;
 pub use book_renderer::BookRenderer;
 pub use chapter::Chapter;
 pub use stats::Stats;
+pub use project::Project;
+pub use manifest::book_to_manifest;
+pub use contributor::Contributor;
 
 #[macro_use]
 #[doc(hidden)]
@@ -155,8 +160,15 @@ mod html_dir;
 mod error;
 mod book;
 mod epub;
+mod epub_check;
+mod epub_patch;
 mod latex;
+mod latex_log;
+mod typst;
 mod odt;
+mod docx;
+mod txt;
+mod markdown;
 mod parser;
 mod token;
 mod cleaner;
@@ -165,6 +177,7 @@ mod number;
 mod resource_handler;
 mod bookoptions;
 mod lang;
+mod lang_tag;
 mod renderer;
 mod book_renderer;
 mod html_single;
@@ -172,12 +185,19 @@ mod html_if;
 mod syntax;
 mod stats;
 mod book_bars;
+mod check;
+mod project;
+mod manifest;
 
 mod zipper;
 mod templates;
 mod bookoption;
 mod misc;
+mod font;
+mod contributor;
 mod text_view;
+#[cfg(feature = "pdf")]
+mod pdf;
 
 #[cfg(feature = "proofread")]
 mod grammar_check;
@@ -0,0 +1,313 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+// Chapter lists, either in the `.book` syntax or in a structured YAML
+// manifest, and conversion from the former to the latter.
+
+use error::{Error, Result, Source};
+use number::Number;
+
+use yaml_rust::Yaml;
+
+/// An entry in the chapter list of a book
+#[derive(Debug, PartialEq, Clone)]
+pub enum ChapterEntry {
+    /// A chapter (or a part whose title is in a file), with its numbering
+    Chapter(Number, String),
+    /// A subchapter, with its level and file
+    Subchapter(i32, String),
+    /// A part that only has a title, and no file
+    PartTitle(String),
+}
+
+/// Returns true if a line of a `.book` file starts the chapter list
+pub fn is_chapter_line(line: &str) -> bool {
+    line.starts_with(|c| match c {
+        '-' | '+' | '!' | '@' => true,
+        _ => c.is_digit(10),
+    })
+}
+
+/// Parses a line of the chapter list of a `.book` file.
+///
+/// Returns `None` for empty lines and comments.
+pub fn parse_chapter_line(source: &Source, line: &str) -> Result<Option<ChapterEntry>> {
+    fn get_filename(source: &Source, s: &str) -> Result<String> {
+        let words: Vec<&str> = (&s[1..]).split_whitespace().collect();
+        if words.len() > 1 {
+            return Err(Error::config_parser(source,
+                                            lformat!("chapter filenames must not contain \
+                                                      whitespace")));
+        } else if words.len() < 1 {
+            return Err(Error::config_parser(source, lformat!("no chapter name specified")));
+        }
+        Ok(words[0].to_owned())
+    }
+
+    fn get_number<'a>(source: &Source, s: &'a str, part: bool) -> Result<(i32, &'a str)> {
+        let parts: Vec<_> = s.splitn(2, |c: char| c == '.' || c == ':' || c == '+')
+            .collect();
+        if parts.len() != 2 {
+            return Err(Error::config_parser(source,
+                                            if part {
+                                                lformat!("ill-formatted line specifying \
+                                                          part number")
+                                            } else {
+                                                lformat!("ill-formatted line specifying \
+                                                          chapter number")
+                                            }));
+        }
+        let number = parts[0].parse::<i32>()
+            .map_err(|err| {
+                Error::config_parser(source,
+                                     if part {
+                                         lformat!("error parsing part number: {error}",
+                                                  error = err)
+                                     } else {
+                                         lformat!("error parsing chapter number: {error}",
+                                                  error = err)
+                                     })
+            })?;
+        Ok((number, parts[1]))
+    }
+
+    let line = line.trim();
+    let entry = if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    } else if line.starts_with("--") {
+        // Subchapter
+        let level = line.bytes().take_while(|b| *b == b'-').count() - 1;
+        let file = get_filename(source, &line[level..])?;
+        ChapterEntry::Subchapter(level as i32, file)
+    } else if line.starts_with('-') {
+        // unnumbered chapter
+        ChapterEntry::Chapter(Number::Unnumbered, get_filename(source, line)?)
+    } else if line.starts_with('+') {
+        // numbered chapter
+        ChapterEntry::Chapter(Number::Default, get_filename(source, line)?)
+    } else if line.starts_with('!') {
+        // hidden chapter
+        ChapterEntry::Chapter(Number::Hidden, get_filename(source, line)?)
+    } else if line.starts_with(|c: char| c.is_digit(10)) {
+        // chapter with specific number
+        let (number, rest) = get_number(source, line, false)?;
+        ChapterEntry::Chapter(Number::Specified(number), get_filename(source, rest)?)
+    } else if line.starts_with('@') {
+        /* Part */
+        let subline = &line[1..];
+        if subline.starts_with(|c: char| c.is_whitespace()) {
+            ChapterEntry::PartTitle(subline.trim().to_owned())
+        } else if subline.starts_with('+') {
+            /* Numbered part */
+            ChapterEntry::Chapter(Number::DefaultPart, get_filename(source, subline)?)
+        } else if subline.starts_with('-') {
+            /* Unnumbered part */
+            ChapterEntry::Chapter(Number::UnnumberedPart, get_filename(source, subline)?)
+        } else if subline.starts_with(|c: char| c.is_digit(10)) {
+            /* Specified  part*/
+            let (number, rest) = get_number(source, subline, true)?;
+            ChapterEntry::Chapter(Number::SpecifiedPart(number), get_filename(source, rest)?)
+        } else {
+            return Err(Error::config_parser(source,
+                                            lformat!("found invalid part definition in the chapter list")));
+        }
+    } else {
+        return Err(Error::config_parser(source,
+                                        lformat!("found invalid chapter definition in \
+                                                  the chapter list")));
+    };
+    Ok(Some(entry))
+}
+
+/// Parses an entry of the `chapters` list of a YAML manifest, e.g.:
+///
+/// ```yaml
+/// - file: chapter.md
+///   number: 3      # or false for an unnumbered chapter
+///   hidden: false  # if true, hide the chapter title
+///   part: false    # if true, this is a part; can also be a part title if there is no file
+///   level: 0       # if > 0, this is a subchapter
+/// ```
+///
+/// A string `foo.md` is also accepted, as a shortcut for `file: foo.md`.
+pub fn parse_chapter_yaml(source: &Source, yaml: &Yaml) -> Result<ChapterEntry> {
+    if let Yaml::String(ref file) = *yaml {
+        return Ok(ChapterEntry::Chapter(Number::Default, file.clone()));
+    }
+    let hash = if let Yaml::Hash(ref hash) = *yaml {
+        hash
+    } else {
+        return Err(Error::config_parser(source,
+                                        lformat!("chapter entries must be strings or hashmaps, \
+                                                  found {:?}",
+                                                 yaml)));
+    };
+
+    let mut file = None;
+    let mut number = None;
+    let mut hidden = false;
+    let mut part = None;
+    let mut level = 0;
+    for (key, value) in hash {
+        let invalid = || Error::config_parser(source,
+                                              lformat!("invalid value {:?} for chapter key {:?}",
+                                                       value,
+                                                       key));
+        match key.as_str() {
+            Some("file") => file = Some(value.as_str().ok_or_else(&invalid)?.to_owned()),
+            Some("number") => {
+                number = match *value {
+                    Yaml::Integer(n) => Some(Some(n as i32)),
+                    Yaml::Boolean(true) => None,
+                    Yaml::Boolean(false) => Some(None),
+                    _ => return Err(invalid()),
+                }
+            },
+            Some("hidden") => hidden = value.as_bool().ok_or_else(&invalid)?,
+            Some("part") => part = Some(value.clone()),
+            Some("level") => level = value.as_i64().ok_or_else(&invalid)? as i32,
+            _ => {
+                return Err(Error::config_parser(source,
+                                                lformat!("unrecognized chapter key {:?}",
+                                                         key)))
+            },
+        }
+    }
+
+    match (file, part) {
+        (None, Some(Yaml::String(title))) => {
+            if number.is_some() || hidden || level != 0 {
+                return Err(Error::config_parser(source,
+                                                lformat!("a part with only a title can't set \
+                                                          number, hidden or level")));
+            }
+            Ok(ChapterEntry::PartTitle(title))
+        },
+        (None, _) => {
+            Err(Error::config_parser(source,
+                                     lformat!("chapter entry must contain either a file or a \
+                                               part title")))
+        },
+        (Some(file), Some(Yaml::Boolean(true))) => {
+            if hidden || level != 0 {
+                return Err(Error::config_parser(source,
+                                                lformat!("a part can't be hidden or have a \
+                                                          level")));
+            }
+            let number = match number {
+                None => Number::DefaultPart,
+                Some(None) => Number::UnnumberedPart,
+                Some(Some(n)) => Number::SpecifiedPart(n),
+            };
+            Ok(ChapterEntry::Chapter(number, file))
+        },
+        (Some(file), None) |
+        (Some(file), Some(Yaml::Boolean(false))) => {
+            if level > 0 {
+                if number.is_some() || hidden {
+                    return Err(Error::config_parser(source,
+                                                    lformat!("a subchapter can't set number or \
+                                                              hidden")));
+                }
+                return Ok(ChapterEntry::Subchapter(level, file));
+            }
+            let number = match (hidden, number) {
+                (true, None) => Number::Hidden,
+                (false, None) => Number::Default,
+                (false, Some(None)) => Number::Unnumbered,
+                (false, Some(Some(n))) => Number::Specified(n),
+                (true, Some(_)) => {
+                    return Err(Error::config_parser(source,
+                                                    lformat!("a hidden chapter can't set \
+                                                              number")))
+                },
+            };
+            Ok(ChapterEntry::Chapter(number, file))
+        },
+        (Some(_), Some(_)) => {
+            Err(Error::config_parser(source,
+                                     lformat!("'part' must be a boolean for a chapter entry \
+                                               with a file")))
+        },
+    }
+}
+
+/// Quotes a string so it can be included in YAML
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Converts a chapter entry to YAML, as a list item of a manifest
+fn entry_to_yaml(entry: &ChapterEntry) -> String {
+    match *entry {
+        ChapterEntry::PartTitle(ref title) => format!("  - part: {}\n", quote(title)),
+        ChapterEntry::Subchapter(level, ref file) => {
+            format!("  - file: {}\n    level: {}\n", quote(file), level)
+        },
+        ChapterEntry::Chapter(number, ref file) => {
+            let mut out = format!("  - file: {}\n", quote(file));
+            if number.is_part() {
+                out.push_str("    part: true\n");
+            }
+            match number {
+                Number::Hidden => out.push_str("    hidden: true\n"),
+                Number::Unnumbered | Number::UnnumberedPart => out.push_str("    number: false\n"),
+                Number::Specified(n) | Number::SpecifiedPart(n) => {
+                    out.push_str(&format!("    number: {}\n", n))
+                },
+                Number::Default | Number::DefaultPart => {},
+            }
+            out
+        },
+    }
+}
+
+/// Converts the content of a `.book` file to a YAML manifest.
+///
+/// The YAML part of the `.book` file is kept as is, while the list of
+/// chapters is converted to a `chapters` list.
+pub fn book_to_manifest(content: &str) -> Result<String> {
+    let mut source = Source::empty();
+    let mut yaml = String::new();
+    let mut chapters = String::new();
+    let mut in_chapters = false;
+    for (i, line) in content.lines().enumerate() {
+        if !in_chapters && is_chapter_line(line) {
+            in_chapters = true;
+        }
+        if !in_chapters {
+            yaml.push_str(line);
+            yaml.push('\n');
+            continue;
+        }
+        source.set_line(i as u32 + 1);
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            // Keep comments
+            chapters.push_str(&format!("  {}\n", trimmed));
+        } else if let Some(entry) = parse_chapter_line(&source, line)? {
+            chapters.push_str(&entry_to_yaml(&entry));
+        }
+    }
+    let mut out = String::from(yaml.trim_right());
+    if !out.is_empty() {
+        out.push_str("\n\n");
+    }
+    out.push_str("chapters:\n");
+    out.push_str(&chapters);
+    Ok(out)
+}
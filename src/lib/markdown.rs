@@ -0,0 +1,346 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+use token::Token;
+use token::Data;
+use book::Book;
+use error::{Error, Result};
+use renderer::Renderer;
+use book_renderer::BookRenderer;
+use text_view::{view_as_text, is_block, indent, TextNumbering};
+
+use std::io;
+use std::mem;
+
+/// Renders a book back to a single CommonMark file
+///
+/// Chapter and part headers are rendered with their numbers, so the
+/// result doesn't depend on the numbering options of the book anymore.
+pub struct MarkdownRenderer<'a> {
+    book: &'a Book,
+    numbering: TextNumbering<'a>,
+    /// Footnote definitions of the current chapter, with their numbers
+    footnotes: Vec<(usize, String)>,
+    footnote_number: usize,
+}
+
+impl<'a> MarkdownRenderer<'a> {
+    /// Creates a new MarkdownRenderer
+    pub fn new(book: &'a Book) -> MarkdownRenderer<'a> {
+        MarkdownRenderer {
+            book: book,
+            numbering: TextNumbering::new(book),
+            footnotes: vec![],
+            footnote_number: 0,
+        }
+    }
+
+    /// Renders the whole book
+    pub fn render_book(&mut self) -> Result<String> {
+        let book = self.book;
+        let mut content = String::new();
+
+        for chapter in &book.chapters {
+            self.numbering.start_chapter(chapter.number);
+            content.push_str(&self.render_blocks(&chapter.content, "\n\n")?);
+
+            // Footnote definitions are put at the end of the chapter
+            let mut footnotes = mem::replace(&mut self.footnotes, vec![]);
+            footnotes.sort_by_key(|&(n, _)| n);
+            for (n, note) in footnotes {
+                content.push_str(&indent(&note, &format!("[^{}]: ", n), "    "));
+                content.push('\n');
+            }
+        }
+        Ok(format!("{}\n", content.trim_right()))
+    }
+
+    /// Renders tokens that can be either blocks or inline elements (e.g. in
+    /// list items or footnotes), ending inline content with `separator`
+    fn render_blocks(&mut self, tokens: &[Token], separator: &str) -> Result<String> {
+        let mut res = String::new();
+        let mut inline = String::new();
+        for token in tokens {
+            if is_block(token) {
+                if !inline.trim().is_empty() {
+                    res.push_str(&escape_line_starts(inline.trim_right()));
+                    res.push_str(separator);
+                }
+                inline.clear();
+                res.push_str(&self.render_token(token)?);
+            } else {
+                inline.push_str(&self.render_token(token)?);
+            }
+        }
+        if !inline.trim().is_empty() {
+            res.push_str(&escape_line_starts(inline.trim_right()));
+            res.push_str(separator);
+        }
+        Ok(res)
+    }
+
+    fn render_header(&mut self, n: i32, vec: &[Token]) -> Result<String> {
+        let numbered = self.numbering.numbered_header(n, vec)?;
+        // Untitled chapters get a title in other formats, but here it would
+        // only be a number
+        if n == 1 && vec.is_empty() {
+            return Ok(String::new());
+        }
+        let text = match numbered {
+            Some(text) => escape(&text),
+            None => self.render_vec(vec)?,
+        };
+        Ok(format!("{} {}\n\n", "#".repeat(n.max(1).min(6) as usize), text.trim()))
+    }
+
+    /// Renders a (possibly ordered) list, indenting the content of items
+    fn render_list(&mut self, start: Option<usize>, items: &[Token]) -> Result<String> {
+        // Lists whose items contain paragraphs are loose, i.e. separated by blank lines
+        let loose = items.iter().any(|item| {
+            item.inner().map_or(false, |vec| {
+                vec.iter().any(|token| match *token {
+                    Token::Paragraph(_) => true,
+                    _ => false,
+                })
+            })
+        });
+        let separator = if loose { "\n\n" } else { "\n" };
+        let mut res = String::new();
+        let mut number = start.unwrap_or(1);
+        for item in items {
+            let marker = match start {
+                Some(_) => format!("{}. ", number),
+                None => String::from("- "),
+            };
+            number += 1;
+            let content = match *item {
+                Token::Item(ref vec) => self.render_blocks(vec, separator)?,
+                ref token => self.render_token(token)?,
+            };
+            if loose && !res.is_empty() {
+                res.push('\n');
+            }
+            res.push_str(&indent(&content, &marker, &" ".repeat(marker.len())));
+        }
+        res.push('\n');
+        Ok(res)
+    }
+
+    /// Renders a row of a table
+    fn render_row(&mut self, cells: &[Token]) -> Result<String> {
+        let mut res = String::from("|");
+        for cell in cells {
+            let content = self.render_token(cell)?;
+            res.push_str(&format!(" {} |", content.trim().replace('\n', " ").replace('|', "\\|")));
+        }
+        res.push('\n');
+        Ok(res)
+    }
+}
+
+impl<'a> Renderer for MarkdownRenderer<'a> {
+    fn render_token(&mut self, token: &Token) -> Result<String> {
+        match *token {
+            Token::Str(ref text) => Ok(escape(text)),
+            Token::Paragraph(ref vec) => {
+                Ok(format!("{}\n\n", escape_line_starts(&self.render_vec(vec)?)))
+            }
+            Token::Header(n, ref vec) => {
+                if n == 1 && self.numbering.is_hidden() {
+                    return Ok(String::new());
+                }
+                self.render_header(n, vec)
+            }
+            Token::Emphasis(ref vec) => Ok(format!("*{}*", self.render_vec(vec)?)),
+            Token::Strong(ref vec) => Ok(format!("**{}**", self.render_vec(vec)?)),
+            Token::Superscript(ref vec) => Ok(format!("^{}^", self.render_vec(vec)?)),
+            Token::Subscript(ref vec) => Ok(format!("~{}~", self.render_vec(vec)?)),
+            Token::Code(ref vec) => Ok(code_span(&view_as_text(vec))),
+            Token::Link(ref url, ref title, ref vec) => {
+                Ok(format!("[{}]({})", self.render_vec(vec)?, link_destination(url, title)))
+            }
+            Token::Image(ref url, ref title, ref alt) => {
+                Ok(format!("![{}]({})", self.render_vec(alt)?, link_destination(url, title)))
+            }
+            Token::StandaloneImage(ref url, ref title, ref alt) => {
+                Ok(format!("![{}]({})\n\n", self.render_vec(alt)?, link_destination(url, title)))
+            }
+            Token::BlockQuote(ref vec) => {
+                let content = self.render_blocks(vec, "\n\n")?;
+                let mut res = String::new();
+                for line in content.trim_right().lines() {
+                    if line.is_empty() {
+                        res.push_str(">\n");
+                    } else {
+                        res.push_str(&format!("> {}\n", line));
+                    }
+                }
+                res.push('\n');
+                Ok(res)
+            }
+            Token::CodeBlock(ref language, ref vec) => {
+                let code = view_as_text(vec);
+                let fence = "`".repeat(longest_run(&code, '`').max(2) + 1);
+                Ok(format!("{fence}{lang}\n{code}\n{fence}\n\n",
+                           fence = fence,
+                           lang = language,
+                           code = code.trim_right_matches('\n')))
+            }
+            Token::SoftBreak => Ok(String::from(" ")),
+            Token::HardBreak => Ok(String::from("\\\n")),
+            Token::Rule => Ok(String::from("***\n\n")),
+            Token::List(ref vec) => self.render_list(None, vec),
+            Token::OrderedList(start, ref vec) => self.render_list(Some(start), vec),
+            Token::Item(ref vec) => self.render_blocks(vec, "\n\n"),
+            Token::Table(n, ref vec) => {
+                let columns = n.max(1) as usize;
+                let mut rows = vec![];
+                for row in vec {
+                    rows.push(self.render_token(row)?);
+                }
+                match vec.first() {
+                    Some(&Token::TableHead(_)) => (),
+                    // Pipe tables always need a header
+                    _ => rows.insert(0, format!("|{}\n", "  |".repeat(columns))),
+                }
+                rows.insert(1, format!("|{}\n", " --- |".repeat(columns)));
+                Ok(format!("{}\n", rows.concat()))
+            }
+            Token::TableHead(ref vec) |
+            Token::TableRow(ref vec) => self.render_row(vec),
+            Token::Annotation(Data::Lang(ref lang), ref vec) => {
+                Ok(format!("<span lang=\"{}\">{}</span>",
+                           lang.replace('"', "&quot;"),
+                           self.render_vec(vec)?))
+            }
+            Token::TableCell(ref vec) |
+            Token::Annotation(_, ref vec) => self.render_vec(vec),
+            Token::Footnote(ref vec) => {
+                self.footnote_number += 1;
+                let n = self.footnote_number;
+                let note = self.render_blocks(vec, "\n\n")?;
+                self.footnotes.push((n, note));
+                Ok(format!("[^{}]", n))
+            }
+            Token::__NonExhaustive => unreachable!(),
+        }
+    }
+}
+
+/// Escapes the characters of a text that could be interpreted as Markdown
+/// inline elements
+fn escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '^' | '~' => res.push('\\'),
+            // Only escape what could be an entity, e.g. `&amp;`
+            '&' => {
+                if chars.peek().map_or(false, |c| c.is_alphanumeric() || *c == '#') {
+                    res.push('\\');
+                }
+            }
+            _ => (),
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Escapes the beginning of lines that could be read as the start of a
+/// block (e.g. a header or a list item)
+fn escape_line_starts(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            res.push('\n');
+        }
+        let digits = line.chars().take_while(|c| c.is_digit(10)).count();
+        if line.starts_with(|c: char| c == '#' || c == '>' || c == '-' || c == '+' || c == '=') {
+            res.push('\\');
+            res.push_str(line);
+        } else if digits > 0 && (line[digits..].starts_with('.') || line[digits..].starts_with(')')) {
+            res.push_str(&line[..digits]);
+            res.push('\\');
+            res.push_str(&line[digits..]);
+        } else {
+            res.push_str(line);
+        }
+    }
+    res
+}
+
+/// Returns the length of the longest run of `c` in the text
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for x in text.chars() {
+        if x == c {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+/// Renders inline code, with enough backticks to contain the code
+fn code_span(code: &str) -> String {
+    let fence = "`".repeat(longest_run(code, '`') + 1);
+    let padding = if code.starts_with('`') || code.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{fence}{pad}{code}{pad}{fence}",
+            fence = fence,
+            pad = padding,
+            code = code)
+}
+
+/// Renders the destination (and title, if any) of a link or an image
+fn link_destination(url: &str, title: &str) -> String {
+    let mut res = if url.is_empty() || url.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+        format!("<{}>", url.replace('<', "\\<").replace('>', "\\>"))
+    } else {
+        url.to_owned()
+    };
+    if !title.is_empty() {
+        res.push_str(&format!(" \"{}\"", title.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    res
+}
+
+pub struct Markdown {}
+
+impl BookRenderer for Markdown {
+    fn auto_path(&self, book_name: &str) -> Result<String> {
+        Ok(format!("{}.md", book_name))
+    }
+
+    fn render(&self, book: &Book, to: &mut io::Write) -> Result<()> {
+        let result = MarkdownRenderer::new(book).render_book()?;
+        to.write_all(result.as_bytes())
+            .map_err(|e| {
+                Error::render(&book.source,
+                              lformat!("problem when writing Markdown: {error}", error = e))
+            })?;
+        Ok(())
+    }
+}
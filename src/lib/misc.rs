@@ -63,3 +63,74 @@ pub fn insert_title(tokens: &mut Vec<Token>) {
     }
     tokens.insert(0, Token::Header(1, vec!()));
 }
+
+/// Computes the Levenshtein distance between two strings, i.e. the
+/// number of insertions, deletions or substitutions needed to get from one
+/// to the other.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, c_a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, c_b) in b.iter().enumerate() {
+            let cost = if c_a == *c_b { 0 } else { 1 };
+            current[j + 1] = std::cmp::min(std::cmp::min(current[j] + 1,
+                                                         previous[j + 1] + 1),
+                                           previous[j] + cost);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Returns the size (width and height, in pixels) of a PNG, GIF or JPEG
+/// image, reading it from its header
+pub fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |i: usize| ((data[i] as u32) << 8) | (data[i + 1] as u32);
+    if data.len() >= 24 && data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // Width and height are the first fields of the IHDR chunk
+        Some(((be16(16) << 16) | be16(18), (be16(20) << 16) | be16(22)))
+    } else if data.len() >= 10 && data.starts_with(b"GIF8") {
+        Some(((data[6] as u32) | ((data[7] as u32) << 8),
+              (data[8] as u32) | ((data[9] as u32) << 8)))
+    } else if data.len() >= 4 && data[0] == 0xFF && data[1] == 0xD8 {
+        // Look for a "start of frame" segment
+        let mut i = 2;
+        while i + 9 < data.len() {
+            if data[i] != 0xFF {
+                return None;
+            }
+            let marker = data[i + 1];
+            if marker >= 0xC0 && marker <= 0xCF && marker != 0xC4 && marker != 0xC8 &&
+               marker != 0xCC {
+                return Some((be16(i + 7), be16(i + 5)));
+            }
+            i += 2 + be16(i + 2) as usize;
+        }
+        None
+    } else {
+        None
+    }
+}
+
+/// Parses a TeX length (e.g. `148mm`, `0.125in` or `10pt`) and returns its
+/// value in TeX points
+pub fn tex_length(s: &str) -> Option<f32> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_alphabetic()).unwrap_or(s.len());
+    let value = match s[..split].trim().parse::<f32>() {
+        Ok(value) => value,
+        Err(_) => return None,
+    };
+    let factor = match s[split..].trim() {
+        "pt" => 1.0,
+        "bp" => 72.27 / 72.0,
+        "mm" => 72.27 / 25.4,
+        "cm" => 72.27 / 2.54,
+        "in" => 72.27,
+        "pc" => 12.0,
+        _ => return None,
+    };
+    Some(value * factor)
+}
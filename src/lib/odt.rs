@@ -1,51 +1,59 @@
 use token::Token;
 use book::{Book, compile_str};
-use number::Number;
-use error::Result;
+use error::{Error, Result, Source};
 use templates::odt;
 use zipper::Zipper;
 use parser::Parser;
 use book_renderer::BookRenderer;
+use resource_handler::ResourceHandler;
+use text_view::{view_as_text, is_block, TextNumbering};
+use misc;
 
 use crowbook_text_processing::escape;
+use mime_guess::guess_mime_type_opt;
 
-use std::io::Write;
+use std::io::{Read, Write};
+use std::fs::File;
+use std::mem;
+
+/// Width of the text area of a page (see `styles.xml`), in centimeters
+const TEXT_WIDTH: f32 = 17.0;
+/// Maximal height of an image, in centimeters
+const MAX_IMAGE_HEIGHT: f32 = 22.0;
 
 /// Rendererer for ODT
-///
-/// Still very experimental.
 pub struct OdtRenderer<'a> {
     book: &'a Book,
-    current_numbering: i32,
-    current_hide: bool,
-    current_chapter: i32,
-    automatic_styles: String,
+    source: Source,
+    numbering: TextNumbering<'a>,
+    /// Style of the paragraphs currently rendered (e.g. `Quotations` in a blockquote)
+    paragraph_style: &'static str,
+    /// Style of the paragraphs of the table cells currently rendered
+    cell_style: &'static str,
+    handler: ResourceHandler,
+    /// Images to embed: path in the archive, media type and content
+    pictures: Vec<(String, String, Vec<u8>)>,
+    footnote_number: u32,
+    table_number: u32,
+    image_number: u32,
 }
 
 impl<'a> OdtRenderer<'a> {
     /// Creates a new OdtRenderer
     pub fn new(book: &'a Book) -> OdtRenderer {
+        let mut handler = ResourceHandler::new();
+        handler.set_images_mapping(true);
         OdtRenderer {
             book: book,
-            current_chapter: 1,
-            current_numbering: book.options.get_i32("rendering.num_depth").unwrap(),
-            current_hide: false,
-            automatic_styles: String::from("
-<style:style style:name=\"T1\" \
-                                            style:family=\"text\">
-  <style:text-properties \
-                                            fo:font-style=\"italic\" \
-                                            style:font-style-asian=\"italic\" \
-                                            style:font-style-complex=\"italic\"/>
-</style:style>
-\
-                                            <style:style style:name=\"T2\" \
-                                            style:family=\"text\">
-  <style:text-properties \
-                                            fo:font-weight=\"bold\" \
-                                            style:font-weight-asian=\"bold\" \
-                                            style:font-weight-complex=\"bold\"/>
-</style:style>"),
+            source: Source::empty(),
+            numbering: TextNumbering::new(book),
+            paragraph_style: "Text_20_body",
+            cell_style: "Table_20_Contents",
+            handler: handler,
+            pictures: vec![],
+            footnote_number: 0,
+            table_number: 0,
+            image_number: 0,
         }
     }
 
@@ -60,174 +68,385 @@ impl<'a> OdtRenderer<'a> {
     ///   ODT format, or the generation of the ODT file itself.
     pub fn render_book(&mut self, to: &mut Write) -> Result<String> {
         let content = self.render_content()?;
+        let content = self.render_template(odt::CONTENT, "content.xml", content)?;
+        let styles = self.render_template(odt::STYLES, "styles.xml", String::new())?;
+        let meta = self.render_template(odt::META, "meta.xml", String::new())?;
 
         let mut zipper =
             Zipper::new(&self.book.options.get_path("crowbook.temp_dir").unwrap())?;
-
-        // Write template.odt there
-        zipper.write("template.odt", odt::ODT, false)?;
-        // unzip it
-        zipper.unzip("template.odt")?;
-        // Complete it with content.xml
+        zipper.write("mimetype", b"application/vnd.oasis.opendocument.text", false)?;
         zipper.write("content.xml", content.as_bytes(), false)?;
+        zipper.write("styles.xml", styles.as_bytes(), false)?;
+        zipper.write("meta.xml", meta.as_bytes(), false)?;
+
+        let mut manifest = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<manifest:manifest xmlns:manifest=\"urn:oasis:names:tc:opendocument:xmlns:manifest:1.0\" \
+                                         manifest:version=\"1.2\">
+ <manifest:file-entry manifest:full-path=\"/\" manifest:version=\"1.2\" \
+                                         manifest:media-type=\"application/vnd.oasis.opendocument.text\"/>
+ <manifest:file-entry manifest:full-path=\"content.xml\" manifest:media-type=\"text/xml\"/>
+ <manifest:file-entry manifest:full-path=\"styles.xml\" manifest:media-type=\"text/xml\"/>
+ <manifest:file-entry manifest:full-path=\"meta.xml\" manifest:media-type=\"text/xml\"/>
+");
+        for &(ref path, ref media_type, ref data) in &self.pictures {
+            zipper.write(path, data, false)?;
+            manifest.push_str(&format!(" <manifest:file-entry manifest:full-path=\"{}\" \
+                                        manifest:media-type=\"{}\"/>\n",
+                                       path,
+                                       media_type));
+        }
+        manifest.push_str("</manifest:manifest>\n");
+        zipper.write("META-INF/manifest.xml", manifest.as_bytes(), false)?;
+
         // Zip and copy
         zipper.generate_odt(self.book.options.get_str("crowbook.zip.command").unwrap(),
                             to)
     }
 
-    /// Render content.xml
-    fn render_content(&mut self) -> Result<String> {
-        // Print a warning for the features that aren't supported in ODT.
-        let mut missing = vec![];
-        if self.book.features.image { missing.push(lformat!("images")); }
-        if self.book.features.blockquote { missing.push(lformat!("blockquotes")); }
-        if self.book.features.codeblock { missing.push(lformat!("codeblocks")); }
-        if self.book.features.ordered_list { missing.push(lformat!("ordered lists")); }
-        if self.book.features.footnote { missing.push(lformat!("footnotes")); }
-        if self.book.features.table { missing.push(lformat!("tables")); }
-        if self.book.features.superscript { missing.push(lformat!("superscript")); }
-        if self.book.features.subscript { missing.push(lformat!("subscript")); }
-
-        if !missing.is_empty() {
-            let missing = missing.join(", ");
-            warn!("{}", lformat!("ODT: The document uses the following features, that are not implemented for ODT output: {features}. They will be ignored in the generated document.",
-                                 features = missing));
-        }
-
-        
-        let mut content = String::new();
-
-        for chapter in &self.book.chapters {
-            let n = chapter.number;
-            let v = &chapter.content;
-            self.current_hide = false;
-            match n {
-                Number::Unnumbered | Number::UnnumberedPart => self.current_numbering = 0,
-                Number::Default | Number::DefaultPart => {
-                    self.current_numbering =
-                        self.book.options.get_i32("rendering.num_depth").unwrap()
-                }
-                Number::Specified(n) | Number::SpecifiedPart(n) => {
-                    self.current_numbering = self.book.options.get_i32("numbering").unwrap();
-                    self.current_chapter = n;
-                }
-                Number::Hidden => {
-                    self.current_numbering = 0;
-                    self.current_hide = true;
-                }
-           } 
-            if n.is_part() {
-                error!("{}", lformat!("Parts are not supported yet in ODT"));
-            }
-
-            for token in v {
-                content.push_str(&self.parse_token(token));
-            }
-        }
-
-        let template = compile_str(odt::CONTENT,
-                                   &self.book.source,
-                                   "could not compile template for content.xml")?;
+    /// Renders one of the XML files of the archive
+    fn render_template(&self, template: &str, name: &str, content: String) -> Result<String> {
+        let template = compile_str(template, &self.book.source, name)?;
+        let lang = self.book.options.get_str("lang").unwrap();
+        let mut parts = lang.splitn(2, |c| c == '_' || c == '-');
+        let language = parts.next().unwrap_or("en").to_lowercase();
+        let country = parts.next().unwrap_or("none").to_uppercase();
         let data = self.book.get_metadata(|s| Ok(s.to_owned()))?
             .insert_str("content", content)
-            .insert_str("automatic_styles", &self.automatic_styles)
+            .insert_str("odt_lang", lang.replace('_', "-"))
+            .insert_str("odt_language", language)
+            .insert_str("odt_country", country)
             .build();
 
         let mut res: Vec<u8> = vec![];
         template.render_data(&mut res, &data)?;
         match String::from_utf8(res) {
-            Err(_) => panic!(lformat!("generated content.xml was not utf-8 valid")),
+            Err(_) => panic!(lformat!("generated {file} was not utf-8 valid", file = name)),
             Ok(res) => Ok(res),
         }
     }
 
+    /// Render the body of content.xml
+    fn render_content(&mut self) -> Result<String> {
+        let mut content = String::new();
+        let book = self.book;
+
+        for chapter in &book.chapters {
+            self.source = Source::new(chapter.filename.as_str());
+            self.numbering.start_chapter(chapter.number);
+
+            content.push_str(&self.render_vec(&chapter.content)?);
+        }
+        self.source = Source::empty();
+        Ok(content)
+    }
+
     /// Transform a vector of `Token`s to Odt format
-    fn render_vec(&mut self, tokens: &[Token]) -> String {
+    #[doc(hidden)]
+    pub fn render_vec(&mut self, tokens: &[Token]) -> Result<String> {
         let mut res = String::new();
 
         for token in tokens {
-            res.push_str(&self.parse_token(token));
+            res.push_str(&self.render_token(token)?);
+        }
+        Ok(res)
+    }
+
+    /// Renders tokens that can be either blocks or inline elements (e.g. in
+    /// list items, table cells or footnotes), wrapping the latter in
+    /// paragraphs with the given style
+    fn render_blocks(&mut self, tokens: &[Token], style: &'static str) -> Result<String> {
+        let previous = mem::replace(&mut self.paragraph_style, style);
+        let mut res = String::new();
+        let mut inline = String::new();
+        for token in tokens {
+            if is_block(token) {
+                if !inline.trim().is_empty() {
+                    res.push_str(&format!("<text:p text:style-name=\"{}\">{}</text:p>\n",
+                                          style,
+                                          inline));
+                }
+                inline.clear();
+                res.push_str(&self.render_token(token)?);
+            } else {
+                inline.push_str(&self.render_token(token)?);
+            }
         }
-        res
+        if !inline.trim().is_empty() {
+            res.push_str(&format!("<text:p text:style-name=\"{}\">{}</text:p>\n", style, inline));
+        }
+        if res.is_empty() {
+            // Some elements must contain at least a paragraph
+            res = format!("<text:p text:style-name=\"{}\"/>\n", style);
+        }
+        self.paragraph_style = previous;
+        Ok(res)
     }
 
-    fn parse_token(&mut self, token: &Token) -> String {
-        match *token {
+    fn render_token(&mut self, token: &Token) -> Result<String> {
+        let res = match *token {
             Token::Str(ref text) => escape::html(self.book.clean(text.as_str(), false)).into_owned(),
             Token::Paragraph(ref vec) => {
-                format!("<text:p text:style-name=\"Text_20_body\">{}</text:p>\n",
-                        self.render_vec(vec))
+                let content = self.render_vec(vec)?;
+                format!("<text:p text:style-name=\"{}\">{}</text:p>\n",
+                        self.paragraph_style,
+                        content)
             }
             Token::Header(n, ref vec) => {
-                if n == 1 && self.current_hide {
-                    return String::new();
+                if n == 1 && self.numbering.is_hidden() {
+                    return Ok(String::new());
                 }
-                let s = if n == 1 && self.current_numbering >= 1 {
-                    let chapter = self.current_chapter;
-                    self.current_chapter += 1;
-                    let res = self.book.get_chapter_header(chapter, self.render_vec(vec), |s| {
-                        Ok(self.render_vec(&Parser::new().parse_inline(s)?))
-                    });
-                    res.unwrap().text
-                } else {
-                    self.render_vec(vec)
+                let book = self.book;
+                let title = self.render_vec(vec)?;
+                let is_part = self.numbering.is_part();
+                let s = match self.numbering.next_number(n) {
+                    Some(number) => {
+                        let f = |s: &str| self.render_vec(&Parser::new().parse_inline(s)?);
+                        if is_part {
+                            book.get_part_header(number, title, f)?.text
+                        } else {
+                            book.get_chapter_header(number, title, f)?.text
+                        }
+                    }
+                    None => title,
                 };
-                format!("<text:h text:style-name=\"Heading_20_{}\">\n{}</text:h>\n",
-                        n,
-                        s)
+                if n == 1 && is_part {
+                    format!("<text:h text:style-name=\"Part\" text:outline-level=\"1\">{}</text:h>\n",
+                            s)
+                } else {
+                    let n = n.min(6);
+                    format!("<text:h text:style-name=\"Heading_20_{}\" \
+                             text:outline-level=\"{}\">{}</text:h>\n",
+                            n,
+                            n,
+                            s)
+                }
             }
             Token::Emphasis(ref vec) => {
-                format!("<text:span text:style-name=\"T1\">{}</text:span>",
-                        self.render_vec(vec))
+                format!("<text:span text:style-name=\"Emphasis\">{}</text:span>",
+                        self.render_vec(vec)?)
             }
             Token::Strong(ref vec) => {
-                format!("<text:span text:style-name=\"T2\">{}</text:span>",
-                        self.render_vec(vec))
+                format!("<text:span text:style-name=\"Strong_20_Emphasis\">{}</text:span>",
+                        self.render_vec(vec)?)
+            }
+            Token::Code(ref vec) => {
+                format!("<text:span text:style-name=\"Source_20_Text\">{}</text:span>",
+                        self.render_vec(vec)?)
+            }
+            Token::Superscript(ref vec) => {
+                format!("<text:span text:style-name=\"Superscript\">{}</text:span>",
+                        self.render_vec(vec)?)
+            }
+            Token::Subscript(ref vec) => {
+                format!("<text:span text:style-name=\"Subscript\">{}</text:span>",
+                        self.render_vec(vec)?)
             }
-            Token::List(ref vec) => format!("<text:list>\n{}</text:list>\n", self.render_vec(vec)),
-            Token::OrderedList(_, ref vec) => {
-                format!("<text:list>\n{}</text:list>\n", self.render_vec(vec))
+            Token::List(ref vec) => {
+                format!("<text:list text:style-name=\"List_20_1\">\n{}</text:list>\n",
+                        self.render_vec(vec)?)
+            }
+            Token::OrderedList(start, ref vec) => {
+                let mut items = self.render_vec(vec)?;
+                if start != 1 {
+                    // The first number is set on the first item
+                    items = items.replacen("<text:list-item>",
+                                           &format!("<text:list-item text:start-value=\"{}\">",
+                                                    start),
+                                           1);
+                }
+                format!("<text:list text:style-name=\"Numbering_20_1\">\n{}</text:list>\n",
+                        items)
             }
             Token::Item(ref vec) => {
-                format!("<text:list-item>\n<text:p>{}</text:p></text:list-item>",
-                        self.render_vec(vec))
+                format!("<text:list-item>\n{}</text:list-item>\n",
+                        self.render_blocks(vec, "List_20_Contents")?)
             }
             Token::Link(ref url, _, ref vec) => {
-                format!("<text:a xlink:type=\"simple\"  xlink:href=\"{}\">{}</text:a>",
-                        url,
-                        self.render_vec(vec))
-            }
-            Token::Code(ref vec) => {
-                format!("<text:span text:style-name=\"Preformatted_20_Text\">{}</text:span>",
-                        self.render_vec(vec))
+                format!("<text:a xlink:type=\"simple\" xlink:href=\"{}\" \
+                         text:style-name=\"Internet_20_link\">{}</text:a>",
+                        escape::html(url.as_str()),
+                        self.render_vec(vec)?)
             }
-            Token::Subscript(ref vec) | Token::Superscript(ref vec) => self.render_vec(vec),
-            Token::BlockQuote(ref vec) |
+            Token::BlockQuote(ref vec) => self.render_blocks(vec, "Quotations")?,
             Token::CodeBlock(_, ref vec) => {
-                format!("<text:p text:style-name=\"Text_20_Body\">{}</text:p>\n",
-                        self.render_vec(vec))
+                let code = view_as_text(vec);
+                let mut res = String::new();
+                for line in code.trim_right_matches('\n').lines() {
+                    res.push_str(&format!("<text:p text:style-name=\"Preformatted_20_Text\">{}</text:p>\n",
+                                          preserve_spaces(&escape::html(line))));
+                }
+                res
+            }
+            Token::SoftBreak => String::from(" "),
+            Token::HardBreak => String::from("<text:line-break/>"),
+            Token::Rule => String::from("<text:p text:style-name=\"Rule\">* * *</text:p>\n"),
+            Token::Image(ref url, ref title, ref alt) => self.render_image(url, title, alt, false)?,
+            Token::StandaloneImage(ref url, ref title, ref alt) => {
+                format!("<text:p text:style-name=\"Figure\">{}</text:p>\n",
+                        self.render_image(url, title, alt, true)?)
+            }
+            Token::Table(_, ref vec) => {
+                // Number of columns, from the first row
+                let columns = vec.iter()
+                    .filter_map(|row| row.inner())
+                    .next()
+                    .map(|cells| cells.len())
+                    .unwrap_or(1)
+                    .max(1);
+                self.table_number += 1;
+                let content = self.render_vec(vec)?;
+                format!("<table:table table:name=\"Table{}\" table:style-name=\"Table\">
+<table:table-column table:number-columns-repeated=\"{}\"/>
+{}</table:table>\n",
+                        self.table_number,
+                        columns,
+                        content)
             }
-            Token::SoftBreak | Token::HardBreak => String::from(" "),
-            Token::Rule => String::from("<text:p /><text:p>***</text:p><text:p />"),
-            Token::Image(_, _, _) |
-            Token::StandaloneImage(_, _, _) => {
-                String::from(" ")
+            Token::TableHead(ref vec) => {
+                self.cell_style = "Table_20_Heading";
+                let row = self.render_vec(vec);
+                self.cell_style = "Table_20_Contents";
+                format!("<table:table-header-rows>
+<table:table-row>
+{}</table:table-row>
+</table:table-header-rows>\n",
+                        row?)
             }
-            Token::Table(_, _) |
-            Token::TableHead(_) |
-            Token::TableRow(_) |
-            Token::TableCell(_) => {
-                String::from(" ")
+            Token::TableRow(ref vec) => {
+                format!("<table:table-row>\n{}</table:table-row>\n", self.render_vec(vec)?)
             }
-            Token::Footnote(_) => {
-                String::new()
+            Token::TableCell(ref vec) => {
+                let style = self.cell_style;
+                let content = if vec.iter().any(is_block) {
+                    self.render_blocks(vec, style)?
+                } else {
+                    // The parser keeps the spaces around the text of a cell
+                    format!("<text:p text:style-name=\"{}\">{}</text:p>\n",
+                            style,
+                            self.render_vec(vec)?.trim())
+                };
+                format!("<table:table-cell table:style-name=\"Table_20_Cell\" \
+                         office:value-type=\"string\">\n{}</table:table-cell>\n",
+                        content)
             }
-            Token::Annotation(_, ref vec) => self.render_vec(vec),
+            Token::Footnote(ref vec) => {
+                self.footnote_number += 1;
+                let number = self.footnote_number;
+                format!("<text:note text:id=\"ftn{}\" text:note-class=\"footnote\">\
+                         <text:note-citation>{}</text:note-citation>\
+                         <text:note-body>\n{}</text:note-body></text:note>",
+                        number,
+                        number,
+                        self.render_blocks(vec, "Footnote")?)
+            }
+            Token::Annotation(_, ref vec) => self.render_vec(vec)?,
             Token::__NonExhaustive => unreachable!(),
+        };
+        Ok(res)
+    }
+
+    /// Embeds an image in the `Pictures` directory, and returns the frame
+    /// displaying it
+    fn render_image(&mut self, url: &str, title: &str, alt: &[Token], standalone: bool) -> Result<String> {
+        if !ResourceHandler::is_local(url) {
+            warn!("{}", lformat!("ODT: image {url} is not a local file and can't be embedded, \
+                                  displaying its alternative text instead",
+                                 url = url));
+            return self.render_vec(alt);
+        }
+        let dest = self.handler.map_image(&self.source, url)?.into_owned();
+        let path = format!("Pictures/{}", dest.trim_left_matches("images/"));
+        if !self.pictures.iter().any(|&(ref p, _, _)| p == &path) {
+            let mut data = vec![];
+            File::open(url)
+                .and_then(|mut f| f.read_to_end(&mut data))
+                .map_err(|_| Error::file_not_found(&self.source, lformat!("image"), url.to_owned()))?;
+            let media_type = guess_mime_type_opt(url)
+                .map(|m| m.to_string())
+                .unwrap_or_else(|| String::from("application/octet-stream"));
+            self.pictures.push((path.clone(), media_type, data));
         }
+        let size = self.pictures
+            .iter()
+            .find(|&&(ref p, _, _)| p == &path)
+            .and_then(|&(_, _, ref data)| misc::image_size(data));
+
+        // Images are displayed at 96 DPI, or at 80% of the text width for
+        // standalone ones (as in HTML), and must fit in a page
+        let (mut width, mut height) = match size {
+            Some((w, h)) if w > 0 && h > 0 => {
+                let (w, h) = (w as f32 * 2.54 / 96.0, h as f32 * 2.54 / 96.0);
+                if standalone {
+                    (TEXT_WIDTH * 0.8, h * TEXT_WIDTH * 0.8 / w)
+                } else {
+                    (w, h)
+                }
+            }
+            _ => {
+                warn!("{}", lformat!("ODT: could not read the size of image {url}",
+                                     url = url));
+                (TEXT_WIDTH * 0.8, TEXT_WIDTH * 0.6)
+            }
+        };
+        if width > TEXT_WIDTH {
+            height = height * TEXT_WIDTH / width;
+            width = TEXT_WIDTH;
+        }
+        if height > MAX_IMAGE_HEIGHT {
+            width = width * MAX_IMAGE_HEIGHT / height;
+            height = MAX_IMAGE_HEIGHT;
+        }
+
+        self.image_number += 1;
+        let title = if title.is_empty() {
+            String::new()
+        } else {
+            format!("<svg:title>{}</svg:title>", escape::html(title))
+        };
+        Ok(format!("<draw:frame draw:style-name=\"Graphics\" draw:name=\"Image{}\" \
+                    text:anchor-type=\"as-char\" svg:width=\"{:.2}cm\" svg:height=\"{:.2}cm\" \
+                    draw:z-index=\"0\"><draw:image xlink:href=\"{}\" xlink:type=\"simple\" \
+                    xlink:show=\"embed\" xlink:actuate=\"onLoad\"/>{}<svg:desc>{}</svg:desc>\
+                    </draw:frame>",
+                   self.image_number,
+                   width,
+                   height,
+                   path,
+                   title,
+                   escape::html(view_as_text(alt))))
     }
 }
 
+/// Keeps the spaces and tabs of a line of code, which would otherwise
+/// be collapsed
+fn preserve_spaces(line: &str) -> String {
+    let mut res = String::new();
+    let mut spaces = 0;
+    for c in line.chars().chain(Some('\n')) {
+        if c == ' ' {
+            spaces += 1;
+            continue;
+        }
+        if spaces > 0 {
+            if res.is_empty() {
+                res.push_str(&format!("<text:s text:c=\"{}\"/>", spaces));
+            } else {
+                res.push(' ');
+                if spaces > 1 {
+                    res.push_str(&format!("<text:s text:c=\"{}\"/>", spaces - 1));
+                }
+            }
+            spaces = 0;
+        }
+        match c {
+            '\t' => res.push_str("<text:tab/>"),
+            '\n' => (),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
 
 pub struct Odt {}
 
@@ -235,7 +454,7 @@ impl BookRenderer for Odt {
     fn auto_path(&self, book_name: &str) -> Result<String> {
         Ok(format!("{}.odt", book_name))
     }
-    
+
     fn render(&self, book: &Book, to: &mut Write) -> Result<()> {
         OdtRenderer::new(book)
             .render_book(to)?;
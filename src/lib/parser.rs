@@ -15,7 +15,7 @@
 // You should have received ba copy of the GNU Lesser General Public License
 // along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
 
-use token::Token;
+use token::{Token, Data};
 use error::{Result, Error, Source};
 use book::Book;
 
@@ -115,6 +115,9 @@ pub struct Parser {
 
     html_as_text: bool,
     superscript: bool,
+
+    /// Offsets in the source of the top-level tokens being parsed
+    offsets: Vec<usize>,
 }
 
 impl Parser {
@@ -126,6 +129,7 @@ impl Parser {
             features: Features::new(),
             html_as_text: true,
             superscript: false,
+            offsets: vec![],
         }
     }
 
@@ -169,6 +173,12 @@ impl Parser {
 
     /// Parse a string and returns an AST  an Error.
     pub fn parse(&mut self, s: &str) -> Result<Vec<Token>> {
+        self.parse_with_lines(s).map(|(tokens, _)| tokens)
+    }
+
+    /// Parse a string and returns an AST, along with the line where each
+    /// of its top-level tokens starts in the source, or an Error.
+    pub fn parse_with_lines(&mut self, s: &str) -> Result<(Vec<Token>, Vec<u32>)> {
         let mut opts = Options::empty();
         opts.insert(OPTION_ENABLE_TABLES);
         opts.insert(OPTION_ENABLE_FOOTNOTES);
@@ -176,11 +186,20 @@ impl Parser {
 
 
         let mut res = vec![];
+        self.offsets.clear();
         self.parse_events(&mut p, &mut res, None)?;
+        let mut lines: Vec<u32> = self.offsets
+            .iter()
+            .map(|offset| {
+                let offset = (*offset).min(s.len());
+                s.as_bytes()[..offset].iter().filter(|b| **b == b'\n').count() as u32 + 1
+            })
+            .collect();
+        self.offsets.clear();
 
         self.parse_footnotes(&mut res)?;
 
-        collapse(&mut res);
+        collapse(&mut res, Some(&mut lines));
 
         find_standalone(&mut res);
 
@@ -190,7 +209,7 @@ impl Parser {
             self.parse_sub_vec(&mut res);
         }
         
-        Ok(res)
+        Ok((res, lines))
     }
 
     /// Parse an inline string and returns a list of `Token`.
@@ -321,20 +340,47 @@ impl Parser {
         }
     }
 
+    /// Adds some HTML code, either as text or not at all, according to `html_as_text`
+    fn push_html(&self, v: &mut Vec<Token>, text: String) {
+        if self.html_as_text {
+            v.push(Token::Str(text));
+        } else {
+            debug!("{}", lformat!("ignoring HTML block '{}'", text));
+        }
+    }
+
     fn parse_events<'a>(&mut self,
                         p: &mut CMParser<'a>,
                         v: &mut Vec<Token>,
                         current_tag: Option<&Tag>)
                         -> Result<()> {
+        // Currently open `<span>` tags, with the index in `v` where their
+        // content starts and their language, if they set one
+        let mut spans: Vec<Option<(usize, String)>> = vec![];
         while let Some(event) = p.next() {
+            let len = v.len();
+            let offset = p.get_offset();
             match event {
-                Event::Html(text) | Event::InlineHtml(text) => {
-                    if self.html_as_text {
-                        v.push(Token::Str(text.into_owned()));
-                    } else {
-                        debug!("{}", lformat!("ignoring HTML block '{}'", text));
+                Event::InlineHtml(text) => {
+                    match span_lang(&text) {
+                        Some(Some(lang)) => spans.push(Some((v.len(), lang))),
+                        Some(None) => {
+                            spans.push(None);
+                            self.push_html(v, text.into_owned());
+                        }
+                        None if text.trim() == "</span>" => {
+                            match spans.pop() {
+                                Some(Some((start, lang))) => {
+                                    let content = v.split_off(start);
+                                    v.push(Token::Annotation(Data::Lang(lang), content));
+                                }
+                                _ => self.push_html(v, text.into_owned()),
+                            }
+                        }
+                        None => self.push_html(v, text.into_owned()),
                     }
-                }, 
+                }
+                Event::Html(text) => self.push_html(v, text.into_owned()),
 
                 Event::Text(text) => {
                     v.push(Token::Str(text.into_owned()));
@@ -354,6 +400,11 @@ impl Parser {
                     v.push(Token::Footnote(vec![Token::Str(text.into_owned())]))
                 }
             }
+            if current_tag.is_none() {
+                for _ in len..v.len() {
+                    self.offsets.push(offset);
+                }
+            }
         }
         Ok(())
     }
@@ -513,14 +564,42 @@ fn parse_super_sub(s: &str, c: u8) -> Option<Vec<Token>> {
     return None;
 }
 
+/// If `tag` is an opening `<span>` tag, returns its `lang` attribute (if any).
+fn span_lang(tag: &str) -> Option<Option<String>> {
+    let tag = tag.trim();
+    if !tag.starts_with("<span") || !tag.ends_with('>') {
+        return None;
+    }
+    let rest = &tag["<span".len()..tag.len() - 1];
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        // e.g. <spanner>
+        return None;
+    }
+    for quote in &['"', '\''] {
+        let pattern = format!(" lang={}", quote);
+        if let Some(start) = rest.find(&pattern) {
+            let value = &rest[start + pattern.len()..];
+            if let Some(end) = value.find(*quote) {
+                return Some(Some(value[..end].trim().to_owned()));
+            }
+        }
+    }
+    Some(None)
+}
+
 /// Replace consecutives Strs by a Str of both, collapse soft breaks to previous std and so on
-fn collapse(ast: &mut Vec<Token>) {
+///
+/// If `lines` is set, the lines of the removed tokens are removed too.
+fn collapse(ast: &mut Vec<Token>, mut lines: Option<&mut Vec<u32>>) {
     let mut i = 0;
     while i < ast.len() {
         if ast[i].is_str() && i + 1 < ast.len() {
             if ast[i + 1].is_str() {
                 // Two consecutives Str, concatenate them
                 let token = ast.remove(i + 1);
+                if let Some(ref mut lines) = lines {
+                    lines.remove(i + 1);
+                }
                 if let (&mut Token::Str(ref mut dest), Token::Str(ref source)) = (&mut ast[i],
                                                                                   token) {
                     //                        dest.push(' ');
@@ -531,6 +610,9 @@ fn collapse(ast: &mut Vec<Token>) {
                 }
             } else if ast[i + 1] == Token::SoftBreak {
                 ast.remove(i + 1);
+                if let Some(ref mut lines) = lines {
+                    lines.remove(i + 1);
+                }
                 if let &mut Token::Str(ref mut dest) = &mut ast[i] {
                     dest.push(' ');
                     continue;
@@ -542,7 +624,7 @@ fn collapse(ast: &mut Vec<Token>) {
 
         // If token is containing others, recurse into them
         if let Some(ref mut inner) = ast[i].inner_mut() {
-            collapse(inner);
+            collapse(inner, None);
         }
         i += 1;
     }
@@ -0,0 +1,1777 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Builtin PDF renderer, which lays out the book itself instead of running
+//! LaTeX or Typst.
+//!
+//! It only uses the standard PDF fonts (Times and Courier), which don't need
+//! to be embedded, so characters that are not in the Windows-1252 (Latin-1)
+//! character set can't be displayed.
+
+use book::{Book, compile_str};
+use error::{Error, Result, Source};
+use token::Token;
+use parser::Parser;
+use text_view::{view_as_text, is_block, TextNumbering};
+use resource_handler::ResourceHandler;
+use latex;
+use misc;
+
+use std::io;
+use std::io::Read;
+use std::fs::File;
+use std::mem;
+use std::collections::HashMap;
+
+/// Ratio between the height of a line and the font size
+const LEADING: f32 = 1.25;
+/// Indentation of paragraphs, in ems
+const PARINDENT: f32 = 1.5;
+/// Ratio between the font size of footnotes and of the main text
+const NOTE_SCALE: f32 = 0.8;
+
+/// Widths of ASCII characters (from space to tilde) in Times-Roman, in
+/// thousandths of em
+static TIMES_ROMAN: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444,
+    921, 722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889, 722, 722,
+    556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333, 278, 333, 469, 500,
+    333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500,
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541];
+
+/// Widths of ASCII characters in Times-Bold
+static TIMES_BOLD: [u16; 95] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    930, 722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944, 722, 778,
+    611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667, 333, 278, 333, 581, 500,
+    333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833, 556, 500,
+    556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520];
+
+/// Widths of ASCII characters in Times-Italic
+static TIMES_ITALIC: [u16; 95] = [
+    250, 333, 420, 500, 500, 833, 778, 214, 333, 333, 500, 675, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 675, 675, 675, 500,
+    920, 611, 611, 667, 722, 611, 611, 722, 722, 333, 444, 667, 556, 833, 667, 722,
+    611, 722, 611, 500, 556, 722, 611, 833, 611, 556, 556, 389, 278, 389, 422, 500,
+    333, 500, 500, 444, 500, 444, 278, 500, 500, 278, 278, 444, 278, 722, 500, 500,
+    500, 500, 389, 389, 278, 500, 444, 667, 444, 444, 389, 400, 275, 400, 541];
+
+/// Widths of ASCII characters in Times-BoldItalic
+static TIMES_BOLD_ITALIC: [u16; 95] = [
+    250, 389, 555, 500, 500, 833, 778, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    832, 667, 667, 667, 722, 667, 667, 722, 778, 389, 500, 667, 611, 889, 722, 722,
+    611, 722, 667, 556, 611, 722, 667, 889, 667, 611, 611, 333, 278, 333, 570, 500,
+    333, 500, 500, 444, 500, 444, 333, 500, 556, 278, 278, 500, 278, 778, 556, 500,
+    500, 500, 389, 389, 278, 556, 444, 667, 500, 444, 389, 348, 220, 348, 570];
+
+/// Base letters of the accented Latin-1 characters (from 0xC0 to 0xFF), whose
+/// widths are used for them
+static LATIN1_BASE: &'static [u8; 64] =
+    b"AAAAAAACEEEEIIIIDNOOOOO+OUUUUYPsaaaaaaaceeeeiiiionooooo+ouuuuypy";
+
+/// One of the standard PDF fonts used by the renderer
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+    Mono,
+}
+
+static FONTS: [Font; 5] = [Font::Regular, Font::Bold, Font::Italic, Font::BoldItalic, Font::Mono];
+
+impl Font {
+    /// Name of the font in the resources of a page
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Italic => "F3",
+            Font::BoldItalic => "F4",
+            Font::Mono => "F5",
+        }
+    }
+
+    /// Name of the standard font
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Regular => "Times-Roman",
+            Font::Bold => "Times-Bold",
+            Font::Italic => "Times-Italic",
+            Font::BoldItalic => "Times-BoldItalic",
+            Font::Mono => "Courier",
+        }
+    }
+
+    /// Width of a (Windows-1252 encoded) character, in thousandths of em
+    fn width(self, byte: u8) -> u16 {
+        let table = match self {
+            Font::Regular => &TIMES_ROMAN,
+            Font::Bold => &TIMES_BOLD,
+            Font::Italic => &TIMES_ITALIC,
+            Font::BoldItalic => &TIMES_BOLD_ITALIC,
+            Font::Mono => return 600,
+        };
+        let ascii = |c: u8| table[(c - 32) as usize];
+        match byte {
+            32...126 => ascii(byte),
+            0x8A => ascii(b'S'),
+            0x8E => ascii(b'Z'),
+            0x9A => ascii(b's'),
+            0x9E => ascii(b'z'),
+            0x9F => ascii(b'Y'),
+            0xA0 => ascii(b' '),
+            0xAD => ascii(b'-'),
+            0x82 => ascii(b','),
+            0x85 | 0x89 | 0x97 => 1000,
+            0x88 | 0x8B | 0x91 | 0x92 | 0x98 | 0x9B => 333,
+            0x84 | 0x93 | 0x94 => 444,
+            0x95 => 350,
+            0xB0 => 400,
+            0xB7 => 250,
+            0xA9 | 0xAE => 760,
+            0x99 => 980,
+            0x8C | 0xC6 => 889,
+            0x9C => 722,
+            0xE6 => 667,
+            0xDF => 500,
+            0xC0...0xFF => ascii(LATIN1_BASE[(byte - 0xC0) as usize]),
+            _ => 500,
+        }
+    }
+}
+
+/// Returns the Windows-1252 code of a character, if it has one
+fn win_ansi(c: char) -> Option<u8> {
+    let byte = match c {
+        ' '...'~' | '\u{a0}'...'\u{ff}' => c as u32 as u8,
+        '€' => 0x80,
+        '‚' => 0x82,
+        'ƒ' => 0x83,
+        '„' => 0x84,
+        '…' => 0x85,
+        '†' => 0x86,
+        '‡' => 0x87,
+        'ˆ' => 0x88,
+        '‰' => 0x89,
+        'Š' => 0x8A,
+        '‹' => 0x8B,
+        'Œ' => 0x8C,
+        'Ž' => 0x8E,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '˜' => 0x98,
+        '™' => 0x99,
+        'š' => 0x9A,
+        '›' => 0x9B,
+        'œ' => 0x9C,
+        'ž' => 0x9E,
+        'Ÿ' => 0x9F,
+        // Thin and narrow non-breaking spaces (e.g. inserted by the French cleaner)
+        '\u{2007}' | '\u{2009}' | '\u{202f}' => 0xA0,
+        '\u{2010}' | '\u{2011}' => b'-',
+        _ => return None,
+    };
+    Some(byte)
+}
+
+/// Width of the text, in points
+fn text_width(font: Font, size: f32, text: &[u8]) -> f32 {
+    text.iter().map(|&c| font.width(c) as f32).sum::<f32>() * size / 1000.0
+}
+
+/// Destination of a link
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    /// External link
+    Uri(String),
+    /// Start of a chapter, given by its index
+    Chapter(usize),
+    /// A position in the content, given by the page index and the vertical
+    /// position
+    Position(usize, f32),
+}
+
+/// Text style
+#[derive(Debug, Clone)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    mono: bool,
+    size: f32,
+    /// Vertical shift of the baseline (for superscript and subscript)
+    rise: f32,
+    link: Option<Target>,
+}
+
+impl Style {
+    fn new(size: f32) -> Style {
+        Style {
+            bold: false,
+            italic: false,
+            mono: false,
+            size: size,
+            rise: 0.0,
+            link: None,
+        }
+    }
+
+    fn font(&self) -> Font {
+        match (self.mono, self.bold, self.italic) {
+            (true, _, _) => Font::Mono,
+            (false, true, true) => Font::BoldItalic,
+            (false, true, false) => Font::Bold,
+            (false, false, true) => Font::Italic,
+            (false, false, false) => Font::Regular,
+        }
+    }
+}
+
+/// Some text in a single style, or an inline image
+#[derive(Debug, Clone)]
+struct Fragment {
+    /// Text, encoded in Windows-1252
+    text: Vec<u8>,
+    font: Font,
+    size: f32,
+    rise: f32,
+    width: f32,
+    link: Option<Target>,
+    /// Index of the image to draw instead of text
+    image: Option<usize>,
+    /// Index of the footnote whose mark this is
+    note: Option<usize>,
+}
+
+/// Inline elements, before they are grouped into words
+#[derive(Debug)]
+enum Inline {
+    Text(Fragment),
+    Space,
+    Break,
+}
+
+/// A word, i.e. fragments that can't be separated by a line break
+#[derive(Debug, Clone)]
+struct Word {
+    fragments: Vec<Fragment>,
+    width: f32,
+    /// Width of the space that follows the word
+    space: f32,
+    /// True if there is a hard line break after the word
+    hard_break: bool,
+}
+
+impl Word {
+    fn new() -> Word {
+        Word {
+            fragments: vec![],
+            width: 0.0,
+            space: 0.0,
+            hard_break: false,
+        }
+    }
+}
+
+/// A line of text, once it has been broken
+#[derive(Debug, Clone)]
+struct Line {
+    words: Vec<Word>,
+    indent: f32,
+    /// True if the line can be justified (i.e. it doesn't end a paragraph)
+    justify: bool,
+    height: f32,
+}
+
+impl Line {
+    fn new(words: Vec<Word>, indent: f32, justify: bool) -> Line {
+        let size = words.iter()
+            .flat_map(|w| w.fragments.iter())
+            .map(|f| f.size)
+            .fold(0.0, f32::max);
+        Line {
+            words: words,
+            indent: indent,
+            justify: justify,
+            height: size * LEADING,
+        }
+    }
+
+    /// Natural width of the line, with its spaces
+    fn width(&self) -> f32 {
+        let mut width = 0.0;
+        for (i, word) in self.words.iter().enumerate() {
+            width += word.width;
+            if i + 1 < self.words.len() {
+                width += word.space;
+            }
+        }
+        width
+    }
+
+    /// Footnotes whose marks are in this line
+    fn notes(&self) -> Vec<usize> {
+        self.words.iter()
+            .flat_map(|w| w.fragments.iter())
+            .filter_map(|f| f.note)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Center,
+    Justify,
+}
+
+/// Groups inline elements into words
+fn to_words(inlines: Vec<Inline>) -> Vec<Word> {
+    let mut words = vec![];
+    let mut current = Word::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(fragment) => {
+                current.width += fragment.width;
+                current.fragments.push(fragment);
+            }
+            Inline::Space => {
+                if !current.fragments.is_empty() {
+                    let space = {
+                        let last = current.fragments.last().unwrap();
+                        text_width(last.font, last.size, b" ")
+                    };
+                    current.space = space;
+                    words.push(mem::replace(&mut current, Word::new()));
+                }
+            }
+            Inline::Break => {
+                if !current.fragments.is_empty() {
+                    current.hard_break = true;
+                    words.push(mem::replace(&mut current, Word::new()));
+                } else if let Some(word) = words.last_mut() {
+                    word.hard_break = true;
+                }
+            }
+        }
+    }
+    if !current.fragments.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Breaks words into lines of at most `width` points (except for words that
+/// are larger than that)
+fn break_lines(words: Vec<Word>, width: f32, first_indent: f32, justify: bool) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut current: Vec<Word> = vec![];
+    let mut current_width = 0.0;
+    let mut indent = first_indent;
+    for word in words {
+        let added = match current.last() {
+            Some(last) => last.space + word.width,
+            None => word.width,
+        };
+        if !current.is_empty() && current_width + added > width - indent {
+            lines.push(Line::new(mem::replace(&mut current, vec![]), indent, justify));
+            indent = 0.0;
+            current_width = word.width;
+        } else {
+            current_width += added;
+        }
+        let hard_break = word.hard_break;
+        current.push(word);
+        if hard_break {
+            lines.push(Line::new(mem::replace(&mut current, vec![]), indent, false));
+            indent = 0.0;
+            current_width = 0.0;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(Line::new(current, indent, false));
+    }
+    lines
+}
+
+/// Formats a number for a PDF file
+fn num(x: f32) -> String {
+    format!("{:.2}", x)
+}
+
+/// Returns a PDF hexadecimal string
+fn hex_string(bytes: &[u8]) -> String {
+    let mut res = String::with_capacity(bytes.len() * 2 + 2);
+    res.push('<');
+    for byte in bytes {
+        res.push_str(&format!("{:02X}", byte));
+    }
+    res.push('>');
+    res
+}
+
+/// Returns a PDF text string (encoded in UTF-16), for metadata and bookmarks
+fn text_string(text: &str) -> String {
+    let mut res = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        res.push_str(&format!("{:04X}", unit));
+    }
+    res.push('>');
+    res
+}
+
+/// Returns a PDF literal string, for ASCII text such as URLs
+fn literal_string(text: &str) -> String {
+    let mut res = String::from("(");
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                res.push('\\');
+                res.push(c);
+            }
+            _ => res.push(c),
+        }
+    }
+    res.push(')');
+    res
+}
+
+/// An image, as it is stored in the PDF file
+struct PdfImage {
+    width: u32,
+    height: u32,
+    color_space: String,
+    bits: u8,
+    filter: &'static str,
+    parms: Option<String>,
+    /// True for CMYK JPEG files, which are usually inverted
+    invert: bool,
+    data: Vec<u8>,
+}
+
+fn be16(data: &[u8], i: usize) -> u32 {
+    ((data[i] as u32) << 8) | data[i + 1] as u32
+}
+
+fn be32(data: &[u8], i: usize) -> u32 {
+    (be16(data, i) << 16) | be16(data, i + 2)
+}
+
+/// Reads the dimensions of a JPEG file, which can be included as is
+fn parse_jpeg(data: Vec<u8>) -> Option<PdfImage> {
+    if data.len() < 4 || data[0] != 0xFF || data[1] != 0xD8 {
+        return None;
+    }
+    let mut i = 2;
+    while i + 9 < data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+        let marker = data[i + 1];
+        if marker == 0xFF {
+            // Fill byte
+            i += 1;
+            continue;
+        }
+        match marker {
+            0xC0...0xC3 | 0xC5...0xC7 | 0xC9...0xCB | 0xCD...0xCF => {
+                let height = be16(&data, i + 5);
+                let width = be16(&data, i + 7);
+                let components = data[i + 9];
+                let color_space = match components {
+                    1 => "/DeviceGray",
+                    3 => "/DeviceRGB",
+                    4 => "/DeviceCMYK",
+                    _ => return None,
+                };
+                return Some(PdfImage {
+                    width: width,
+                    height: height,
+                    color_space: color_space.to_owned(),
+                    bits: 8,
+                    filter: "/DCTDecode",
+                    parms: None,
+                    invert: components == 4,
+                    data: data,
+                });
+            }
+            _ => i += 2 + be16(&data, i + 2) as usize,
+        }
+    }
+    None
+}
+
+/// Reads a PNG file, whose compressed data can be included as is if it is
+/// not interlaced and has no alpha channel
+fn parse_png(data: &[u8]) -> Option<PdfImage> {
+    if data.len() < 8 || &data[..8] != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    let mut i = 8;
+    let mut header = None;
+    let mut palette = None;
+    let mut idat = vec![];
+    while i + 8 <= data.len() {
+        let length = be32(data, i) as usize;
+        let kind = &data[i + 4..i + 8];
+        let start = i + 8;
+        let end = start + length;
+        if end > data.len() {
+            return None;
+        }
+        let chunk = &data[start..end];
+        if kind == b"IHDR" && length >= 13 {
+            header = Some((be32(chunk, 0), be32(chunk, 4), chunk[8], chunk[9], chunk[12]));
+        } else if kind == b"PLTE" {
+            palette = Some(chunk.to_vec());
+        } else if kind == b"IDAT" {
+            idat.extend_from_slice(chunk);
+        } else if kind == b"IEND" {
+            break;
+        }
+        // Skip the CRC
+        i = end + 4;
+    }
+    let (width, height, bits, color, interlace) = match header {
+        Some(header) => header,
+        None => return None,
+    };
+    if interlace != 0 {
+        return None;
+    }
+    let (color_space, colors) = match (color, palette) {
+        (0, _) => (String::from("/DeviceGray"), 1),
+        (2, _) => (String::from("/DeviceRGB"), 3),
+        (3, Some(palette)) => {
+            (format!("[/Indexed /DeviceRGB {} {}]",
+                     palette.len() / 3 - 1,
+                     hex_string(&palette)),
+             1)
+        }
+        // Alpha channel, which would require to decompress the image
+        _ => return None,
+    };
+    Some(PdfImage {
+        width: width,
+        height: height,
+        color_space: color_space,
+        bits: bits,
+        filter: "/FlateDecode",
+        parms: Some(format!("<< /Predictor 15 /Colors {} /BitsPerComponent {} /Columns {} >>",
+                            colors,
+                            bits,
+                            width)),
+        invert: false,
+        data: idat,
+    })
+}
+
+/// A page, whose horizontal coordinates are relative to the left margin
+/// (which depends on the position of the page in the final document)
+struct Page {
+    ops: String,
+    links: Vec<([f32; 4], Target)>,
+    images: Vec<usize>,
+    numbered: bool,
+}
+
+impl Page {
+    fn new() -> Page {
+        Page {
+            ops: String::new(),
+            links: vec![],
+            images: vec![],
+            numbered: true,
+        }
+    }
+}
+
+/// Writes the objects of a PDF file, and the cross-reference table
+struct PdfWriter {
+    objects: Vec<Vec<u8>>,
+}
+
+impl PdfWriter {
+    fn new() -> PdfWriter {
+        PdfWriter { objects: vec![] }
+    }
+
+    /// Reserves an object number, for an object that will be set later
+    fn reserve(&mut self) -> usize {
+        self.objects.push(vec![]);
+        self.objects.len()
+    }
+
+    fn set(&mut self, id: usize, content: String) {
+        self.objects[id - 1] = content.into_bytes();
+    }
+
+    fn add(&mut self, content: String) -> usize {
+        let id = self.reserve();
+        self.set(id, content);
+        id
+    }
+
+    fn add_stream(&mut self, dict: &str, data: &[u8]) -> usize {
+        let mut content = format!("<< {} /Length {} >>\nstream\n", dict, data.len()).into_bytes();
+        content.extend_from_slice(data);
+        content.extend_from_slice(b"\nendstream");
+        self.objects.push(content);
+        self.objects.len()
+    }
+
+    fn finish(&self, root: usize, info: usize) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = vec![];
+        for (i, object) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.extend_from_slice(object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = out.len();
+        out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1)
+                              .as_bytes());
+        for offset in offsets {
+            out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        out.extend_from_slice(format!("trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\n\
+                                       startxref\n{}\n%%EOF\n",
+                                      self.objects.len() + 1,
+                                      root,
+                                      info,
+                                      xref)
+                              .as_bytes());
+        out
+    }
+}
+
+/// Builtin PDF renderer
+pub struct PdfRenderer<'a> {
+    book: &'a Book,
+    source: Source,
+
+    page_width: f32,
+    page_height: f32,
+    inside: f32,
+    outside: f32,
+    top: f32,
+    bottom: f32,
+    twoside: bool,
+    text_width: f32,
+    size: f32,
+
+    pages: Vec<Page>,
+    /// Vertical position of the top of the next line
+    y: f32,
+    /// Height taken by footnotes at the bottom of the current page
+    notes_height: f32,
+    /// Footnotes of the current page
+    page_notes: Vec<usize>,
+    /// Lines of all footnotes
+    notes: Vec<Vec<Line>>,
+
+    /// Indentation of the current block, on the left and on the right
+    indent: f32,
+    right_indent: f32,
+    /// True if the next paragraph must be indented
+    indent_paragraph: bool,
+    /// Bullet or number of a list item, drawn before its first line
+    marker: Option<Fragment>,
+
+    images: Vec<PdfImage>,
+    loaded_images: HashMap<String, Option<usize>>,
+    /// Headers, with their level, title and position
+    headings: Vec<(i32, String, usize, f32)>,
+    /// Position of the start of each chapter
+    chapter_starts: Vec<(usize, f32)>,
+    chapter_files: HashMap<String, usize>,
+    missing_chars: bool,
+
+    numbering: TextNumbering<'a>,
+}
+
+impl<'a> PdfRenderer<'a> {
+    /// Creates a new PdfRenderer
+    pub fn new(book: &'a Book) -> PdfRenderer<'a> {
+        let (page_width, page_height) = page_size(book);
+        let twoside = latex::is_book_class(book.options.get_str("tex.class").unwrap());
+        let inside = length(book,
+                            &["tex.margin.inside", "tex.margin.left"],
+                            if twoside { "1.5cm" } else { "2cm" }) +
+                     book.options
+            .get_str("tex.margin.gutter")
+            .ok()
+            .and_then(misc::tex_length)
+            .map_or(0.0, |gutter| gutter * 72.0 / 72.27);
+        let outside = length(book, &["tex.margin.outside", "tex.margin.right"], "2cm");
+        let size = book.options.get_i32("tex.font.size").unwrap_or(10) as f32;
+        PdfRenderer {
+            book: book,
+            source: Source::empty(),
+            page_width: page_width,
+            page_height: page_height,
+            inside: inside,
+            outside: outside,
+            top: length(book, &["tex.margin.top"], "2cm"),
+            bottom: length(book, &["tex.margin.bottom"], "1.5cm"),
+            twoside: twoside,
+            text_width: page_width - inside - outside,
+            size: size,
+            pages: vec![],
+            y: 0.0,
+            notes_height: 0.0,
+            page_notes: vec![],
+            notes: vec![],
+            indent: 0.0,
+            right_indent: 0.0,
+            indent_paragraph: false,
+            marker: None,
+            images: vec![],
+            loaded_images: HashMap::new(),
+            headings: vec![],
+            chapter_starts: vec![],
+            chapter_files: HashMap::new(),
+            missing_chars: false,
+            numbering: TextNumbering::new(book),
+        }
+    }
+
+    /// Renders the book to a PDF file
+    pub fn render_pdf(&mut self, to: &mut io::Write) -> Result<()> {
+        let book = self.book;
+
+        // Title page, rendered first so it is the first page
+        let mut prefix = vec![];
+        if book.options.get_bool("tex.title").unwrap() {
+            self.title_page()?;
+            self.finish_page();
+            prefix = mem::replace(&mut self.pages, vec![]);
+        }
+
+        // Content
+        self.new_page();
+        for (i, chapter) in book.chapters.iter().enumerate() {
+            self.chapter_files.insert(chapter.filename.clone(), i);
+        }
+        for (i, chapter) in book.chapters.iter().enumerate() {
+            self.source = Source::new(chapter.filename.as_str());
+            self.numbering.start_chapter(chapter.number);
+            // Chapters that start with a header start on a new page
+            if chapter.content.first().map_or(false, |t| t.is_header()) {
+                self.page_break();
+            }
+            let start = (self.pages.len() - 1, self.y);
+            self.chapter_starts.push(start);
+            self.indent_paragraph = false;
+            self.blocks(&chapter.content)?;
+            debug_assert!(self.chapter_starts.len() == i + 1);
+        }
+        self.finish_page();
+        self.source = Source::empty();
+        let content = mem::replace(&mut self.pages, vec![]);
+
+        // The table of contents is rendered last, since it needs the page
+        // numbers, but the number of its pages doesn't depend on them
+        let mut toc = vec![];
+        if book.options.get_bool("rendering.inline_toc").unwrap() {
+            let len = self.toc(0)?.len();
+            toc = self.toc(prefix.len() + len)?;
+        }
+        if self.missing_chars {
+            warn!("{}", lformat!("PDF: some characters can't be displayed by the builtin PDF \
+                                  engine, which only supports Latin characters"));
+        }
+
+        let offset = prefix.len() + toc.len();
+        let mut pages = prefix;
+        pages.extend(toc);
+        pages.extend(content);
+        let pdf = self.write_pdf(&pages, offset)?;
+        to.write_all(&pdf)
+            .map_err(|e| {
+                Error::render(&book.source,
+                              lformat!("problem when writing PDF: {error}", error = e))
+            })?;
+        Ok(())
+    }
+
+    /// Renders the title page
+    fn title_page(&mut self) -> Result<()> {
+        self.new_page();
+        if let Some(page) = self.pages.last_mut() {
+            page.numbered = false;
+        }
+        self.y -= (self.page_height - self.top - self.bottom) * 0.25;
+        let book = self.book;
+        let title = plain_text(book.options.get_str("title").unwrap())?;
+        let size = self.size;
+        self.centered(&title, size * 2.4, true)?;
+        if let Ok(subtitle) = book.options.get_str("subtitle") {
+            self.y -= size;
+            let subtitle = plain_text(subtitle)?;
+            self.centered(&subtitle, size * 1.6, false)?;
+        }
+        let authors: Vec<String> = book.authors()
+            .into_iter()
+            .map(|author| author.name)
+            .collect();
+        if !authors.is_empty() {
+            self.y -= size * 3.0;
+            let authors = plain_text(&authors.join(", "))?;
+            self.centered(&authors, size * 1.4, false)?;
+        }
+        Ok(())
+    }
+
+    /// Renders the table of contents, with page numbers shifted by `offset`
+    fn toc(&mut self, offset: usize) -> Result<Vec<Page>> {
+        let content = mem::replace(&mut self.pages, vec![]);
+        self.new_page();
+
+        let book = self.book;
+        let name = book.options.get_str("rendering.inline_toc.name").unwrap();
+        let name = compile_str(name, &book.source, "rendering.inline_toc.name")?;
+        let mut res = vec![];
+        name.render_data(&mut res, &book.get_metadata(|s| Ok(s.to_owned()))?.build())?;
+        let name = String::from_utf8_lossy(&res).into_owned();
+        let size = self.size;
+        self.y -= size * 3.0;
+        let mut style = Style::new(size * 2.0);
+        style.bold = true;
+        let mut inlines = vec![];
+        self.push_text(&name, &style, &mut inlines);
+        let lines = break_lines(to_words(inlines), self.text_width, 0.0, false);
+        for line in &lines {
+            self.place_line(line, Align::Left);
+        }
+        self.y -= size * 2.0;
+
+        let depth = book.options.get_i32("rendering.num_depth").unwrap().max(1);
+        let headings = self.headings.clone();
+        for (level, title, page, y) in headings {
+            if level > depth {
+                continue;
+            }
+            let number = format!("{}", page + offset + 1);
+            let number_width = text_width(Font::Regular, size, number.as_bytes());
+            let mut style = Style::new(size);
+            style.bold = level == 1;
+            style.link = Some(Target::Position(page, y));
+            let mut inlines = vec![];
+            self.push_text(&title, &style, &mut inlines);
+            let indent = (level - 1) as f32 * size * PARINDENT;
+            let lines = break_lines(to_words(inlines),
+                                    self.text_width - indent - 3.0 * size,
+                                    0.0,
+                                    false);
+            self.indent = indent;
+            let mut baseline = 0.0;
+            for line in &lines {
+                baseline = self.place_line(line, Align::Left);
+            }
+            self.indent = 0.0;
+            let x = self.text_width - number_width;
+            let mut fragment = self.fragment(number.into_bytes(), &Style::new(size));
+            fragment.link = Some(Target::Position(page, y));
+            self.draw_fragment(&fragment, x, baseline);
+        }
+        self.finish_page();
+        Ok(mem::replace(&mut self.pages, content))
+    }
+
+    /// Renders a line of centered text
+    fn centered(&mut self, text: &str, size: f32, bold: bool) -> Result<()> {
+        let mut style = Style::new(size);
+        style.bold = bold;
+        let mut inlines = vec![];
+        self.push_text(text, &style, &mut inlines);
+        let lines = break_lines(to_words(inlines), self.text_width, 0.0, false);
+        for line in &lines {
+            self.place_line(line, Align::Center);
+        }
+        Ok(())
+    }
+
+    /// Left margin of a page, according to its position in the document
+    fn left_margin(&self, page: usize) -> f32 {
+        if self.twoside && page % 2 == 1 {
+            self.outside
+        } else {
+            self.inside
+        }
+    }
+
+    /// Width available for the lines of the current block
+    fn line_width(&self) -> f32 {
+        self.text_width - self.indent - self.right_indent
+    }
+
+    /// Lowest position a line can reach on the current page
+    fn limit(&self) -> f32 {
+        self.bottom + self.notes_height
+    }
+
+    fn page_is_empty(&self) -> bool {
+        self.pages.last().map_or(true, |p| p.ops.is_empty())
+    }
+
+    /// Starts a new page, unless the current one is empty
+    fn page_break(&mut self) {
+        if !self.page_is_empty() {
+            self.new_page();
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.finish_page();
+        self.pages.push(Page::new());
+        self.y = self.page_height - self.top;
+        self.notes_height = 0.0;
+    }
+
+    /// Adds vertical space (in ems), unless at the top of a page
+    fn skip(&mut self, ems: f32) {
+        if !self.page_is_empty() {
+            self.y -= ems * self.size;
+        }
+    }
+
+    /// Draws the footnotes of the current page
+    fn finish_page(&mut self) {
+        if self.page_notes.is_empty() {
+            return;
+        }
+        let notes: Vec<usize> = self.page_notes.drain(..).collect();
+        let mut y = self.bottom + self.notes_height;
+        let separator = self.size * NOTE_SCALE;
+        let ops = format!("0.5 w 0 {y} m {x} {y} l S\n",
+                          y = num(y - separator / 2.0),
+                          x = num(self.text_width * 0.3));
+        self.push_ops(&ops);
+        y -= separator;
+        for n in notes {
+            let lines = self.notes[n].clone();
+            for line in &lines {
+                let width = self.text_width;
+                self.draw_line(line, 0.0, width, y - line.height * 0.8, Align::Justify);
+                y -= line.height;
+            }
+        }
+    }
+
+    /// Height taken by a footnote
+    fn note_height(&self, n: usize) -> f32 {
+        self.notes[n].iter().map(|l| l.height).sum()
+    }
+
+    fn push_ops(&mut self, ops: &str) {
+        if let Some(page) = self.pages.last_mut() {
+            page.ops.push_str(ops);
+        }
+    }
+
+    /// Places a line at the current position, starting a new page if there
+    /// isn't enough room. Returns the position of its baseline.
+    fn place_line(&mut self, line: &Line, align: Align) -> f32 {
+        let notes = line.notes();
+        let notes_extra = |this: &Self| -> f32 {
+            let mut extra: f32 = notes.iter().map(|&n| this.note_height(n)).sum();
+            if !notes.is_empty() && this.page_notes.is_empty() {
+                extra += this.size * NOTE_SCALE;
+            }
+            extra
+        };
+        let mut extra = notes_extra(self);
+        if self.y - line.height < self.limit() + extra && !self.page_is_empty() {
+            self.new_page();
+            extra = notes_extra(self);
+        }
+        let baseline = self.y - line.height * 0.8;
+        if let Some(marker) = self.marker.take() {
+            let x = self.indent - marker.width - self.size * 0.5;
+            self.draw_fragment(&marker, x, baseline);
+        }
+        let x = self.indent + line.indent;
+        let width = self.line_width() - line.indent;
+        self.draw_line(line, x, width, baseline, align);
+        self.y -= line.height;
+        self.notes_height += extra;
+        self.page_notes.extend_from_slice(&notes);
+        baseline
+    }
+
+    /// Draws the words of a line
+    fn draw_line(&mut self, line: &Line, x: f32, width: f32, baseline: f32, align: Align) {
+        let natural = line.width();
+        let gaps = line.words.len().saturating_sub(1);
+        let (mut x, extra) = match align {
+            Align::Justify if line.justify && gaps > 0 && natural < width => {
+                (x, (width - natural) / gaps as f32)
+            }
+            Align::Center => (x + (width - natural) / 2.0, 0.0),
+            _ => (x, 0.0),
+        };
+        for (i, word) in line.words.iter().enumerate() {
+            for fragment in &word.fragments {
+                self.draw_fragment(fragment, x, baseline);
+                x += fragment.width;
+            }
+            if i < gaps {
+                x += word.space + extra;
+            }
+        }
+    }
+
+    fn draw_fragment(&mut self, fragment: &Fragment, x: f32, baseline: f32) {
+        let y = baseline + fragment.rise;
+        let ops = match fragment.image {
+            Some(image) => {
+                if let Some(page) = self.pages.last_mut() {
+                    page.images.push(image);
+                }
+                format!("q {} 0 0 {} {} {} cm /Im{} Do Q\n",
+                        num(fragment.width),
+                        num(fragment.size),
+                        num(x),
+                        num(y - fragment.size * 0.2),
+                        image)
+            }
+            None => {
+                format!("BT /{} {} Tf {} {} Td {} Tj ET\n",
+                        fragment.font.resource(),
+                        num(fragment.size),
+                        num(x),
+                        num(y),
+                        hex_string(&fragment.text))
+            }
+        };
+        self.push_ops(&ops);
+        if let Some(ref target) = fragment.link {
+            if let Some(page) = self.pages.last_mut() {
+                page.links.push(([x,
+                                  baseline - fragment.size * 0.2,
+                                  x + fragment.width,
+                                  baseline + fragment.size * 0.8],
+                                 target.clone()));
+            }
+        }
+    }
+
+    /// Creates a fragment of (encoded) text
+    fn fragment(&self, text: Vec<u8>, style: &Style) -> Fragment {
+        let font = style.font();
+        Fragment {
+            width: text_width(font, style.size, &text),
+            text: text,
+            font: font,
+            size: style.size,
+            rise: style.rise,
+            link: style.link.clone(),
+            image: None,
+            note: None,
+        }
+    }
+
+    /// Splits text into fragments and spaces
+    fn push_text(&mut self, text: &str, style: &Style, out: &mut Vec<Inline>) {
+        let mut current = vec![];
+        for c in text.chars() {
+            if c.is_whitespace() && win_ansi(c) != Some(0xA0) {
+                if !current.is_empty() {
+                    let fragment = self.fragment(mem::replace(&mut current, vec![]), style);
+                    out.push(Inline::Text(fragment));
+                }
+                out.push(Inline::Space);
+            } else {
+                match win_ansi(c) {
+                    Some(byte) => current.push(byte),
+                    None => {
+                        self.missing_chars = true;
+                        current.push(b'?');
+                    }
+                }
+            }
+        }
+        if !current.is_empty() {
+            let fragment = self.fragment(current, style);
+            out.push(Inline::Text(fragment));
+        }
+    }
+
+    /// Converts inline tokens to fragments and spaces
+    fn inline(&mut self, tokens: &[Token], style: &Style, out: &mut Vec<Inline>) -> Result<()> {
+        for token in tokens {
+            match *token {
+                Token::Str(ref text) => {
+                    let text = self.book.clean(text.as_str(), false);
+                    self.push_text(&text, style, out);
+                }
+                Token::SoftBreak => out.push(Inline::Space),
+                Token::HardBreak => out.push(Inline::Break),
+                Token::Emphasis(ref vec) => {
+                    let mut style = style.clone();
+                    style.italic = !style.italic;
+                    self.inline(vec, &style, out)?;
+                }
+                Token::Strong(ref vec) => {
+                    let mut style = style.clone();
+                    style.bold = true;
+                    self.inline(vec, &style, out)?;
+                }
+                Token::Code(ref vec) => {
+                    let mut style = style.clone();
+                    style.mono = true;
+                    self.push_text(&view_as_text(vec), &style, out);
+                }
+                Token::Superscript(ref vec) => {
+                    let mut style = style.clone();
+                    style.rise += style.size * 0.35;
+                    style.size *= 0.7;
+                    self.inline(vec, &style, out)?;
+                }
+                Token::Subscript(ref vec) => {
+                    let mut style = style.clone();
+                    style.rise -= style.size * 0.15;
+                    style.size *= 0.7;
+                    self.inline(vec, &style, out)?;
+                }
+                Token::Link(ref url, _, ref vec) => {
+                    let mut style = style.clone();
+                    style.link = self.target(url);
+                    self.inline(vec, &style, out)?;
+                }
+                Token::Image(ref url, _, ref vec) |
+                Token::StandaloneImage(ref url, _, ref vec) => {
+                    match self.load_image(url) {
+                        Some(image) => {
+                            let ratio = self.images[image].width as f32 /
+                                        self.images[image].height as f32;
+                            let mut fragment = self.fragment(vec![], style);
+                            fragment.width = style.size * ratio;
+                            fragment.image = Some(image);
+                            out.push(Inline::Text(fragment));
+                        }
+                        None => self.inline(vec, style, out)?,
+                    }
+                }
+                Token::Footnote(ref vec) => self.footnote(vec, style, out)?,
+                Token::Paragraph(ref vec) => {
+                    // Paragraphs inside footnotes or table cells
+                    if !out.is_empty() {
+                        out.push(Inline::Break);
+                    }
+                    self.inline(vec, style, out)?;
+                }
+                _ => {
+                    if let Some(inner) = token.inner() {
+                        self.inline(inner, style, out)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lays out a footnote, and adds its mark to the text
+    fn footnote(&mut self, tokens: &[Token], style: &Style, out: &mut Vec<Inline>) -> Result<()> {
+        let n = self.notes.len();
+        // Reserve the index, in case of footnotes inside this one
+        self.notes.push(vec![]);
+        let number = format!("{}", n + 1);
+
+        let size = self.size * NOTE_SCALE;
+        let mut mark_style = Style::new(size * 0.7);
+        mark_style.rise = size * 0.35;
+        let mut inlines = vec![Inline::Text(self.fragment(number.clone().into_bytes(),
+                                                          &mark_style)),
+                               Inline::Space];
+        self.inline(tokens, &Style::new(size), &mut inlines)?;
+        self.notes[n] = break_lines(to_words(inlines), self.text_width, 0.0, true);
+
+        let mut mark_style = style.clone();
+        mark_style.rise += style.size * 0.35;
+        mark_style.size *= 0.7;
+        mark_style.link = None;
+        let mut fragment = self.fragment(number.into_bytes(), &mark_style);
+        fragment.note = Some(n);
+        out.push(Inline::Text(fragment));
+        Ok(())
+    }
+
+    /// Returns the destination of a link
+    fn target(&self, url: &str) -> Option<Target> {
+        if !ResourceHandler::is_local(url) {
+            return Some(Target::Uri(url.to_owned()));
+        }
+        let file = url.split('#').next().unwrap_or("");
+        if let Some(&i) = self.chapter_files.get(file) {
+            return Some(Target::Chapter(i));
+        }
+        // Links to the .html version of a chapter
+        let md = format!("{}", ::std::path::Path::new(file).with_extension("md").display());
+        self.chapter_files.get(&md).map(|&i| Target::Chapter(i))
+    }
+
+    /// Loads an image, returning its index, if it has a supported format
+    fn load_image(&mut self, url: &str) -> Option<usize> {
+        if let Some(image) = self.loaded_images.get(url) {
+            return *image;
+        }
+        let image = if ResourceHandler::is_local(url) {
+            let mut data = vec![];
+            match File::open(url).and_then(|mut f| f.read_to_end(&mut data)) {
+                Ok(_) => {
+                    let image = if data.starts_with(b"\x89PNG") {
+                        parse_png(&data)
+                    } else {
+                        parse_jpeg(data)
+                    };
+                    if image.is_none() {
+                        warn!("{}", lformat!("PDF ({source}): image '{url}' has an unsupported \
+                                              format; only JPEG and PNG images without \
+                                              transparency are supported",
+                                             source = self.source,
+                                             url = url));
+                    }
+                    image
+                }
+                Err(_) => {
+                    warn!("{}", lformat!("PDF ({source}): could not read image '{url}'",
+                                         source = self.source,
+                                         url = url));
+                    None
+                }
+            }
+        } else {
+            debug!("{}", lformat!("PDF ({source}): image '{url}' doesn't seem to be \
+                                   local; ignoring it.",
+                                  source = self.source,
+                                  url = url));
+            None
+        };
+        let index = match image {
+            Some(image) => {
+                self.images.push(image);
+                Some(self.images.len() - 1)
+            }
+            None => None,
+        };
+        self.loaded_images.insert(url.to_owned(), index);
+        index
+    }
+
+    /// Renders a list of blocks, grouping inline tokens into paragraphs
+    fn blocks(&mut self, tokens: &[Token]) -> Result<()> {
+        let mut start = 0;
+        for (i, token) in tokens.iter().enumerate() {
+            if is_block(token) {
+                if start < i {
+                    self.paragraph(&tokens[start..i])?;
+                }
+                self.block(token)?;
+                start = i + 1;
+            }
+        }
+        if start < tokens.len() {
+            self.paragraph(&tokens[start..])?;
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, token: &Token) -> Result<()> {
+        let size = self.size;
+        match *token {
+            Token::Paragraph(ref vec) => self.paragraph(vec)?,
+            Token::Header(n, ref vec) => self.header(n, vec)?,
+            Token::BlockQuote(ref vec) => {
+                self.skip(0.5);
+                self.indent += size * 2.0;
+                self.right_indent += size * 2.0;
+                self.indent_paragraph = false;
+                self.blocks(vec)?;
+                self.indent -= size * 2.0;
+                self.right_indent -= size * 2.0;
+                self.skip(0.5);
+            }
+            Token::CodeBlock(_, ref vec) => self.code_block(&view_as_text(vec)),
+            Token::Rule => {
+                self.skip(1.0);
+                if self.y - size < self.limit() {
+                    self.new_page();
+                }
+                let width = self.line_width() * 0.3;
+                let x = self.indent + (self.line_width() - width) / 2.0;
+                let ops = format!("0.5 w {x0} {y} m {x1} {y} l S\n",
+                                  x0 = num(x),
+                                  x1 = num(x + width),
+                                  y = num(self.y - size / 2.0));
+                self.push_ops(&ops);
+                self.y -= size * 2.0;
+            }
+            Token::List(ref vec) => self.list(None, vec)?,
+            Token::OrderedList(start, ref vec) => self.list(Some(start), vec)?,
+            Token::Table(n, ref vec) => self.table(n, vec)?,
+            Token::StandaloneImage(ref url, _, ref vec) => {
+                match self.load_image(url) {
+                    Some(image) => self.standalone_image(image),
+                    None => self.paragraph(vec)?,
+                }
+            }
+            _ => unreachable!(),
+        }
+        self.indent_paragraph = false;
+        Ok(())
+    }
+
+    fn paragraph(&mut self, tokens: &[Token]) -> Result<()> {
+        let style = Style::new(self.size);
+        let mut inlines = vec![];
+        self.inline(tokens, &style, &mut inlines)?;
+        // Only paragraphs of the main text are indented
+        let indent = if self.indent_paragraph && self.indent == 0.0 {
+            self.size * PARINDENT
+        } else {
+            0.0
+        };
+        self.indent_paragraph = true;
+        let lines = break_lines(to_words(inlines), self.line_width(), indent, true);
+        for line in &lines {
+            self.place_line(line, Align::Justify);
+        }
+        Ok(())
+    }
+
+    fn header(&mut self, n: i32, tokens: &[Token]) -> Result<()> {
+        if n == 1 && self.numbering.is_hidden() {
+            self.page_break();
+            return Ok(());
+        }
+        let is_part = n == 1 && self.numbering.is_part();
+        let scale = match n {
+            1 if is_part => 2.4,
+            1 => 2.0,
+            2 => 1.5,
+            3 => 1.25,
+            _ => 1.1,
+        };
+        let mut style = Style::new(self.size * scale);
+        style.bold = true;
+        let mut inlines = vec![];
+        let title = match self.numbering.numbered_header(n, tokens)? {
+            Some(text) => {
+                self.push_text(&text, &style, &mut inlines);
+                text
+            }
+            None => {
+                self.inline(tokens, &style, &mut inlines)?;
+                view_as_text(tokens)
+            }
+        };
+        let lines = break_lines(to_words(inlines), self.line_width(), 0.0, false);
+        let height: f32 = lines.iter().map(|l| l.height).sum();
+
+        if n == 1 {
+            self.page_break();
+            let room = if is_part {
+                (self.page_height - self.top - self.bottom) * 0.3
+            } else {
+                self.size * 3.0
+            };
+            self.y -= room;
+        } else {
+            // Keep the header with the beginning of the next paragraph
+            let before = self.size * scale;
+            let needed = before + height + self.size * LEADING * 2.0;
+            if self.y - needed < self.limit() {
+                self.new_page();
+            } else {
+                self.skip(scale);
+            }
+        }
+        let position = (self.pages.len() - 1, self.y);
+        self.headings.push((n, title, position.0, position.1));
+
+        let align = if is_part {
+            Align::Center
+        } else {
+            Align::Left
+        };
+        for line in &lines {
+            self.place_line(line, align);
+        }
+        self.y -= self.size * if n == 1 { 2.0 } else { 0.5 };
+        self.indent_paragraph = false;
+        Ok(())
+    }
+
+    fn code_block(&mut self, code: &str) {
+        let size = self.size * 0.85;
+        let mut style = Style::new(size);
+        style.mono = true;
+        self.skip(0.5);
+        self.indent += self.size;
+        let max_chars = ((self.line_width() / (size * 0.6)) as usize).max(1);
+        for source_line in code.trim_right_matches('\n').split('\n') {
+            let source_line = source_line.replace('\t', "    ");
+            let mut bytes = vec![];
+            for c in source_line.chars() {
+                match win_ansi(c) {
+                    Some(byte) => bytes.push(byte),
+                    None => {
+                        self.missing_chars = true;
+                        bytes.push(b'?');
+                    }
+                }
+            }
+            // Long lines are broken at the maximum number of characters
+            let mut chunks: Vec<Vec<u8>> = bytes.chunks(max_chars).map(|c| c.to_vec()).collect();
+            if chunks.is_empty() {
+                chunks.push(vec![]);
+            }
+            for chunk in chunks {
+                let fragment = self.fragment(chunk, &style);
+                let word = Word {
+                    width: fragment.width,
+                    fragments: vec![fragment],
+                    space: 0.0,
+                    hard_break: false,
+                };
+                let line = Line::new(vec![word], 0.0, false);
+                self.place_line(&line, Align::Left);
+            }
+        }
+        self.indent -= self.size;
+        self.skip(0.5);
+    }
+
+    fn list(&mut self, start: Option<usize>, items: &[Token]) -> Result<()> {
+        let size = self.size;
+        if self.marker.is_none() {
+            self.skip(0.3);
+        }
+        self.indent += size * PARINDENT;
+        let mut number = start.unwrap_or(1);
+        for item in items {
+            let label = match start {
+                Some(_) => format!("{}.", number),
+                None => String::from("•"),
+            };
+            number += 1;
+            let mut inlines = vec![];
+            self.push_text(&label, &Style::new(size), &mut inlines);
+            if let Some(Inline::Text(fragment)) = inlines.pop() {
+                self.marker = Some(fragment);
+            }
+            self.indent_paragraph = false;
+            match *item {
+                Token::Item(ref vec) => self.blocks(vec)?,
+                ref token => self.blocks(&[token.clone()])?,
+            }
+            self.marker = None;
+        }
+        self.indent -= size * PARINDENT;
+        self.skip(0.3);
+        Ok(())
+    }
+
+    fn table(&mut self, columns: i32, rows: &[Token]) -> Result<()> {
+        let columns = columns.max(1) as usize;
+        let column_width = self.line_width() / columns as f32;
+        let pad = self.size * 0.3;
+        self.skip(0.5);
+        for row in rows {
+            let (cells, head) = match *row {
+                Token::TableHead(ref cells) => (cells, true),
+                Token::TableRow(ref cells) => (cells, false),
+                _ => continue,
+            };
+            let mut style = Style::new(self.size);
+            style.bold = head;
+            let mut cell_lines = vec![];
+            for cell in cells.iter().take(columns) {
+                let mut inlines = vec![];
+                match *cell {
+                    Token::TableCell(ref vec) => self.inline(vec, &style, &mut inlines)?,
+                    ref token => self.inline(&[token.clone()], &style, &mut inlines)?,
+                }
+                cell_lines.push(break_lines(to_words(inlines),
+                                            column_width - 2.0 * pad,
+                                            0.0,
+                                            false));
+            }
+            let height = cell_lines.iter()
+                .map(|lines| lines.iter().map(|l| l.height).sum::<f32>())
+                .fold(self.size * LEADING, f32::max) + 2.0 * pad;
+            if self.y - height < self.limit() && !self.page_is_empty() {
+                self.new_page();
+            }
+            let top = self.y;
+            for (i, lines) in cell_lines.iter().enumerate() {
+                let x = self.indent + column_width * i as f32;
+                let mut y = top - pad;
+                for line in lines {
+                    self.draw_line(line, x + pad, column_width - 2.0 * pad, y - line.height * 0.8,
+                                   Align::Left);
+                    y -= line.height;
+                    // Footnotes of table cells
+                    for n in line.notes() {
+                        self.notes_height += self.note_height(n);
+                        if self.page_notes.is_empty() {
+                            self.notes_height += self.size * NOTE_SCALE;
+                        }
+                        self.page_notes.push(n);
+                    }
+                }
+                let ops = format!("0.5 w {} {} {} {} re S\n",
+                                  num(x),
+                                  num(top - height),
+                                  num(column_width),
+                                  num(height));
+                self.push_ops(&ops);
+            }
+            self.y -= height;
+        }
+        self.skip(0.5);
+        Ok(())
+    }
+
+    fn standalone_image(&mut self, image: usize) {
+        // Images are displayed at 96 DPI, if they are small enough
+        let (width, height) = (self.images[image].width as f32 * 0.75,
+                               self.images[image].height as f32 * 0.75);
+        let max_width = self.line_width();
+        let max_height = (self.page_height - self.top - self.bottom) * 0.8;
+        let scale = (max_width / width).min(max_height / height).min(1.0);
+        let (width, height) = (width * scale, height * scale);
+        self.skip(0.5);
+        if self.y - height < self.limit() && !self.page_is_empty() {
+            self.new_page();
+        }
+        let x = self.indent + (max_width - width) / 2.0;
+        let ops = format!("q {} 0 0 {} {} {} cm /Im{} Do Q\n",
+                          num(width),
+                          num(height),
+                          num(x),
+                          num(self.y - height),
+                          image);
+        self.push_ops(&ops);
+        if let Some(page) = self.pages.last_mut() {
+            page.images.push(image);
+        }
+        self.y -= height;
+        self.skip(0.5);
+    }
+
+    /// Writes the PDF file. `offset` is the number of pages before the
+    /// content (title page and table of contents)
+    fn write_pdf(&self, pages: &[Page], offset: usize) -> Result<Vec<u8>> {
+        let book = self.book;
+        let mut pdf = PdfWriter::new();
+        let catalog = pdf.reserve();
+        let pages_id = pdf.reserve();
+
+        let mut fonts = String::new();
+        for font in &FONTS {
+            let id = pdf.add(format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} \
+                                      /Encoding /WinAnsiEncoding >>",
+                                     font.base_font()));
+            fonts.push_str(&format!("/{} {} 0 R ", font.resource(), id));
+        }
+        let mut images = vec![];
+        for image in &self.images {
+            let mut dict = format!("/Type /XObject /Subtype /Image /Width {} /Height {} \
+                                    /ColorSpace {} /BitsPerComponent {} /Filter {}",
+                                   image.width,
+                                   image.height,
+                                   image.color_space,
+                                   image.bits,
+                                   image.filter);
+            if let Some(ref parms) = image.parms {
+                dict.push_str(&format!(" /DecodeParms {}", parms));
+            }
+            if image.invert {
+                dict.push_str(" /Decode [1 0 1 0 1 0 1 0]");
+            }
+            images.push(pdf.add_stream(&dict, &image.data));
+        }
+
+        let page_ids: Vec<usize> = pages.iter().map(|_| pdf.reserve()).collect();
+        // Converts a target to a destination in the file
+        let destination = |page: usize, y: f32| -> String {
+            let page = (page + offset).min(pages.len() - 1);
+            format!("[{} 0 R /XYZ null {} null]", page_ids[page], num(y))
+        };
+        for (i, page) in pages.iter().enumerate() {
+            let left = self.left_margin(i);
+            let mut content = format!("q 1 0 0 1 {} 0 cm\n{}Q\n", num(left), page.ops);
+            if page.numbered {
+                let number = format!("{}", i + 1);
+                let width = text_width(Font::Regular, self.size, number.as_bytes());
+                content.push_str(&format!("BT /F1 {} Tf {} {} Td {} Tj ET\n",
+                                          num(self.size),
+                                          num(left + (self.text_width - width) / 2.0),
+                                          num(self.bottom / 2.0),
+                                          hex_string(number.as_bytes())));
+            }
+            let content_id = pdf.add_stream("", content.as_bytes());
+
+            let mut annots = vec![];
+            for &(rect, ref target) in &page.links {
+                let action = match *target {
+                    Target::Uri(ref uri) => format!("/A << /S /URI /URI {} >>", literal_string(uri)),
+                    Target::Chapter(c) => {
+                        let (page, y) = self.chapter_starts[c];
+                        format!("/Dest {}", destination(page, y))
+                    }
+                    Target::Position(page, y) => format!("/Dest {}", destination(page, y)),
+                };
+                let id = pdf.add(format!("<< /Type /Annot /Subtype /Link /Rect [{} {} {} {}] \
+                                          /Border [0 0 0] {} >>",
+                                         num(rect[0] + left),
+                                         num(rect[1]),
+                                         num(rect[2] + left),
+                                         num(rect[3]),
+                                         action));
+                annots.push(format!("{} 0 R", id));
+            }
+
+            let mut xobjects = String::new();
+            for &image in &page.images {
+                let name = format!("/Im{} {} 0 R ", image, images[image]);
+                if !xobjects.contains(&name) {
+                    xobjects.push_str(&name);
+                }
+            }
+            pdf.set(page_ids[i],
+                    format!("<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] \
+                             /Resources << /Font << {}>> /XObject << {}>> >> \
+                             /Contents {} 0 R /Annots [{}] >>",
+                            pages_id,
+                            num(self.page_width),
+                            num(self.page_height),
+                            fonts,
+                            xobjects,
+                            content_id,
+                            annots.join(" ")));
+        }
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+        pdf.set(pages_id,
+                format!("<< /Type /Pages /Kids [{}] /Count {} >>",
+                        kids.join(" "),
+                        page_ids.len()));
+
+        // Bookmarks, for chapters and parts
+        let chapters: Vec<&(i32, String, usize, f32)> = self.headings
+            .iter()
+            .filter(|h| h.0 == 1)
+            .collect();
+        let mut outlines = String::new();
+        if !chapters.is_empty() {
+            let outlines_id = pdf.reserve();
+            let ids: Vec<usize> = chapters.iter().map(|_| pdf.reserve()).collect();
+            for (i, &&(_, ref title, page, y)) in chapters.iter().enumerate() {
+                let mut item = format!("<< /Title {} /Parent {} 0 R /Dest {}",
+                                       text_string(title),
+                                       outlines_id,
+                                       destination(page, y));
+                if i > 0 {
+                    item.push_str(&format!(" /Prev {} 0 R", ids[i - 1]));
+                }
+                if i + 1 < ids.len() {
+                    item.push_str(&format!(" /Next {} 0 R", ids[i + 1]));
+                }
+                item.push_str(" >>");
+                pdf.set(ids[i], item);
+            }
+            pdf.set(outlines_id,
+                    format!("<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count {} >>",
+                            ids[0],
+                            ids[ids.len() - 1],
+                            ids.len()));
+            outlines = format!(" /Outlines {} 0 R /PageMode /UseOutlines", outlines_id);
+        }
+        pdf.set(catalog,
+                format!("<< /Type /Catalog /Pages {} 0 R{} /Lang {} >>",
+                        pages_id,
+                        outlines,
+                        literal_string(book.options.get_str("lang").unwrap())));
+
+        let mut info = format!("<< /Title {} /Producer (Crowbook)",
+                               text_string(&plain_text(book.options.get_str("title").unwrap())?));
+        let authors: Vec<String> = book.authors().into_iter().map(|a| a.name).collect();
+        if !authors.is_empty() {
+            info.push_str(&format!(" /Author {}", text_string(&authors.join(", "))));
+        }
+        info.push_str(" >>");
+        let info = pdf.add(info);
+        Ok(pdf.finish(catalog, info))
+    }
+}
+
+/// Returns the text of an inline Markdown string, without formatting
+fn plain_text(s: &str) -> Result<String> {
+    Ok(view_as_text(&Parser::new().parse_inline(s)?))
+}
+
+/// Returns the first length set among `keys`, in PDF points
+fn length(book: &Book, keys: &[&str], default: &str) -> f32 {
+    let value = keys.iter()
+        .filter_map(|key| book.options.get_str(key).ok())
+        .next()
+        .unwrap_or(default);
+    let pt = match misc::tex_length(value) {
+        Some(pt) => pt,
+        None => {
+            warn!("{}", lformat!("PDF: invalid length '{length}', using {default}",
+                                 length = value,
+                                 default = default));
+            misc::tex_length(default).unwrap()
+        }
+    };
+    pt * 72.0 / 72.27
+}
+
+/// Returns the size of the pages, in PDF points
+fn page_size(book: &Book) -> (f32, f32) {
+    let trim = (book.options.get_str("tex.trim.width").ok().and_then(misc::tex_length),
+                book.options.get_str("tex.trim.height").ok().and_then(misc::tex_length));
+    let (width, height) = match trim {
+        (Some(width), Some(height)) => (width, height),
+        _ => {
+            let paper = book.options.get_str("tex.paper.size").unwrap();
+            match latex::paper_dimensions(paper) {
+                Some(dimensions) => dimensions,
+                None => {
+                    warn!("{}", lformat!("PDF: unknown paper size '{paper}', using a5paper",
+                                         paper = paper));
+                    latex::paper_dimensions("a5paper").unwrap()
+                }
+            }
+        }
+    };
+    (width * 72.0 / 72.27, height * 72.0 / 72.27)
+}
@@ -0,0 +1,312 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Projects, containing several editions of a same book
+
+use book::Book;
+use error::{Error, Result, Source};
+
+use yaml_rust::{Yaml, YamlLoader};
+use rayon::prelude::*;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// An edition of a project
+#[derive(Debug, Default)]
+struct Edition {
+    name: String,
+    inherits: Option<String>,
+    options: Vec<(Yaml, Yaml)>,
+    exclude: Vec<String>,
+    chapters: Vec<String>,
+}
+
+/// A project, declaring several editions (e.g. "paperback", "ebook", "web")
+/// of a same book.
+///
+/// A project file is a YAML file, e.g.:
+///
+/// ```yaml
+/// book: common.book        # base book configuration (optional)
+/// tex.paper.size: a5paper  # options shared by all editions
+/// editions:
+///   ebook:
+///     output: [epub, html]
+///   web:
+///     inherits: ebook      # start from another edition
+///     output: [html.dir]
+///     exclude: [colophon.md]
+///     chapters:
+///       - + web_extras.md
+/// ```
+///
+/// Each edition gets the chapters and options of the base book and of the
+/// shared options, then the ones of the edition it inherits from (if
+/// any), and finally its own.
+#[derive(Debug)]
+pub struct Project {
+    source: Source,
+    root: PathBuf,
+    book: Option<String>,
+    common: Edition,
+    editions: Vec<Edition>,
+}
+
+impl Project {
+    /// Loads a project file
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Project> {
+        let filename = format!("{}", path.as_ref().display());
+        let mut f = File::open(path.as_ref())
+            .map_err(|_| {
+                Error::file_not_found(Source::empty(), lformat!("project"), filename.clone())
+            })?;
+        let mut content = String::new();
+        f.read_to_string(&mut content)
+            .map_err(|err| Error::config_parser(Source::new(filename.as_str()),
+                                                lformat!("could not read source: {error}",
+                                                         error = err)))?;
+        let mut project = Project::from_str(&content, Source::new(filename.as_str()))?;
+        if let Some(parent) = path.as_ref().parent() {
+            project.root = parent.to_owned();
+        }
+        Ok(project)
+    }
+
+    /// Reads a project from a YAML string
+    #[doc(hidden)]
+    pub fn from_str(content: &str, source: Source) -> Result<Project> {
+        let docs = YamlLoader::load_from_str(content)
+            .map_err(|err| Error::config_parser(&source,
+                                                lformat!("project file is not valid YAML: {error}",
+                                                         error = err)))?;
+        let hash = match docs.into_iter().next() {
+            Some(Yaml::Hash(hash)) => hash,
+            _ => {
+                return Err(Error::config_parser(&source,
+                                                lformat!("project file is not a valid hashmap")))
+            }
+        };
+
+        let mut project = Project {
+            source: source,
+            root: PathBuf::new(),
+            book: None,
+            common: Edition::default(),
+            editions: vec![],
+        };
+        for (key, value) in hash {
+            let name = key.as_str().unwrap_or("").to_owned();
+            match name.as_ref() {
+                "book" => {
+                    if let Yaml::String(book) = value {
+                        project.book = Some(book);
+                    } else {
+                        return Err(Error::config_parser(&project.source,
+                                                        lformat!("expected a string as value \
+                                                                  for key 'book'")));
+                    }
+                },
+                "editions" => {
+                    let editions = if let Yaml::Hash(editions) = value {
+                        editions
+                    } else {
+                        return Err(Error::config_parser(&project.source,
+                                                        lformat!("expected a hashmap as value \
+                                                                  for key 'editions'")));
+                    };
+                    for (name, edition) in editions {
+                        let name = if let Yaml::String(name) = name {
+                            name
+                        } else {
+                            return Err(Error::config_parser(&project.source,
+                                                            lformat!("edition names must be \
+                                                                      strings, found {:?}",
+                                                                     name)));
+                        };
+                        let edition = Project::read_edition(&project.source, name, edition)?;
+                        project.editions.push(edition);
+                    }
+                },
+                _ => {
+                    Project::read_edition_key(&project.source, &mut project.common, key, value)?
+                },
+            }
+        }
+        if project.editions.is_empty() {
+            return Err(Error::config_parser(&project.source,
+                                            lformat!("project file does not declare any edition")));
+        }
+        Ok(project)
+    }
+
+    /// Reads the YAML description of an edition
+    fn read_edition(source: &Source, name: String, yaml: Yaml) -> Result<Edition> {
+        let mut edition = Edition::default();
+        edition.name = name;
+        match yaml {
+            Yaml::Hash(hash) => {
+                for (key, value) in hash {
+                    Project::read_edition_key(source, &mut edition, key, value)?;
+                }
+            },
+            // An edition with no specific option
+            Yaml::Null => (),
+            _ => {
+                return Err(Error::config_parser(source,
+                                                lformat!("edition '{name}' is not a valid \
+                                                          hashmap",
+                                                         name = edition.name)))
+            }
+        }
+        Ok(edition)
+    }
+
+    /// Reads a key of an edition, which is either an option or one of the
+    /// special keys `inherits`, `exclude` and `chapters`.
+    fn read_edition_key(source: &Source, edition: &mut Edition, key: Yaml, value: Yaml) -> Result<()> {
+        let name = key.as_str().unwrap_or("").to_owned();
+        match name.as_ref() {
+            "inherits" => {
+                if let Yaml::String(parent) = value {
+                    edition.inherits = Some(parent);
+                } else {
+                    return Err(Error::config_parser(source,
+                                                    lformat!("expected a string as value for \
+                                                              key 'inherits'")));
+                }
+            },
+            "exclude" => {
+                edition.exclude = Project::read_strings(source, "exclude", value)?;
+            },
+            "chapters" => {
+                edition.chapters = Project::read_strings(source, "chapters", value)?;
+            },
+            _ => edition.options.push((key, value)),
+        }
+        Ok(())
+    }
+
+    /// Reads a list of strings
+    fn read_strings(source: &Source, key: &str, value: Yaml) -> Result<Vec<String>> {
+        let error = || Error::config_parser(source,
+                                            lformat!("expected a list of strings as value for \
+                                                      key '{key}'",
+                                                     key = key));
+        if let Yaml::Array(array) = value {
+            let mut strings = vec![];
+            for value in array {
+                if let Yaml::String(s) = value {
+                    strings.push(s);
+                } else {
+                    return Err(error());
+                }
+            }
+            Ok(strings)
+        } else {
+            Err(error())
+        }
+    }
+
+    /// Returns the names of the editions declared in this project
+    pub fn editions(&self) -> Vec<&str> {
+        self.editions
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect()
+    }
+
+    /// Returns the list of editions to apply for a given edition, starting
+    /// with the one it (indirectly) inherits from.
+    fn lineage(&self, name: &str) -> Result<Vec<&Edition>> {
+        let mut lineage: Vec<&Edition> = vec![];
+        let mut current = Some(name);
+        while let Some(name) = current {
+            if lineage.iter().any(|e| e.name == name) {
+                return Err(Error::config_parser(&self.source,
+                                                lformat!("edition '{name}' inherits from itself",
+                                                         name = name)));
+            }
+            let edition = self.editions
+                .iter()
+                .find(|e| e.name == name)
+                .ok_or_else(|| Error::config_parser(&self.source,
+                                                    lformat!("project does not contain an edition \
+                                                              named '{name}'",
+                                                             name = name)))?;
+            lineage.push(edition);
+            current = edition.inherits.as_ref().map(|s| s.as_str());
+        }
+        lineage.push(&self.common);
+        lineage.reverse();
+        Ok(lineage)
+    }
+
+    /// Loads an edition of the project into a book.
+    ///
+    /// As for `Book::load_file`, you should set the book's progress bar and
+    /// "keep going" mode before calling this method if you want them.
+    pub fn load_edition(&self, book: &mut Book, name: &str) -> Result<()> {
+        let lineage = self.lineage(name)?;
+        let options: Vec<_> = lineage.iter()
+            .flat_map(|e| e.options.iter().cloned())
+            .collect();
+
+        book.root = self.root.clone();
+        book.options.root = self.root.clone();
+        book.source = self.source.clone();
+        book.options.source = self.source.clone();
+
+        // Options are set both before and after loading the base book, so
+        // they are taken into account when parsing chapters but still
+        // override the base book's ones.
+        book.set_yaml_options(options.clone());
+        if let Some(ref base) = self.book {
+            book.load_file(self.root.join(base))?;
+        } else {
+            book.read_config(&b""[..])?;
+        }
+        let base_source = book.source.clone();
+        book.source = self.source.clone();
+        book.options.source = self.source.clone();
+        book.set_yaml_options(options);
+
+        for edition in &lineage {
+            if !edition.exclude.is_empty() {
+                book.chapters.retain(|c| !edition.exclude.contains(&c.filename));
+            }
+            for line in &edition.chapters {
+                if let Err(err) = book.read_chapter_line(line) {
+                    book.keep_going_or(err)?;
+                }
+            }
+        }
+
+        book.source = base_source.clone();
+        book.options.source = base_source;
+        book.set_edition(name);
+        Ok(())
+    }
+
+    /// Renders all the formats of several books (typically, editions of a
+    /// project) in parallel.
+    pub fn render_all(books: &[Book]) {
+        books.par_iter()
+            .for_each(|book| book.render_all());
+    }
+}
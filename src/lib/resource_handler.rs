@@ -144,6 +144,9 @@ impl ResourceHandler {
     pub fn get_link<'a>(&'a self, from: &'a str) -> &'a str {
         if let Some(link) = self.links.get(from) {
             link
+        } else if from.starts_with('#') {
+            // Link to an anchor of the current chapter
+            from
         } else {
             // Try to get a link by changing the extension
             let new_from = format!("{}", Path::new(from)
@@ -53,9 +53,14 @@ pub mod latex {
     pub static TEMPLATE: &str = include_str!("../../templates/latex/template.tex");
 }
 
+pub mod typst {
+    pub static TEMPLATE: &str = include_str!("../../templates/typst/template.typ");
+}
+
 pub mod epub {
     pub static TEMPLATE: &str = include_str!("../../templates/epub/template.xhtml");
     pub static CSS: &str = include_str!("../../templates/epub/stylesheet.css");
+    pub static KINDLE_CSS: &str = include_str!("../../templates/epub/kindle.css");
     pub static COVER: &str = include_str!("../../templates/epub/cover.xhtml");
     pub static TITLE: &str = include_str!("../../templates/epub/titlepage.xhtml");
 }
@@ -68,5 +73,13 @@ pub mod epub3 {
 
 pub mod odt {
     pub static CONTENT: &str = include_str!("../../templates/odt/content.xml");
-    pub static ODT: &[u8] = include_bytes!("../../templates/odt/template.odt");
+    pub static STYLES: &str = include_str!("../../templates/odt/styles.xml");
+    pub static META: &str = include_str!("../../templates/odt/meta.xml");
+}
+
+pub mod docx {
+    pub static DOCUMENT: &str = include_str!("../../templates/docx/document.xml");
+    pub static STYLES: &str = include_str!("../../templates/docx/styles.xml");
+    pub static SETTINGS: &str = include_str!("../../templates/docx/settings.xml");
+    pub static CORE: &str = include_str!("../../templates/docx/core.xml");
 }
@@ -1,4 +1,5 @@
 use book::Book;
+use number::Number;
 use super::test_eq;
 
 #[test]
@@ -19,3 +20,106 @@ epub.version: 3";
             "A long description");
     assert_eq!(book.options.get_i32("epub.version").unwrap(), 3);
 }
+
+#[test]
+fn keep_going() {
+    let config = "
+author: Author
++ missing_1.md
++ missing_2.md";
+    let mut book = Book::new();
+    assert!(book.read_config(config.as_bytes()).is_err());
+
+    let mut book = Book::new();
+    book.set_keep_going(true);
+    book.read_config(config.as_bytes()).unwrap();
+    assert!(book.has_errors());
+    assert_eq!(book.take_errors().len(), 2);
+    assert!(!book.has_errors());
+}
+
+#[test]
+fn keep_going_render() {
+    let mut book = Book::new();
+    book.set_keep_going(true);
+    book.set_options(&[("output.tex", "/nonexistent/dir/book.tex")]);
+    book.render_format("tex");
+    let errors = book.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(format!("{}", errors[0]).starts_with("Error rendering tex: "));
+}
+
+#[test]
+fn check_option_values() {
+    let mut book = Book::new();
+    assert!(book.options.set("epub.version", "3").is_ok());
+    assert!(book.options.set("epub.version", "4").is_err());
+    assert!(book.options.set("rendering.highlight", "highlight.js").is_ok());
+    assert!(book.options.set("rendering.highlight", "pygments").is_err());
+    assert!(book.options.set("epub.profile", "kindle").is_ok());
+    assert!(book.options.set("epub.profile", "kobo").is_err());
+    assert!(book.options.set("epub.layout", "pre-paginated").is_ok());
+    assert!(book.options.set("epub.layout", "fixed").is_err());
+    assert!(book.options.set("epub.layout.spread", "both").is_ok());
+    assert!(book.options.set("epub.layout.spread", "always").is_err());
+}
+
+#[test]
+fn suggest_key() {
+    let mut book = Book::new();
+    let err = book.options.set("autor", "John Smith").unwrap_err();
+    assert!(format!("{}", err).contains("'author'"));
+    let err = book.options.set("tex.papersize", "a4paper").unwrap_err();
+    assert!(format!("{}", err).contains("'tex.paper.size'"));
+}
+
+#[test]
+fn check_links_and_images() {
+    let mut book = Book::new();
+    book.set_keep_going(true);
+    book.add_chapter_from_source(Number::Default,
+                                 "# Title\n\n[missing](missing.md) and ![img](missing.png)\n\n\
+                                  [anchor](#link-1) [web](http://example.com)".as_bytes())
+        .unwrap();
+    book.check().unwrap();
+    assert_eq!(book.take_errors().len(), 2);
+}
+
+#[test]
+fn check_anchors() {
+    let mut book = Book::new();
+    book.set_keep_going(true);
+    book.add_chapter_from_named_source(Number::Default,
+                                       "one.md",
+                                       "# Chapter one\n\n## Some section!\n\n\
+                                        [ok](#link-2) [ko](#some-section) [ko](#link-3)"
+                                       .as_bytes())
+        .unwrap();
+    book.add_chapter_from_named_source(Number::Default,
+                                       "two.md",
+                                       "# Two\n\n[ok](one.md#link-1) [ok](one.md) \
+                                        [ok](#link-3) [ko](one.md#link-3)".as_bytes())
+        .unwrap();
+    book.check_links().unwrap();
+    assert_eq!(book.take_errors().len(), 3);
+
+    // When rendering, broken links are only displayed as warnings
+    book.set_keep_going(false);
+    book.warn_links();
+    assert!(book.take_errors().is_empty());
+}
+
+#[test]
+fn text_direction() {
+    let mut book = Book::new();
+    assert!(!book.is_rtl());
+    book.set_options(&[("lang", "ar")]);
+    assert!(book.is_rtl());
+    book.set_options(&[("rendering.direction", "ltr")]);
+    assert!(!book.is_rtl());
+
+    let mut book = Book::new();
+    book.set_options(&[("lang", "sr-Latn"), ("rendering.direction", "rtl")]);
+    assert!(book.is_rtl());
+    assert!(book.options.set("rendering.direction", "up").is_err());
+}
@@ -0,0 +1,28 @@
+use contributor::{self, Contributor};
+
+#[test]
+fn contributors() {
+    let c = Contributor::parse("Jane Doe (translator)");
+    assert_eq!(c.name, "Jane Doe");
+    assert_eq!(c.role, "trl");
+    assert_eq!(Contributor::parse("John Smith (ill)").role, "ill");
+    assert_eq!(Contributor::parse("Someone").role, "ctb");
+    assert_eq!(c.file_as, None);
+
+    let a = Contributor::parse_author("Jane Doe [Doe, Jane]");
+    assert_eq!(a.name, "Jane Doe");
+    assert_eq!(a.role, "aut");
+    assert_eq!(a.file_as(), "Doe, Jane");
+    let a = Contributor::parse_author("John Smith [Smith, John] (editor)");
+    assert_eq!(a.file_as, Some(String::from("Smith, John")));
+    assert_eq!(a.role, "edt");
+    assert_eq!(Contributor::parse_author("Anne Roe").file_as(), "Anne Roe");
+}
+
+#[test]
+fn join_names() {
+    let names = vec![String::from("A"), String::from("B"), String::from("C")];
+    assert_eq!(contributor::join_names(&names[..1], "and"), "A");
+    assert_eq!(contributor::join_names(&names[..2], "and"), "A and B");
+    assert_eq!(contributor::join_names(&names, "et"), "A, B et C");
+}
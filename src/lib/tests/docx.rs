@@ -0,0 +1,37 @@
+use docx::DocxRenderer;
+use book::Book;
+use parser::Parser;
+
+fn md_to_docx(md: &str) -> String {
+    let mut book = Book::new();
+    book.set_options(&[("rendering.num_depth", "0")]);
+    let tokens = Parser::new().parse(md).unwrap();
+    let mut docx = DocxRenderer::new(&book);
+    docx.render_vec(&tokens).unwrap()
+}
+
+#[test]
+fn docx_runs() {
+    let s = md_to_docx("Some *emphasis with **strong** text*");
+    assert!(s.contains("<w:pStyle w:val=\"BodyText\"/>"));
+    assert!(s.contains("<w:r><w:rPr><w:rStyle w:val=\"Emphasis\"/></w:rPr>\
+                        <w:t xml:space=\"preserve\">emphasis with </w:t></w:r>"));
+    assert!(s.contains("<w:r><w:rPr><w:rStyle w:val=\"Strong\"/><w:i/></w:rPr>\
+                        <w:t xml:space=\"preserve\">strong</w:t></w:r>"));
+}
+
+#[test]
+fn docx_lists() {
+    let s = md_to_docx("* foo\n    1. bar");
+    assert!(s.contains("<w:numPr><w:ilvl w:val=\"0\"/><w:numId w:val=\"1\"/></w:numPr>"));
+    assert!(s.contains("<w:numPr><w:ilvl w:val=\"1\"/><w:numId w:val=\"2\"/></w:numPr>"));
+}
+
+#[test]
+fn docx_footnotes_and_tables() {
+    let s = md_to_docx("Text[^1].\n\n[^1]: A note\n\n| a | b |\n|---|---|\n| c | d |");
+    assert!(s.contains("<w:footnoteReference w:id=\"1\"/>"));
+    assert!(s.contains("<w:tr><w:trPr><w:tblHeader/></w:trPr>"));
+    assert!(s.contains("<w:pStyle w:val=\"TableHeading\"/>"));
+    assert_eq!(s.matches("<w:gridCol ").count(), 2);
+}
@@ -0,0 +1,70 @@
+use epub::EpubRenderer;
+use book::{Book, compile_str};
+
+use mustache::Template;
+
+fn book(options: &[(&str, &str)]) -> Book {
+    let mut book = Book::new();
+    book.set_options(&[("rendering.highlight", "none")]);
+    book.set_options(options);
+    book.add_chapter_from_source(::number::Number::Default,
+                                 "# Chapter\n\nFoo[^1].\n\n[^1]: Bar".as_bytes())
+        .unwrap();
+    book
+}
+
+fn template(book: &Book) -> Template {
+    compile_str(book.get_template("epub.chapter.xhtml").unwrap().as_ref(),
+                &book.source,
+                "epub.chapter.xhtml")
+        .unwrap()
+}
+
+#[test]
+fn epub3_notes() {
+    let book = book(&[("epub.version", "3")]);
+    let mut epub = EpubRenderer::new(&book).unwrap();
+    let (pages, _) = epub.render_chapter(&book.chapters[0].content, &template(&book)).unwrap();
+    let content = &pages[0];
+    assert!(content.contains("<a epub:type = \"noteref\" role = \"doc-noteref\" \
+                              href = \"#note-dest-1\"><sup id = \"note-source-1\">[1]</sup></a>"));
+    assert!(content.contains("<section class = \"notes\" epub:type = \"endnotes\" \
+                              role = \"doc-endnotes\">"));
+    assert!(content.contains("<aside class = \"note\" epub:type = \"footnote\" \
+                              role = \"doc-footnote\" id = \"note-dest-1\">"));
+    assert!(content.contains("<a href = \"#note-source-1\" role = \"doc-backlink\">[1]</a>"));
+}
+
+#[test]
+fn epub2_notes() {
+    let book = book(&[("epub.version", "2")]);
+    let epub = EpubRenderer::new(&book).unwrap();
+    let notes = vec![(1, String::from("chapter_000.xhtml"), String::from("<p>Bar</p>"))];
+    let content = epub.render_notes(&notes, false);
+    assert!(!content.contains("aside"));
+    assert!(content.starts_with("<div class = \"note\" id = \"note-dest-1\">"));
+    // Notes are in their own document, so back links include the chapter file
+    assert!(content.contains("<a href = \"chapter_000.xhtml#note-source-1\">[1]</a>"));
+    let content = epub.render_notes(&notes, true);
+    assert!(content.contains("<a href = \"#note-source-1\">[1]</a>"));
+}
+
+#[test]
+fn notes_single_file() {
+    let book = book(&[("epub.version", "3"), ("epub.notes.single_file", "true")]);
+    let template = template(&book);
+    let mut epub = EpubRenderer::new(&book).unwrap();
+    let (pages, _) = epub.render_chapter(&book.chapters[0].content, &template).unwrap();
+    assert!(pages[0].contains("href = \"notes.xhtml#note-dest-1\""));
+    assert!(!pages[0].contains("<aside"));
+
+    let (content, title) = epub.render_notes_file(&template).unwrap();
+    assert_eq!(title, "Notes");
+    assert!(content.contains("<h1>Notes</h1>"));
+    assert!(content.contains("epub:type = \"footnote\""));
+    assert!(content.contains("id = \"note-dest-1\""));
+    assert!(content.contains("#note-source-1\" role = \"doc-backlink\""));
+    // Notes are only rendered once
+    let (content, _) = epub.render_notes_file(&template).unwrap();
+    assert!(!content.contains("note-dest-1"));
+}
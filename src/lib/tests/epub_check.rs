@@ -0,0 +1,90 @@
+use epub_check::{parse_xml, validate_epub};
+use error::Source;
+
+use zip::ZipWriter;
+use zip::write::FileOptions;
+
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+
+/// Builds a minimal EPUB 2 archive containing a single chapter
+fn archive(chapter: &str) -> Vec<u8> {
+    let files = [("mimetype", "application/epub+zip"),
+                 ("META-INF/container.xml",
+                  "<?xml version=\"1.0\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles><rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/></rootfiles>
+</container>"),
+                 ("OEBPS/content.opf",
+                  "<?xml version=\"1.0\"?>
+<package version=\"2.0\" xmlns=\"http://www.idpf.org/2007/opf\">
+<manifest>
+<item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>
+<item id=\"chapter\" href=\"chapter_001.xhtml\" media-type=\"application/xhtml+xml\"/>
+</manifest>
+<spine toc=\"ncx\"><itemref idref=\"chapter\"/></spine>
+</package>"),
+                 ("OEBPS/toc.ncx", "<ncx><navMap/></ncx>"),
+                 ("OEBPS/chapter_001.xhtml", chapter)];
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for &(name, content) in &files {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn xml_well_formed() {
+    let doc = parse_xml("<?xml version=\"1.0\"?>
+<!DOCTYPE html>
+<html><body id=\"a\"><p>Foo &amp; bar&#160;<br/></p><!-- <oops> --></body></html>")
+        .unwrap();
+    assert_eq!(doc.name, "html");
+    assert_eq!(doc.find_all("body")[0].attr("id"), Some("a"));
+    assert_eq!(doc.find_all("br").len(), 1);
+}
+
+#[test]
+fn xml_not_well_formed() {
+    assert!(parse_xml("<p>foo<br></p>").is_err());
+    assert!(parse_xml("<p>foo</p><p>bar</p>").is_err());
+    assert!(parse_xml("<p class=foo>bar</p>").is_err());
+    assert!(parse_xml("<p>foo & bar</p>").is_err());
+    assert!(parse_xml("<p>&nbsp;</p>").is_err());
+    assert!(parse_xml("<p a=\"1\" a=\"2\"/>").is_err());
+    let err = parse_xml("<html>\n<body>\n<p>foo</div>\n</body></html>").unwrap_err();
+    assert!(err.contains("3"));
+}
+
+#[test]
+fn xml_entities() {
+    assert!(parse_xml("<!DOCTYPE html [<!ENTITY nbsp \"&#160;\">]>\n<p>&nbsp;</p>").is_ok());
+    assert!(parse_xml("<!DOCTYPE html [<!ENTITY % foo \"bar\">]>\n<p>&foo;</p>").is_err());
+    assert!(parse_xml("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.1//EN\" \
+                       \"http://www.w3.org/TR/xhtml11/DTD/xhtml11.dtd\">\n<p>&eacute;</p>")
+                .is_err());
+}
+
+#[test]
+fn validate_archive() {
+    let sources = HashMap::new();
+    let source = Source::empty();
+    let errors = validate_epub(&archive("<html><body><p id=\"a\">Foo</p></body></html>"),
+                               &sources,
+                               &[],
+                               &source);
+    assert!(errors.is_empty());
+    let errors = validate_epub(&archive("<html><body><p id=\"a\">&nbsp;<a href=\"#b\">b</a>\
+                                         </p></body></html>"),
+                               &sources,
+                               &[],
+                               &source);
+    assert_eq!(errors.len(), 1);
+    let errors = validate_epub(&archive("<html><body><p id=\"a\"><a href=\"#b\">b</a>\
+                                         </p></body></html>"),
+                               &sources,
+                               &[],
+                               &source);
+    assert_eq!(errors.len(), 1);
+}
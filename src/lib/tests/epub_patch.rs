@@ -0,0 +1,193 @@
+use epub_check::{parse_xml, decode_entities};
+use epub_patch::{EpubPatch, meta, normalize_identifier, obfuscate_font};
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+/// Returns the content of a (fake) font file
+fn font() -> String {
+    (0..2000).map(|i| (b'a' + (i % 26) as u8) as char).collect()
+}
+
+/// Writes a minimal unzipped EPUB 3 archive, with a navigation document
+/// similar to the one generated by epub-builder, in a temporary directory
+fn unzipped_epub(name: &str, identifier: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("crowbook-test-{}-{}", name, ::std::process::id()));
+    let opf = format!("<?xml version=\"1.0\"?>
+<package version=\"3.0\" unique-identifier=\"epub-id-1\" xmlns=\"http://www.idpf.org/2007/opf\">
+<metadata><dc:identifier id=\"epub-id-1\">{0}</dc:identifier>
+<dc:source>{0}</dc:source>
+<dc:date>2017-01-01T00:00:00Z</dc:date></metadata>
+<manifest>
+<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>
+<item id=\"chapter\" href=\"chapter_000.xhtml\" media-type=\"application/xhtml+xml\"/>
+</manifest>
+<spine><itemref idref=\"chapter\"/></spine>
+</package>",
+                      identifier);
+    let font = font();
+    let files = [("META-INF/container.xml",
+                  "<?xml version=\"1.0\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles><rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/></rootfiles>
+</container>"),
+                 ("OEBPS/content.opf", opf.as_str()),
+                 ("OEBPS/nav.xhtml",
+                  "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">
+<head><title>Toc</title></head>
+<body>
+  <nav epub:type = \"toc\" id=\"toc\">
+    <ol><li><a href=\"chapter_000.xhtml\">Chapter</a></li></ol>
+  </nav>
+  <nav epub:type = \"landmarks\">
+    <ol>
+<li><a epub:type=\"bodymatter\" href = \"chapter_000.xhtml\">Chapter</a></li>
+</ol>
+  </nav>
+</body>
+</html>"),
+                 ("OEBPS/chapter_000.xhtml", "<html><body><h1>Chapter</h1></body></html>"),
+                 ("OEBPS/fonts/font.ttf", font.as_str())];
+    for &(file, content) in &files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn landmarks_replaced() {
+    let dir = unzipped_epub("landmarks", "urn:uuid:1234");
+    let mut patch = EpubPatch::default();
+    patch.landmarks.push((String::from("titlepage"),
+                          String::from("title_page.xhtml"),
+                          String::from("Title")));
+    patch.landmarks.push((String::from("bodymatter"),
+                          String::from("chapter_000.xhtml"),
+                          String::from("Start")));
+    patch.apply(&dir).unwrap();
+
+    let mut nav = String::new();
+    File::open(dir.join("OEBPS/nav.xhtml")).unwrap().read_to_string(&mut nav).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let doc = parse_xml(&nav).unwrap();
+    let landmarks: Vec<_> = doc.find_all("nav")
+        .into_iter()
+        .filter(|n| n.attr("epub:type") == Some("landmarks"))
+        .collect();
+    assert_eq!(landmarks.len(), 1);
+    let links: Vec<_> = landmarks[0]
+        .find_all("a")
+        .into_iter()
+        .map(|a| (a.attr("epub:type").unwrap(), a.attr("href").unwrap(), a.text.as_str()))
+        .collect();
+    assert_eq!(links,
+               vec![("titlepage", "title_page.xhtml", "Title"),
+                    ("bodymatter", "chapter_000.xhtml", "Start")]);
+    // The table of contents is left untouched
+    assert_eq!(doc.find_all("nav")[0].attr("id"), Some("toc"));
+}
+
+#[test]
+fn opf_meta() {
+    assert_eq!(meta(true, "schema:accessMode", "textual"),
+               "<meta property=\"schema:accessMode\">textual</meta>");
+    assert_eq!(meta(false, "schema:accessibilitySummary", "Foo & bar"),
+               "<meta name=\"schema:accessibilitySummary\" content=\"Foo &amp; bar\" />");
+}
+
+#[test]
+fn identifiers() {
+    assert_eq!(normalize_identifier("978-2-1234-5680-3"), "urn:isbn:9782123456803");
+    assert_eq!(normalize_identifier("2-1234-5680-X"), "urn:isbn:212345680X");
+    assert_eq!(normalize_identifier("6BA7B810-9DAD-11D1-80B4-00C04FD430C8"),
+               "urn:uuid:6ba7b810-9dad-11d1-80b4-00c04fd430c8");
+    assert_eq!(normalize_identifier("https://example.org/my-book"),
+               "https://example.org/my-book");
+}
+
+#[test]
+fn identifier_replaced() {
+    let dir = unzipped_epub("identifier", "urn:uuid:1234");
+    let mut patch = EpubPatch::default();
+    patch.identifier = Some(String::from("urn:isbn:9782123456803"));
+    patch.apply(&dir).unwrap();
+
+    let mut opf = String::new();
+    File::open(dir.join("OEBPS/content.opf")).unwrap().read_to_string(&mut opf).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let package = parse_xml(&opf).unwrap();
+    assert_eq!(package.find_all("dc:identifier")[0].text, "urn:isbn:9782123456803");
+    // Other elements are left untouched, even if they contain the same text
+    assert_eq!(package.find_all("dc:source")[0].text, "urn:uuid:1234");
+}
+
+#[test]
+fn entities() {
+    assert_eq!(decode_entities("a&amp;b &lt;&#233;&#xE9;&gt; &nbsp; & c"),
+               "a&b <éé> &nbsp; & c");
+}
+
+#[test]
+fn font_obfuscation() {
+    let font: Vec<u8> = (0..2000).map(|i| (i % 256) as u8).collect();
+    let mut data = font.clone();
+    obfuscate_font(&mut data, "urn:uuid:1234");
+    assert!(data[..1040] != font[..1040]);
+    assert_eq!(data[1040..], font[1040..]);
+    // Whitespace in the identifier is ignored, and obfuscating twice is a no-op
+    obfuscate_font(&mut data, " urn:uuid:1234\n");
+    assert_eq!(data, font);
+
+    // The key is the SHA-1 digest of the identifier
+    let mut data = vec![0; 20];
+    obfuscate_font(&mut data, "abc");
+    let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+    assert_eq!(hex, "a9993e364706816aba3e25717850c26c9cd0d89d");
+}
+
+#[test]
+fn obfuscation_identifier() {
+    // The identifier is escaped in the OPF file, but the key must be
+    // computed from its actual value
+    let dir = unzipped_epub("obfuscation", "urn:x:a&amp;b");
+    let mut patch = EpubPatch::default();
+    patch.obfuscated.push(String::from("fonts/font.ttf"));
+    patch.apply(&dir).unwrap();
+
+    let mut data = vec![];
+    File::open(dir.join("OEBPS/fonts/font.ttf")).unwrap().read_to_end(&mut data).unwrap();
+    let mut encryption = String::new();
+    File::open(dir.join("META-INF/encryption.xml"))
+        .unwrap()
+        .read_to_string(&mut encryption)
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    obfuscate_font(&mut data, "urn:x:a&b");
+    assert_eq!(data, font().into_bytes());
+    assert!(encryption.contains("URI=\"OEBPS/fonts/font.ttf\""));
+}
+
+#[test]
+fn date_replaced() {
+    let dir = unzipped_epub("date", "urn:uuid:1234");
+    let mut patch = EpubPatch::default();
+    patch.date = Some(String::from("<dc:date>2016-05-04</dc:date>"));
+    patch.apply(&dir).unwrap();
+
+    let mut opf = String::new();
+    File::open(dir.join("OEBPS/content.opf")).unwrap().read_to_string(&mut opf).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let dates: Vec<_> = parse_xml(&opf)
+        .unwrap()
+        .find_all("dc:date")
+        .iter()
+        .map(|e| e.text.clone())
+        .collect();
+    assert_eq!(dates, vec![String::from("2016-05-04")]);
+}
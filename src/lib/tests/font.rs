@@ -0,0 +1,16 @@
+use font::FontFile;
+
+#[test]
+fn font_files() {
+    let font = FontFile::parse("fonts/Lora-BoldItalic.ttf");
+    assert_eq!(font.family, "Lora");
+    assert_eq!(font.weight, "bold");
+    assert_eq!(font.style, "italic");
+    assert_eq!(font.media_type(true), Some("font/ttf"));
+    let font = FontFile::parse("fonts/lora_sb.otf; Lora Serif; 600");
+    assert_eq!(font.file, "fonts/lora_sb.otf");
+    assert_eq!(font.family, "Lora Serif");
+    assert_eq!(font.weight, "600");
+    assert_eq!(font.style, "normal");
+    assert_eq!(FontFile::parse("foo.pdf").media_type(true), None);
+}
@@ -0,0 +1,51 @@
+use lang_tag::LangTag;
+
+#[test]
+fn lang_tag_parse() {
+    let tag = LangTag::parse("pt-BR").unwrap();
+    assert_eq!(tag.language, "pt");
+    assert_eq!(tag.region, Some(String::from("BR")));
+    assert_eq!(tag.script, None);
+
+    let tag = LangTag::parse("sr_latn_rs").unwrap();
+    assert_eq!(tag.language, "sr");
+    assert_eq!(tag.script, Some(String::from("Latn")));
+    assert_eq!(tag.region, Some(String::from("RS")));
+
+    let tag = LangTag::parse("es-419").unwrap();
+    assert_eq!(tag.region, Some(String::from("419")));
+
+    let tag = LangTag::parse("de-CH-1996-x-foo").unwrap();
+    assert_eq!(tag.region, Some(String::from("CH")));
+
+    assert!(LangTag::parse("").is_none());
+    assert!(LangTag::parse("e").is_none());
+    assert!(LangTag::parse("en--US").is_none());
+    assert!(LangTag::parse("français").is_none());
+}
+
+#[test]
+fn lang_tag_rtl() {
+    assert!(LangTag::parse("ar").unwrap().is_rtl());
+    assert!(LangTag::parse("he-IL").unwrap().is_rtl());
+    assert!(!LangTag::parse("fr").unwrap().is_rtl());
+    assert!(!LangTag::parse("az-Latn").unwrap().is_rtl());
+    assert!(LangTag::parse("az-Arab").unwrap().is_rtl());
+}
+
+#[test]
+fn lang_tag_latex_names() {
+    let tag = LangTag::parse("pt-BR").unwrap();
+    assert_eq!(tag.babel(), Some("brazilian"));
+    assert_eq!(tag.polyglossia(), Some(("portuguese", "variant=brazilian")));
+
+    let tag = LangTag::parse("de").unwrap();
+    assert_eq!(tag.babel(), Some("ngerman"));
+    assert_eq!(tag.polyglossia(), Some(("german", "")));
+
+    let tag = LangTag::parse("ja").unwrap();
+    assert_eq!(tag.babel(), None);
+    assert_eq!(tag.polyglossia(), Some(("japanese", "")));
+
+    assert_eq!(LangTag::parse("tlh").unwrap().polyglossia(), None);
+}
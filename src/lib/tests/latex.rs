@@ -0,0 +1,150 @@
+use latex::LatexRenderer;
+use book::Book;
+use misc;
+use parser::Parser;
+use renderer::Renderer;
+
+fn render_with(options: &[(&str, &str)]) -> String {
+    let mut book = Book::new();
+    book.set_options(options);
+    LatexRenderer::new(&book).render_book().unwrap()
+}
+
+#[test]
+fn latex_standard_class() {
+    let tex = render_with(&[("tex.font.size", "11"), ("tex.footer.right", "\\thepage")]);
+    assert!(tex.contains("\\documentclass[11pt]{book}"));
+    assert!(tex.contains("{geometry}"));
+    assert!(tex.contains("\\fancyhead[C]{}"));
+    assert!(tex.contains("\\fancyfoot[R]{\\thepage}"));
+}
+
+#[test]
+fn latex_koma_class() {
+    let tex = render_with(&[("tex.class", "scrbook")]);
+    assert!(tex.contains("\\documentclass[paper=a5,DIV=calc]{scrbook}"));
+    assert!(!tex.contains("{geometry}"));
+    assert!(tex.contains("\\cohead{\\thepage}"));
+}
+
+#[test]
+fn latex_memoir_class() {
+    let tex = render_with(&[("tex.class", "memoir"), ("tex.chapter.style", "bringhurst")]);
+    assert!(tex.contains("\\documentclass[a5paper]{memoir}"));
+    assert!(tex.contains("\\chapterstyle{bringhurst}"));
+    assert!(tex.contains("\\makeoddhead{crowbook}{}{\\thepage}{}"));
+}
+
+#[test]
+fn latex_print_layout() {
+    let tex = render_with(&[("tex.trim.width", "5.5in"),
+                            ("tex.trim.height", "8.5in"),
+                            ("tex.bleed", "0.125in"),
+                            ("tex.crop_marks", "true"),
+                            ("tex.margin.inside", "2.5cm"),
+                            ("tex.margin.gutter", "5mm")]);
+    assert!(tex.contains("\\usepackage[paperwidth=5.5in,paperheight=8.5in, top=2cm"));
+    assert!(tex.contains("left=2.5cm,right=2cm,bindingoffset=5mm]{geometry}"));
+    assert!(tex.contains("\\usepackage[cam,center,width=472.45pt,height=689.26pt]{crop}"));
+    assert!(tex.contains("/TrimBox [37.34 37.34 433.34 649.34]"));
+}
+
+#[test]
+fn latex_print_layout_errors() {
+    let mut book = Book::new();
+    book.set_options(&[("tex.trim.width", "5.5in")]);
+    assert!(LatexRenderer::new(&book).render_book().is_err());
+
+    let mut book = Book::new();
+    book.set_options(&[("tex.paper.size", "a3paper"), ("tex.bleed", "3mm")]);
+    assert!(LatexRenderer::new(&book).render_book().is_err());
+
+    let mut book = Book::new();
+    assert!(book.options.set("tex.bleed", "3 miles").is_err());
+    assert!(book.options.set("tex.pdfx", "x-2").is_err());
+}
+
+#[test]
+fn latex_pdfx() {
+    let tex = render_with(&[("tex.pdfx", "x-1a"), ("title", "Foo")]);
+    assert!(tex.contains("\\usepackage[x-1a]{pdfx}"));
+    assert!(tex.contains("\\Title{Foo}"));
+    assert!(!tex.contains("{crop}"));
+}
+
+#[test]
+fn tex_lengths() {
+    assert_eq!(misc::tex_length("10pt"), Some(10.0));
+    assert_eq!(misc::tex_length("1in"), Some(72.27));
+    assert_eq!(misc::tex_length(" 2 pc"), Some(24.0));
+    assert_eq!(misc::tex_length("3 miles"), None);
+    assert_eq!(misc::tex_length("mm"), None);
+}
+
+#[test]
+fn latex_fonts() {
+    let tex = render_with(&[("lang", "fr"),
+                            ("tex.font.main", "Linux Libertine O"),
+                            ("tex.font.mono", "DejaVu Sans Mono"),
+                            ("tex.font.cjk", "Noto Serif CJK JP")]);
+    assert!(tex.contains("\\setmainfont{Linux Libertine O}"));
+    assert!(tex.contains("\\setmonofont{DejaVu Sans Mono}"));
+    assert!(!tex.contains("\\setsansfont"));
+    assert!(tex.contains("\\setdefaultlanguage{french}"));
+    assert!(tex.contains("\\setCJKmainfont{Noto Serif CJK JP}"));
+    assert!(!tex.contains("{babel}"));
+
+    let tex = render_with(&[("tex.command", "pdflatex"), ("tex.font.main", "Linux Libertine O")]);
+    assert!(!tex.contains("fontspec"));
+    assert!(tex.contains("\\usepackage[english]{babel}"));
+}
+
+#[test]
+fn latex_script_fallbacks() {
+    let mut book = Book::new();
+    book.set_options(&[("tex.font.arabic", "Amiri"), ("tex.font.cyrillic", "PT Serif")]);
+    let tokens = Parser::new().parse("Salam: سلام عليكم, and привет!").unwrap();
+    let tex = LatexRenderer::new(&book).render_vec(&tokens).unwrap();
+    assert!(tex.contains("Salam: \\mdarabic{سلام عليكم}, and \\mdcyrillic{привет}!"));
+
+    // No need for a fallback for the script of the main language
+    book.set_options(&[("lang", "ru")]);
+    let tex = LatexRenderer::new(&book).render_vec(&tokens).unwrap();
+    assert!(!tex.contains("\\mdcyrillic"));
+}
+
+#[test]
+fn latex_languages() {
+    let tex = render_with(&[("lang", "pt-BR")]);
+    assert!(tex.contains("\\setdefaultlanguage[variant=brazilian]{portuguese}"));
+
+    let tex = render_with(&[("lang", "pt-BR"), ("tex.command", "pdflatex")]);
+    assert!(tex.contains("\\usepackage[brazilian]{babel}"));
+
+    let mut book = Book::new();
+    assert!(book.options.set("lang", "not a language").is_err());
+}
+
+#[test]
+fn latex_lang_spans() {
+    let tokens = Parser::new().parse("A <span lang=\"de-CH\">Kindergarten</span>.").unwrap();
+    let book = Book::new();
+    let tex = LatexRenderer::new(&book).render_vec(&tokens).unwrap();
+    assert!(tex.contains("A \\textgerman[variant=swiss]{Kindergarten}."));
+
+    let mut book = Book::new();
+    book.set_options(&[("tex.command", "pdflatex")]);
+    let tex = LatexRenderer::new(&book).render_vec(&tokens).unwrap();
+    assert!(tex.contains("A \\foreignlanguage{nswissgerman}{Kindergarten}."));
+}
+
+#[test]
+fn latex_direction() {
+    let tex = render_with(&[("lang", "he")]);
+    assert!(tex.contains("\\setdefaultlanguage{hebrew}"));
+    assert!(!tex.contains("\\setRTL"));
+
+    let tex = render_with(&[("rendering.direction", "rtl")]);
+    assert!(tex.contains("\\usepackage{bidi}"));
+    assert!(tex.find("\\usepackage{bidi}") > tex.find("\\usepackage{hyperref}"));
+}
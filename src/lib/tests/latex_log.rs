@@ -0,0 +1,63 @@
+use latex_log::{self, LatexError};
+use latex::LatexRenderer;
+use book::Book;
+use number::Number;
+
+#[test]
+fn latex_log_error() {
+    let log = "This is XeTeX, Version 3.14159265\n\
+               (./result.tex\n\
+               ! Undefined control sequence.\n\
+               l.42 \\foo\n\
+               \n\
+               No pages of output.\n";
+    let log = latex_log::parse(log);
+    assert_eq!(log.errors,
+               vec![LatexError {
+                        line: Some(42),
+                        message: String::from("Undefined control sequence."),
+                        context: Some(String::from("\\foo")),
+                    }]);
+    assert!(!log.rerun);
+}
+
+#[test]
+fn latex_log_file_line_error() {
+    let log = "./result.tex:12: LaTeX Error: Environment foo undefined.\n";
+    let log = latex_log::parse(log);
+    assert_eq!(log.errors[0].line, Some(12));
+    assert_eq!(log.errors[0].message, "LaTeX Error: Environment foo undefined.");
+}
+
+#[test]
+fn latex_log_rerun() {
+    let log = "LaTeX Warning: Label(s) may have changed. Rerun to get cross-references ri\n\
+               ght.\n\
+               Package biblatex Warning: Please (re)run Biber on the file:\n";
+    let log = latex_log::parse(log);
+    assert!(log.rerun);
+    assert!(log.biber);
+    assert!(log.errors.is_empty());
+}
+
+#[test]
+fn latex_source_map() {
+    let mut book = Book::new();
+    book.add_chapter_from_named_source(Number::Default,
+                                       "chapter_03.md",
+                                       "---\nauthor: Someone\n---\n\n# Title\n\nSome \
+                                        text\n\nMore text\n"
+                                           .as_bytes())
+        .unwrap();
+    let mut latex = LatexRenderer::new(&book);
+    let tex = latex.render_book().unwrap();
+    let map = latex.source_map_content();
+    let mut entries = map.lines().map(|l| l.split('\t').collect::<Vec<_>>());
+    let title = entries.next().unwrap();
+    assert_eq!(title[1], "chapter_03.md:5");
+    let text = entries.nth(1).unwrap();
+    assert_eq!(text[1], "chapter_03.md:9");
+    // The line in the LaTeX file is the one where the block is rendered
+    let line: usize = text[0].parse().unwrap();
+    assert!(tex.lines().nth(line - 1).unwrap().contains("More text"));
+}
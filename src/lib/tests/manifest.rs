@@ -0,0 +1,67 @@
+use manifest::{book_to_manifest, parse_chapter_line, parse_chapter_yaml, ChapterEntry};
+use number::Number;
+use book::Book;
+use error::Source;
+
+use yaml_rust::{Yaml, YamlLoader};
+
+#[test]
+fn book_to_manifest_roundtrip() {
+    let content = "author: Foo
+title: Bar
+
+! intro.md
+# Main content
++ chapter_1.md
+3. chapter_3.md
+-- section.md
+@ Part \"two\"
+@- part.md
+- annex.md";
+    let manifest = book_to_manifest(content).unwrap();
+    let docs = YamlLoader::load_from_str(&manifest).unwrap();
+    assert_eq!(docs[0]["author"].as_str(), Some("Foo"));
+    let chapters: Vec<_> = docs[0]["chapters"]
+        .as_vec()
+        .unwrap()
+        .iter()
+        .map(|c| parse_chapter_yaml(&Source::empty(), c).unwrap())
+        .collect();
+    let expected: Vec<_> = content.lines()
+        .skip(3)
+        .filter_map(|l| parse_chapter_line(&Source::empty(), l).unwrap())
+        .collect();
+    assert_eq!(chapters, expected);
+    assert_eq!(chapters[5], ChapterEntry::Chapter(Number::UnnumberedPart, String::from("part.md")));
+}
+
+#[test]
+fn chapter_yaml() {
+    let source = Source::empty();
+    let parse = |s: &str| -> Yaml { YamlLoader::load_from_str(s).unwrap().pop().unwrap() };
+    assert_eq!(parse_chapter_yaml(&source, &parse("foo.md")).unwrap(),
+               ChapterEntry::Chapter(Number::Default, String::from("foo.md")));
+    assert_eq!(parse_chapter_yaml(&source, &parse("{file: foo.md, number: false}")).unwrap(),
+               ChapterEntry::Chapter(Number::Unnumbered, String::from("foo.md")));
+    assert_eq!(parse_chapter_yaml(&source, &parse("{file: foo.md, part: true, number: 2}")).unwrap(),
+               ChapterEntry::Chapter(Number::SpecifiedPart(2), String::from("foo.md")));
+    assert_eq!(parse_chapter_yaml(&source, &parse("{file: foo.md, level: 2}")).unwrap(),
+               ChapterEntry::Subchapter(2, String::from("foo.md")));
+    assert!(parse_chapter_yaml(&source, &parse("{file: foo.md, hidden: true, number: 2}")).is_err());
+    assert!(parse_chapter_yaml(&source, &parse("{nmuber: 2}")).is_err());
+}
+
+#[test]
+fn read_manifest() {
+    let content = "
+title: Some title
+epub.version: 3
+chapters:
+  - part: First part
+  - part: Second part";
+    let mut book = Book::new();
+    book.read_manifest(content.as_bytes()).unwrap();
+    assert_eq!(book.options.get_str("title").unwrap(), "Some title");
+    assert_eq!(book.options.get_i32("epub.version").unwrap(), 3);
+    assert_eq!(book.chapters.len(), 2);
+}
@@ -0,0 +1,41 @@
+use markdown::MarkdownRenderer;
+use book::Book;
+use number::Number;
+use super::test_eq;
+
+fn md_to_md(md: &str) -> String {
+    let mut book = Book::new();
+    book.add_chapter_from_source(Number::Default, md.as_bytes()).unwrap();
+    MarkdownRenderer::new(&book).render_book().unwrap()
+}
+
+#[test]
+fn markdown_book() {
+    let s = md_to_md("# Title\n\nSome *text* with `code` and a [link](http://example.com \"t\")[^1].\n\n\
+                      1. one\n2. two\n\n> quote\n\n```rust\nfn main() {}\n```\n\n[^1]: A note");
+    test_eq(&s,
+            "# 1. Title\n\n\
+             Some *text* with `code` and a [link](http://example.com \"t\")[^1].\n\n\
+             1. one\n2. two\n\n\
+             > quote\n\n\
+             ```rust\nfn main() {}\n```\n\n\
+             [^1]: A note\n");
+}
+
+#[test]
+fn markdown_escape() {
+    let s = md_to_md("\\*foo\\* and a\\_b\n\n\\# not a header\n\nSome `` `code` ``");
+    test_eq(&s, "\\*foo\\* and a\\_b\n\n\\# not a header\n\nSome `` `code` ``\n");
+}
+
+#[test]
+fn markdown_table() {
+    let s = md_to_md("| a | b |\n|---|---|\n| c | d |");
+    test_eq(&s, "| a | b |\n| --- | --- |\n| c | d |\n");
+}
+
+#[test]
+fn markdown_lang() {
+    let s = md_to_md("Some <span lang=\"fr\">texte</span>.");
+    test_eq(&s, "Some <span lang=\"fr\">texte</span>.\n");
+}
@@ -8,3 +8,20 @@ pub fn test_eq(actual: &str, expected: &str) {
 mod parser;
 mod html;
 mod book;
+mod project;
+mod manifest;
+mod epub;
+mod epub_check;
+mod epub_patch;
+mod font;
+mod contributor;
+mod odt;
+mod docx;
+mod latex_log;
+mod latex;
+mod typst;
+mod lang_tag;
+mod txt;
+mod markdown;
+#[cfg(feature = "pdf")]
+mod pdf;
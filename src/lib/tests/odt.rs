@@ -0,0 +1,46 @@
+use odt::OdtRenderer;
+use book::Book;
+use parser::Parser;
+use misc;
+
+fn md_to_odt(md: &str) -> String {
+    let mut book = Book::new();
+    book.set_options(&[("rendering.num_depth", "0")]);
+    let tokens = Parser::new().parse(md).unwrap();
+    let mut odt = OdtRenderer::new(&book);
+    odt.render_vec(&tokens).unwrap()
+}
+
+#[test]
+fn odt_image_size() {
+    let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x01\x90\x00\x00\x00\xc8\x08\x06\x00\x00\x00";
+    assert_eq!(misc::image_size(png), Some((400, 200)));
+    let gif = b"GIF89a\x20\x00\x10\x00";
+    assert_eq!(misc::image_size(gif), Some((32, 16)));
+    assert_eq!(misc::image_size(b"not an image"), None);
+}
+
+#[test]
+fn odt_footnotes() {
+    let s = md_to_odt("Text[^1].\n\n[^1]: A note");
+    assert!(s.contains("<text:note text:id=\"ftn1\" text:note-class=\"footnote\">"));
+    assert!(s.contains("<text:p text:style-name=\"Footnote\">A note</text:p>"));
+}
+
+#[test]
+fn odt_lists_and_code() {
+    let s = md_to_odt("3. foo\n4. bar\n\n```\n  x\n```");
+    assert!(s.contains("<text:list text:style-name=\"Numbering_20_1\">"));
+    assert!(s.contains("<text:list-item text:start-value=\"3\">"));
+    assert!(s.contains("<text:p text:style-name=\"List_20_Contents\">foo</text:p>"));
+    assert!(s.contains("<text:p text:style-name=\"Preformatted_20_Text\"><text:s text:c=\"2\"/>x</text:p>"));
+}
+
+#[test]
+fn odt_tables() {
+    let s = md_to_odt("| a | b |\n|---|---|\n| c | d |");
+    assert!(s.contains("<table:table-column table:number-columns-repeated=\"2\"/>"));
+    assert!(s.contains("<table:table-header-rows>"));
+    assert!(s.contains("<text:p text:style-name=\"Table_20_Heading\">a</text:p>"));
+    assert!(s.contains("<text:p text:style-name=\"Table_20_Contents\">d</text:p>"));
+}
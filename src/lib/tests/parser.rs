@@ -271,3 +271,25 @@ fn footnote_incorrect() {
     let result = parser.parse(doc);
     assert!(result.is_err());
 }
+
+#[test]
+fn parse_with_lines() {
+    let doc = "# Title\n\nSome text\non two lines[^1].\n\n[^1]: A note\n\n* item\n";
+    let (tokens, lines) = Parser::new().parse_with_lines(doc).unwrap();
+    assert_eq!(tokens.len(), lines.len());
+    assert_eq!(lines[0], 1);
+    assert_eq!(lines[1], 3);
+    assert_eq!(*lines.last().unwrap(), 8);
+}
+
+#[test]
+fn lang_spans() {
+    let doc = "A <span lang=\"de\">Kindergarten *teacher*</span>, \
+               <span class=\"foo\">not a language</span>.";
+    let mut parser = Parser::new();
+    parser.html_as_text(false);
+    let result = format!("{:?}", parser.parse(doc).unwrap());
+    let expected = "[Paragraph([Str(\"A \"), Annotation(Lang(\"de\"), [Str(\"Kindergarten \"), \
+                    Emphasis([Str(\"teacher\")])]), Str(\", not a language.\")])]";
+    test_eq(&result, expected);
+}
@@ -0,0 +1,51 @@
+use pdf::PdfRenderer;
+use book::Book;
+use number::Number;
+
+fn md_to_pdf(md: &str, options: &[(&str, &str)]) -> String {
+    let mut book = Book::new();
+    book.set_options(&[("title", "Test"), ("author", "Someone")]);
+    book.set_options(options);
+    book.add_chapter_from_source(Number::Default, md.as_bytes()).unwrap();
+    let mut res = vec![];
+    PdfRenderer::new(&book).render_pdf(&mut res).unwrap();
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+/// Returns the number of pages, read from the `/Type /Pages` dictionary
+fn page_count(pdf: &str) -> usize {
+    let pages = &pdf[pdf.find("/Type /Pages").unwrap()..];
+    let dict = &pages[..pages.find(">>").unwrap()];
+    dict.split("/Count ").nth(1).unwrap().trim().parse().unwrap()
+}
+
+#[test]
+fn pdf_structure() {
+    let s = md_to_pdf("# Chapter\n\nSome *text* with a footnote[^1].\n\n[^1]: A note",
+                      &[]);
+    assert!(s.starts_with("%PDF-1.4"));
+    assert!(s.ends_with("%%EOF\n"));
+    assert!(s.contains("/Type /Catalog"));
+    assert!(s.contains("/BaseFont /Times-Italic"));
+    // Title page and chapter
+    assert_eq!(page_count(&s), 2);
+    assert!(s.contains("/Type /Outlines"));
+}
+
+#[test]
+fn pdf_toc_and_links() {
+    let s = md_to_pdf("# One\n\n[a link](http://example.com)\n\n# Two\n\nText",
+                      &[("rendering.inline_toc", "true"), ("tex.title", "false")]);
+    // Table of contents and two chapters
+    assert_eq!(page_count(&s), 3);
+    assert!(s.contains("/URI (http://example.com)"));
+    assert!(s.contains("/Subtype /Link"));
+}
+
+#[test]
+fn pdf_page_breaks() {
+    let paragraph = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(20);
+    let md: Vec<String> = (0..30).map(|_| paragraph.clone()).collect();
+    let s = md_to_pdf(&md.join("\n\n"), &[("tex.title", "false")]);
+    assert!(page_count(&s) > 1);
+}
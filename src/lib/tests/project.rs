@@ -0,0 +1,51 @@
+use project::Project;
+use book::Book;
+use error::Source;
+
+#[test]
+fn load_editions() {
+    let content = "
+title: Shared title
+chapters:
+  - \"@ Part one\"
+editions:
+  ebook:
+    epub.version: 3
+    chapters:
+      - \"@ Part two\"
+  web:
+    inherits: ebook
+    title: Web title
+";
+    let project = Project::from_str(content, Source::empty()).unwrap();
+    assert_eq!(project.editions(), vec!["ebook", "web"]);
+
+    let mut book = Book::new();
+    project.load_edition(&mut book, "ebook").unwrap();
+    assert_eq!(book.options.get_str("title").unwrap(), "Shared title");
+    assert_eq!(book.options.get_i32("epub.version").unwrap(), 3);
+    assert_eq!(book.chapters.len(), 2);
+
+    let mut book = Book::new();
+    project.load_edition(&mut book, "web").unwrap();
+    assert_eq!(book.options.get_str("title").unwrap(), "Web title");
+    assert_eq!(book.options.get_i32("epub.version").unwrap(), 3);
+    assert_eq!(book.chapters.len(), 2);
+
+    let mut book = Book::new();
+    assert!(project.load_edition(&mut book, "paperback").is_err());
+}
+
+#[test]
+fn inheritance_loop() {
+    let content = "
+editions:
+  a:
+    inherits: b
+  b:
+    inherits: a
+";
+    let project = Project::from_str(content, Source::empty()).unwrap();
+    let mut book = Book::new();
+    assert!(project.load_edition(&mut book, "a").is_err());
+}
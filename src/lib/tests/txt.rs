@@ -0,0 +1,28 @@
+use txt::TxtRenderer;
+use book::Book;
+use number::Number;
+
+fn md_to_txt(md: &str) -> String {
+    let mut book = Book::new();
+    book.set_options(&[("title", "Test"), ("author", "Someone")]);
+    book.add_chapter_from_source(Number::Default, md.as_bytes()).unwrap();
+    TxtRenderer::new(&book).render_book().unwrap()
+}
+
+#[test]
+fn txt_book() {
+    let s = md_to_txt("# Title\n\nSome *text*[^1].\n\n- a\n- b\n\n## Section\n\n[^1]: A note");
+    assert!(s.starts_with("Test\nSomeone\n\n"));
+    assert!(s.contains("\n1. Title\n========\n\n"));
+    assert!(s.contains("\nSome text[1].\n\n- a\n- b\n\n"));
+    assert!(s.contains("\nSection\n-------\n\n"));
+    assert!(s.ends_with("----\n\n[1] A note\n"));
+}
+
+#[test]
+fn txt_blocks() {
+    let s = md_to_txt("> A quote\n>\n> on two paragraphs\n\n    some code\n\n1. one\n\n    more\n2. two");
+    assert!(s.contains("    A quote\n\n    on two paragraphs\n\n"));
+    assert!(s.contains("    some code\n\n"));
+    assert!(s.contains("1. one\n\n   more\n2. two\n"));
+}
@@ -0,0 +1,49 @@
+use typst::{TypstRenderer, escape_typst};
+use book::Book;
+use parser::Parser;
+use renderer::Renderer;
+
+#[test]
+fn typst_escape() {
+    assert_eq!(escape_typst("#let x = *a* [b]"), "\\#let x \\= \\*a\\* \\[b\\]");
+    assert_eq!(escape_typst("Ça va ?"), "Ça va ?");
+}
+
+#[test]
+fn typst_tokens() {
+    let doc = "Some *emphasis*, **strong** and `#code`.
+
+> Quote
+
+- one
+- two
+
+```rust
+let x = \"a\";
+```
+
+[link](http://example.com)";
+    let tokens = Parser::new().parse(doc).unwrap();
+    let book = Book::new();
+    let typ = TypstRenderer::new(&book).render_vec(&tokens).unwrap();
+    assert!(typ.contains("Some #emph[emphasis], #strong[strong] and #raw(\"#code\")."));
+    assert!(typ.contains("#quote(block: true)[\nQuote\n\n]"));
+    assert!(typ.contains("#list(\n[one],\n[two])"));
+    assert!(typ.contains("#raw(block: true, lang: \"rust\", \"let x = \\\"a\\\";\")"));
+    assert!(typ.contains("#link(\"http://example.com\")[link]"));
+}
+
+#[test]
+fn typst_book() {
+    let mut book = Book::new();
+    book.set_options(&[("lang", "pt-BR"), ("tex.paper.size", "letterpaper"),
+                       ("tex.margin.top", "1in")]);
+    let typ = TypstRenderer::new(&book).render_book().unwrap();
+    assert!(typ.contains("lang: \"pt\""));
+    assert!(typ.contains("region: \"br\""));
+    assert!(typ.contains("paper: \"us-letter\""));
+    assert!(typ.contains("top: 72.00pt"));
+
+    assert!(book.options.set("pdf.engine", "typst").is_ok());
+    assert!(book.options.set("pdf.engine", "context").is_err());
+}
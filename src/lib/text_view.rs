@@ -20,6 +20,9 @@ use std::default::Default;
 
 use token::Token;
 use token::Data;
+use book::Book;
+use number::Number;
+use error::Result;
 
 pub fn traverse_token<F1, F2, R>(token: &Token, f: &F1, add: &F2) -> R
     where F1: Fn(&str) -> R,
@@ -71,6 +74,131 @@ pub fn count_length(tokens: &[Token]) -> usize {
 }
 
 
+/// Numbering of chapters and parts, for renderers that don't rely on
+/// `HtmlRenderer` (e.g. ODT or plain text)
+#[doc(hidden)]
+pub struct TextNumbering<'a> {
+    book: &'a Book,
+    current_numbering: i32,
+    current_hide: bool,
+    current_chapter: i32,
+    current_part: i32,
+    /// Whether the current chapter is a part
+    is_part: bool,
+}
+
+impl<'a> TextNumbering<'a> {
+    /// Creates a new TextNumbering
+    pub fn new(book: &'a Book) -> TextNumbering<'a> {
+        TextNumbering {
+            book: book,
+            current_numbering: book.options.get_i32("rendering.num_depth").unwrap(),
+            current_hide: false,
+            current_chapter: 1,
+            current_part: 1,
+            is_part: false,
+        }
+    }
+
+    /// Sets the numbering for a new chapter
+    pub fn start_chapter(&mut self, n: Number) {
+        let book_numbering = self.book.options.get_i32("rendering.num_depth").unwrap();
+        self.current_hide = false;
+        self.is_part = n.is_part();
+        match n {
+            Number::Unnumbered | Number::UnnumberedPart => self.current_numbering = 0,
+            Number::Default | Number::DefaultPart => self.current_numbering = book_numbering,
+            Number::Specified(n) => {
+                self.current_numbering = book_numbering;
+                self.current_chapter = n;
+            }
+            Number::SpecifiedPart(n) => {
+                self.current_numbering = book_numbering;
+                self.current_part = n;
+            }
+            Number::Hidden => {
+                self.current_numbering = 0;
+                self.current_hide = true;
+            }
+        }
+    }
+
+    /// Returns true if the title of the current chapter must not be displayed
+    pub fn is_hidden(&self) -> bool {
+        self.current_hide
+    }
+
+    /// Returns true if the current chapter is a part
+    pub fn is_part(&self) -> bool {
+        self.is_part
+    }
+
+    /// Returns the number of a header if it is a numbered chapter or part
+    /// title, and increments the current chapter or part number
+    pub fn next_number(&mut self, n: i32) -> Option<i32> {
+        if n != 1 || self.current_numbering < 1 {
+            None
+        } else if self.is_part {
+            self.current_part += 1;
+            Some(self.current_part - 1)
+        } else {
+            self.current_chapter += 1;
+            Some(self.current_chapter - 1)
+        }
+    }
+
+    /// Returns the text of a header with its chapter or part number, or
+    /// `None` if it isn't numbered
+    pub fn numbered_header(&mut self, n: i32, vec: &[Token]) -> Result<Option<String>> {
+        let number = match self.next_number(n) {
+            Some(number) => number,
+            None => return Ok(None),
+        };
+        let title = view_as_text(vec);
+        let header = if self.is_part {
+            self.book.get_part_header(number, title, |s| Ok(s.to_owned()))?
+        } else {
+            self.book.get_chapter_header(number, title, |s| Ok(s.to_owned()))?
+        };
+        Ok(Some(header.text))
+    }
+}
+
+/// Returns true if the token is a block, and not an inline element
+#[doc(hidden)]
+pub fn is_block(token: &Token) -> bool {
+    match *token {
+        Token::Paragraph(_) |
+        Token::Header(..) |
+        Token::BlockQuote(_) |
+        Token::CodeBlock(..) |
+        Token::Rule |
+        Token::List(_) |
+        Token::OrderedList(..) |
+        Token::Table(..) |
+        Token::StandaloneImage(..) => true,
+        _ => false,
+    }
+}
+
+/// Indents the lines of a text, the first one with `first` and the
+/// following non-empty ones with `rest`
+#[doc(hidden)]
+pub fn indent(text: &str, first: &str, rest: &str) -> String {
+    let mut res = String::new();
+    for (i, line) in text.trim_right().lines().enumerate() {
+        if i == 0 {
+            res.push_str(first);
+        } else if !line.is_empty() {
+            res.push_str(rest);
+        }
+        res.push_str(line);
+        res.push('\n');
+    }
+    res
+}
+
+
 /// Insert an annotation at begin and end pos begin+len in the text_view
 #[doc(hidden)]
 pub fn insert_annotation(tokens: &mut Vec<Token>,
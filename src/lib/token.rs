@@ -23,6 +23,8 @@
 pub enum Data {
     GrammarError(String),
     Repetition(String),
+    /// A passage in another language, with its BCP 47 tag
+    Lang(String),
     __NonExhaustive,
 }
 
@@ -0,0 +1,236 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+use token::Token;
+use book::Book;
+use error::{Error, Result};
+use renderer::Renderer;
+use book_renderer::BookRenderer;
+use parser::Parser;
+use text_view::{view_as_text, is_block, indent, TextNumbering};
+
+use std::io;
+use std::mem;
+
+/// Renders a book as plain text, e.g. for audiobook narrators
+///
+/// Paragraphs are separated by blank lines and not wrapped, and footnotes
+/// are displayed at the end of each chapter.
+pub struct TxtRenderer<'a> {
+    book: &'a Book,
+    numbering: TextNumbering<'a>,
+    /// Footnotes of the current chapter, with their numbers
+    footnotes: Vec<(usize, String)>,
+    footnote_number: usize,
+}
+
+impl<'a> TxtRenderer<'a> {
+    /// Creates a new TxtRenderer
+    pub fn new(book: &'a Book) -> TxtRenderer<'a> {
+        TxtRenderer {
+            book: book,
+            numbering: TextNumbering::new(book),
+            footnotes: vec![],
+            footnote_number: 0,
+        }
+    }
+
+    /// Renders the whole book
+    pub fn render_book(&mut self) -> Result<String> {
+        let book = self.book;
+        let mut content = self.render_title()?;
+
+        for chapter in &book.chapters {
+            self.numbering.start_chapter(chapter.number);
+            content.push_str(&self.render_blocks(&chapter.content)?);
+
+            // Footnotes are displayed at the end of the chapter
+            let mut footnotes = mem::replace(&mut self.footnotes, vec![]);
+            if !footnotes.is_empty() {
+                footnotes.sort_by_key(|&(n, _)| n);
+                content.push_str("----\n\n");
+                for (n, note) in footnotes {
+                    content.push_str(&indent(&note, &format!("[{}] ", n), "    "));
+                    content.push('\n');
+                }
+            }
+        }
+        Ok(format!("{}\n", content.trim_right()))
+    }
+
+    /// Renders the title, subtitle and authors of the book
+    fn render_title(&self) -> Result<String> {
+        let book = self.book;
+        let mut res = String::new();
+        let title = Parser::new().parse_inline(book.options.get_str("title").unwrap())?;
+        res.push_str(&view_as_text(&title));
+        res.push('\n');
+        if let Ok(subtitle) = book.options.get_str("subtitle") {
+            let subtitle = Parser::new().parse_inline(subtitle)?;
+            res.push_str(&view_as_text(&subtitle));
+            res.push('\n');
+        }
+        let authors: Vec<String> = book.authors()
+            .into_iter()
+            .map(|author| author.name)
+            .collect();
+        if !authors.is_empty() {
+            res.push_str(&authors.join(", "));
+            res.push('\n');
+        }
+        res.push('\n');
+        Ok(res)
+    }
+
+    /// Renders tokens that can be either blocks or inline elements (e.g. in
+    /// list items or footnotes), separating paragraphs with blank lines
+    fn render_blocks(&mut self, tokens: &[Token]) -> Result<String> {
+        let mut res = String::new();
+        let mut inline = String::new();
+        for token in tokens {
+            if is_block(token) {
+                if !inline.trim().is_empty() {
+                    res.push_str(inline.trim_right());
+                    res.push_str("\n\n");
+                }
+                inline.clear();
+                res.push_str(&self.render_token(token)?);
+            } else {
+                inline.push_str(&self.render_token(token)?);
+            }
+        }
+        if !inline.trim().is_empty() {
+            res.push_str(inline.trim_right());
+            res.push_str("\n\n");
+        }
+        Ok(res)
+    }
+
+    fn render_header(&mut self, n: i32, vec: &[Token]) -> Result<String> {
+        let text = match self.numbering.numbered_header(n, vec)? {
+            Some(text) => text,
+            None => self.render_vec(vec)?,
+        };
+        let underline = match n {
+            1 => "=",
+            2 => "-",
+            _ => return Ok(format!("{}\n\n", text)),
+        };
+        Ok(format!("\n{}\n{}\n\n", text, underline.repeat(text.chars().count())))
+    }
+
+    /// Renders a (possibly ordered) list, indenting the content of items
+    fn render_list(&mut self, start: Option<usize>, items: &[Token]) -> Result<String> {
+        let mut res = String::new();
+        let mut number = start.unwrap_or(1);
+        for item in items {
+            let marker = match start {
+                Some(_) => format!("{}. ", number),
+                None => String::from("- "),
+            };
+            number += 1;
+            let content = self.render_token(item)?;
+            res.push_str(&indent(&content, &marker, &" ".repeat(marker.len())));
+        }
+        res.push('\n');
+        Ok(res)
+    }
+
+    /// Renders a row of a table, separating cells with `|`
+    fn render_row(&mut self, cells: &[Token]) -> Result<String> {
+        let mut res = vec![];
+        for cell in cells {
+            res.push(self.render_token(cell)?.trim().replace('\n', " "));
+        }
+        Ok(format!("{}\n", res.join(" | ")))
+    }
+}
+
+impl<'a> Renderer for TxtRenderer<'a> {
+    fn render_token(&mut self, token: &Token) -> Result<String> {
+        match *token {
+            Token::Str(ref text) => Ok(self.book.clean(text.as_str(), false).into_owned()),
+            Token::Paragraph(ref vec) => Ok(format!("{}\n\n", self.render_vec(vec)?)),
+            Token::Header(n, ref vec) => {
+                if n == 1 && self.numbering.is_hidden() {
+                    return Ok(String::new());
+                }
+                self.render_header(n, vec)
+            }
+            Token::Emphasis(ref vec) |
+            Token::Strong(ref vec) |
+            Token::Superscript(ref vec) |
+            Token::Subscript(ref vec) |
+            Token::Link(_, _, ref vec) |
+            Token::TableCell(ref vec) |
+            Token::Annotation(_, ref vec) => self.render_vec(vec),
+            Token::Code(ref vec) => Ok(view_as_text(vec)),
+            Token::BlockQuote(ref vec) => {
+                let content = self.render_blocks(vec)?;
+                Ok(format!("{}\n", indent(&content, "    ", "    ")))
+            }
+            Token::CodeBlock(_, ref vec) => {
+                let code = view_as_text(vec);
+                Ok(format!("{}\n", indent(&code, "    ", "    ")))
+            }
+            Token::SoftBreak => Ok(String::from(" ")),
+            Token::HardBreak => Ok(String::from("\n")),
+            Token::Rule => Ok(String::from("* * *\n\n")),
+            Token::List(ref vec) => self.render_list(None, vec),
+            Token::OrderedList(start, ref vec) => self.render_list(Some(start), vec),
+            Token::Item(ref vec) => self.render_blocks(vec),
+            Token::Image(_, _, ref alt) => Ok(view_as_text(alt)),
+            Token::StandaloneImage(_, _, ref alt) => {
+                let alt = view_as_text(alt);
+                if alt.is_empty() {
+                    Ok(String::new())
+                } else {
+                    Ok(format!("[{}]\n\n", alt))
+                }
+            }
+            Token::Table(_, ref vec) => Ok(format!("{}\n", self.render_vec(vec)?)),
+            Token::TableHead(ref vec) |
+            Token::TableRow(ref vec) => self.render_row(vec),
+            Token::Footnote(ref vec) => {
+                self.footnote_number += 1;
+                let n = self.footnote_number;
+                let note = self.render_blocks(vec)?;
+                self.footnotes.push((n, note));
+                Ok(format!("[{}]", n))
+            }
+            Token::__NonExhaustive => unreachable!(),
+        }
+    }
+}
+
+pub struct Txt {}
+
+impl BookRenderer for Txt {
+    fn auto_path(&self, book_name: &str) -> Result<String> {
+        Ok(format!("{}.txt", book_name))
+    }
+
+    fn render(&self, book: &Book, to: &mut io::Write) -> Result<()> {
+        let result = TxtRenderer::new(book).render_book()?;
+        to.write_all(result.as_bytes())
+            .map_err(|e| {
+                Error::render(&book.source,
+                              lformat!("problem when writing text file: {error}", error = e))
+            })?;
+        Ok(())
+    }
+}
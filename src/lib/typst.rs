@@ -0,0 +1,422 @@
+// Copyright (C) 2017 Élisabeth HENRY.
+//
+// This file is part of Crowbook.
+//
+// Crowbook is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Crowbook is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
+
+// Typst renderer, an alternative to LaTeX for PDF output.
+
+use book::{Book, compile_str};
+use error::{Error, Result, Source};
+use token::Token;
+use token::Data;
+use zipper::Zipper;
+use resource_handler::ResourceHandler;
+use renderer::Renderer;
+use parser::Parser;
+use book_renderer::BookRenderer;
+use lang_tag::LangTag;
+use text_view::{view_as_text, TextNumbering};
+use latex::is_book_class;
+use misc;
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::fmt::Write;
+use std::process::Command;
+
+/// Typst renderer, an alternative to LaTeX for PDF output
+pub struct TypstRenderer<'a> {
+    book: &'a Book,
+    handler: ResourceHandler,
+    source: Source,
+    numbering: TextNumbering<'a>,
+    /// Index of the current chapter, used to label its first header
+    chapter_index: usize,
+}
+
+impl<'a> TypstRenderer<'a> {
+    /// Creates a new TypstRenderer
+    pub fn new(book: &'a Book) -> TypstRenderer<'a> {
+        let mut handler = ResourceHandler::new();
+        handler.set_images_mapping(true);
+        TypstRenderer {
+            book: book,
+            handler: handler,
+            source: Source::empty(),
+            numbering: TextNumbering::new(book),
+            chapter_index: 0,
+        }
+    }
+
+    /// Render pdf to a file, using `typst.command`
+    pub fn render_pdf(&mut self, to: &mut io::Write) -> Result<String> {
+        let content = self.render_book()?;
+        debug!("{}", lformat!("Attempting to run Typst on generated file"));
+        let mut zipper = Zipper::new(&self.book.options.get_path("crowbook.temp_dir")
+                                     .unwrap())?;
+        zipper.write("result.typ", content.as_bytes(), false)?;
+
+        // write image files
+        for (source, dest) in self.handler.images_mapping() {
+            let mut f = fs::canonicalize(source)
+                .and_then(|f| File::open(f))
+                .map_err(|_| {
+                    Error::file_not_found(&self.source, lformat!("image"), source.to_owned())
+                    })?;
+            let mut content = vec![];
+            f.read_to_end(&mut content)
+                .map_err(|e| {
+                    Error::render(&self.source,
+                                  lformat!("error while reading image file: {error}", error = e))
+                })?;
+            zipper.write(dest, &content, false)?;
+        }
+
+        let command_name = self.book.options.get_str("typst.command").unwrap();
+        let mut command = Command::new(command_name);
+        command.arg("compile")
+            .arg("result.typ")
+            .arg("result.pdf");
+        zipper.run_command(command, command_name, "result.pdf", to)
+    }
+
+    /// Render Typst markup in a string
+    pub fn render_book(&mut self) -> Result<String> {
+        let mut content = String::new();
+        let book = self.book;
+
+        if book.options.get_bool("rendering.inline_toc").unwrap() {
+            content.push_str("#outline()\n\n");
+        }
+
+        for (i, chapter) in book.chapters.iter().enumerate() {
+            self.handler.add_link(chapter.filename.as_ref(), format!("chapter-{}", i));
+        }
+        for (i, chapter) in book.chapters.iter().enumerate() {
+            self.source = Source::new(chapter.filename.as_str());
+            self.chapter_index = i;
+            self.numbering.start_chapter(chapter.number);
+            let v = &chapter.content;
+            if v.is_empty() || !v[0].is_header() {
+                // Still allow links to this chapter
+                write!(content, "#pagebreak(weak: true)\n#metadata(none) <chapter-{}>\n\n", i)?;
+            }
+            content.push_str(&self.render_vec(v)?);
+        }
+        self.source = Source::empty();
+
+        let template = compile_str(book.get_template("typst.template")?.as_ref(),
+                                   &book.source,
+                                   "typst.template")?;
+        let mut data = book.get_metadata(|s| self.render_vec(&Parser::new().parse_inline(s)?))?
+            .insert_str("content", content)
+            .insert_str("document_title", typst_string(book.options.get_str("title").unwrap()))
+            .insert_bool("tex_title", book.options.get_bool("tex.title").unwrap());
+
+        // Authors, as markup and as a plain array for the document's metadata
+        let authors = book.authors();
+        let mut names = vec![];
+        let mut rendered = vec![];
+        for author in &authors {
+            names.push(typst_string(&author.name));
+            rendered.push(self.render_vec(&Parser::new().parse_inline(&author.name)?)?);
+        }
+        data = data.insert_str("document_author", format!("({}{})",
+                                                            names.join(", "),
+                                                            if names.len() == 1 { "," } else { "" }));
+        if !rendered.is_empty() {
+            data = data.insert_str("author", rendered.join(", "));
+        }
+        if let Ok(add) = book.options.get_str("typst.template.add") {
+            data = data.insert_str("additional_code", add);
+        }
+
+        // Language and region of the text
+        let lang = book.options.get_str("lang").unwrap();
+        if let Some(tag) = LangTag::parse(lang) {
+            data = data.insert_str("typst_lang", tag.language.clone());
+            if let Some(region) = tag.region {
+                if region.len() == 2 {
+                    data = data.insert_str("typst_region", region.to_lowercase());
+                }
+            }
+        }
+
+        // Page layout, shared with the LaTeX renderer
+        let paper = book.options.get_str("tex.paper.size").unwrap();
+        data = data.insert_str("paper", typst_paper(paper));
+        if let Ok(size) = book.options.get_i32("tex.font.size") {
+            data = data.insert_str("font_size", format!("{}pt", size));
+        }
+        let is_book = is_book_class(book.options.get_str("tex.class").unwrap());
+        let margin = |keys: &[&str], default: &str| -> String {
+            let value = keys.iter()
+                .filter_map(|key| book.options.get_str(key).ok())
+                .next()
+                .unwrap_or(default);
+            match misc::tex_length(value) {
+                // TeX points are slightly smaller than Typst's
+                Some(pt) => format!("{:.2}pt", pt * 72.0 / 72.27),
+                None => {
+                    warn!("{}", lformat!("Typst: invalid length '{length}', using {default}",
+                                         length = value,
+                                         default = default));
+                    String::from(default)
+                }
+            }
+        };
+        data = data
+            .insert_bool("twoside", is_book)
+            .insert_str("margin_left", margin(&["tex.margin.inside", "tex.margin.left"],
+                                              if is_book { "1.5cm" } else { "2cm" }))
+            .insert_str("margin_right", margin(&["tex.margin.outside", "tex.margin.right"], "2cm"))
+            .insert_str("margin_top", margin(&["tex.margin.top"], "2cm"))
+            .insert_str("margin_bottom", margin(&["tex.margin.bottom"], "1.5cm"));
+
+        let data = data.build();
+        let mut res: Vec<u8> = vec![];
+        template.render_data(&mut res, &data)?;
+        match String::from_utf8(res) {
+            Err(_) => panic!(lformat!("generated Typst was not valid utf-8")),
+            Ok(res) => Ok(res),
+        }
+    }
+
+    /// Renders a header, numbered according to the chapter or part template
+    /// if needed
+    fn render_header(&mut self, n: i32, vec: &[Token]) -> Result<String> {
+        let title = match self.numbering.numbered_header(n, vec)? {
+            Some(text) => escape_typst(&text),
+            None => self.render_vec(vec)?,
+        };
+        let mut res = String::new();
+        for _ in 0..n {
+            res.push('=');
+        }
+        write!(res, " {}", title)?;
+        if n == 1 {
+            write!(res, " <chapter-{}>", self.chapter_index)?;
+        }
+        res.push_str("\n\n");
+        Ok(res)
+    }
+
+    /// Renders a list of items, as arguments of `#list` or `#enum`
+    fn render_items(&mut self, vec: &[Token]) -> Result<String> {
+        let mut items = vec![];
+        for token in vec {
+            items.push(self.render_token(token)?);
+        }
+        Ok(items.join(",\n"))
+    }
+
+    /// Renders the path of an image, or an empty string (with a warning) if
+    /// it isn't local
+    fn render_image(&mut self, url: &str) -> Result<Option<String>> {
+        if ResourceHandler::is_local(url) {
+            let img = self.handler.map_image(&self.source, url)?;
+            Ok(Some(typst_string(&img)))
+        } else {
+            debug!("{}", lformat!("Typst ({source}): image '{url}' doesn't seem to be \
+                                   local; ignoring it.",
+                                  source = self.source,
+                                  url = url));
+            Ok(None)
+        }
+    }
+}
+
+impl<'a> Renderer for TypstRenderer<'a> {
+    fn render_token(&mut self, token: &Token) -> Result<String> {
+        match *token {
+            Token::Str(ref text) => Ok(escape_typst(&self.book.clean(text.as_str(), false))),
+            Token::Paragraph(ref vec) => Ok(format!("{}\n\n", self.render_vec(vec)?)),
+            Token::Header(n, ref vec) => {
+                if n == 1 && self.numbering.is_hidden() {
+                    return Ok(format!("#pagebreak(weak: true)\n#metadata(none) <chapter-{}>\n\n",
+                                      self.chapter_index));
+                }
+                self.render_header(n, vec)
+            }
+            Token::Emphasis(ref vec) => Ok(format!("#emph[{}]", self.render_vec(vec)?)),
+            Token::Strong(ref vec) => Ok(format!("#strong[{}]", self.render_vec(vec)?)),
+            Token::Code(ref vec) => Ok(format!("#raw({})", typst_string(&view_as_text(vec)))),
+            Token::Superscript(ref vec) => Ok(format!("#super[{}]", self.render_vec(vec)?)),
+            Token::Subscript(ref vec) => Ok(format!("#sub[{}]", self.render_vec(vec)?)),
+            Token::BlockQuote(ref vec) => {
+                Ok(format!("#quote(block: true)[\n{}]\n\n", self.render_vec(vec)?))
+            }
+            Token::CodeBlock(ref language, ref vec) => {
+                let mut code = view_as_text(vec);
+                // Remove trailing newline
+                if code.ends_with('\n') {
+                    code.pop();
+                }
+                if language.is_empty() {
+                    Ok(format!("#raw(block: true, {})\n\n", typst_string(&code)))
+                } else {
+                    Ok(format!("#raw(block: true, lang: {}, {})\n\n",
+                               typst_string(language),
+                               typst_string(&code)))
+                }
+            }
+            Token::Rule => Ok(String::from("#align(center, line(length: 30%))\n\n")),
+            Token::SoftBreak => Ok(String::from(" ")),
+            Token::HardBreak => Ok(String::from("\\\n")),
+            Token::List(ref vec) => Ok(format!("#list(\n{})\n\n", self.render_items(vec)?)),
+            Token::OrderedList(n, ref vec) => {
+                Ok(format!("#enum(start: {},\n{})\n\n", n, self.render_items(vec)?))
+            }
+            Token::Item(ref vec) => Ok(format!("[{}]", self.render_vec(vec)?)),
+            Token::Link(ref url, _, ref vec) => {
+                let content = self.render_vec(vec)?;
+                if self.handler.contains_link(url) {
+                    let label = self.handler.get_link(url).to_owned();
+                    if is_typst_label(&label) {
+                        return Ok(format!("#link(<{}>)[{}]", label, content));
+                    }
+                }
+                Ok(format!("#link({})[{}]", typst_string(url), content))
+            }
+            Token::StandaloneImage(ref url, _, _) => {
+                match self.render_image(url)? {
+                    Some(path) => Ok(format!("#align(center, image({}, fit: \"contain\"))\n\n",
+                                             path)),
+                    None => Ok(String::new()),
+                }
+            }
+            Token::Image(ref url, _, _) => {
+                match self.render_image(url)? {
+                    Some(path) => Ok(format!("#box(image({}))", path)),
+                    None => Ok(String::new()),
+                }
+            }
+            Token::Footnote(ref vec) => {
+                let content = self.render_vec(vec)?;
+                Ok(format!("#footnote[{}]", content.trim()))
+            }
+            Token::Table(n, ref vec) => {
+                Ok(format!("#table(columns: {},\n{})\n\n", n, self.render_vec(vec)?))
+            }
+            Token::TableHead(ref vec) => {
+                let mut res = String::new();
+                for cell in vec {
+                    write!(res, "[#strong{}], ", self.render_token(cell)?)?;
+                }
+                res.push('\n');
+                Ok(res)
+            }
+            Token::TableRow(ref vec) => {
+                let mut res = String::new();
+                for cell in vec {
+                    write!(res, "{}, ", self.render_token(cell)?)?;
+                }
+                res.push('\n');
+                Ok(res)
+            }
+            Token::TableCell(ref vec) => Ok(format!("[{}]", self.render_vec(vec)?)),
+            Token::Annotation(Data::Lang(ref lang), ref vec) => {
+                let content = self.render_vec(vec)?;
+                match LangTag::parse(lang) {
+                    Some(tag) => {
+                        let region = match tag.region {
+                            Some(ref region) if region.len() == 2 => {
+                                format!(", region: \"{}\"", region.to_lowercase())
+                            }
+                            _ => String::new(),
+                        };
+                        Ok(format!("#text(lang: \"{}\"{})[{}]", tag.language, region, content))
+                    }
+                    None => {
+                        warn!("{}", lformat!("Typst: invalid language tag '{lang}'", lang = lang));
+                        Ok(content)
+                    }
+                }
+            }
+            Token::Annotation(_, ref vec) => self.render_vec(vec),
+
+            Token::__NonExhaustive => unreachable!(),
+        }
+    }
+}
+
+pub struct Typst;
+
+impl BookRenderer for Typst {
+    fn auto_path(&self, book_name: &str) -> Result<String> {
+        Ok(format!("{}.typ", book_name))
+    }
+
+    fn render(&self, book: &Book, to: &mut io::Write) -> Result<()> {
+        let mut typst = TypstRenderer::new(book);
+        let result = typst.render_book()?;
+        to.write_all(result.as_bytes())
+            .map_err(|e| {
+                Error::render(&book.source,
+                              lformat!("problem when writing Typst: {error}", error = e))
+            })?;
+        Ok(())
+    }
+}
+
+/// Escapes characters that have a meaning in Typst markup
+pub fn escape_typst(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '*' | '_' | '`' | '#' | '$' | '@' | '<' | '>' | '[' | ']' | '~' | '=' |
+            '-' | '+' | '/' | '"' | '\'' => {
+                result.push('\\');
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Returns a Typst string literal
+fn typst_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Returns true if a string can be used as a Typst label
+fn is_typst_label(label: &str) -> bool {
+    !label.is_empty() &&
+    label.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == ':' || c == '.')
+}
+
+/// Converts a LaTeX paper size (e.g. `a5paper`) to a Typst one
+fn typst_paper(paper: &str) -> String {
+    let paper = paper.trim_right_matches("paper");
+    match paper {
+        "letter" | "legal" | "executive" => format!("us-{}", paper),
+        _ => paper.to_owned(),
+    }
+}
@@ -15,11 +15,13 @@
 // You should have received ba copy of the GNU Lesser General Public License
 // along with Crowbook.  If not, see <http://www.gnu.org/licenses/>.
 
-use error::{Error, Result};
+use error::{Error, Result, Source};
+use latex;
+use latex_log;
 
 use std::path::{Path, PathBuf};
 use std::io;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::process::Command;
 use std::fs::{self, File, DirBuilder};
 use uuid;
@@ -55,6 +57,11 @@ impl Zipper {
         })
     }
 
+    /// Returns the path of the temporary directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     /// writes a content to a temporary file
     pub fn write<P:AsRef<Path>>(&mut self, path: P, content: &[u8], add_args: bool) -> Result<()> {
         let path = path.as_ref();
@@ -157,32 +164,206 @@ This is forbidden because we are supposed \
         }
     }
 
-    /// zip all files in zipper's tmp dir to a given file name and write to odt file
+    /// zip all files in zipper's tmp dir to an ODT file, with the `mimetype`
+    /// file first and uncompressed, as required by the OpenDocument specification
     pub fn generate_odt(&mut self, command_name: &str, odt_file: &mut Write) -> Result<String> {
+        self.generate_archive(command_name, "result.odt", odt_file)
+    }
+
+    /// zip all files in zipper's tmp dir to an EPUB file, with the `mimetype`
+    /// file first and uncompressed, as required by the EPUB specification
+    pub fn generate_epub(&mut self, command_name: &str, epub_file: &mut Write) -> Result<String> {
+        self.generate_archive(command_name, "result.epub", epub_file)
+    }
+
+    /// zip all files in zipper's tmp dir to a DOCX file
+    pub fn generate_docx(&mut self, command_name: &str, docx_file: &mut Write) -> Result<String> {
         let mut command = Command::new(command_name);
-        command.arg("-r");
-        command.arg("result.odt");
-        command.arg(".");
-        self.run_command(command, command_name, "result.odt", odt_file)
+        command.args(&["-X", "-r", "-9", "result.docx", ".", "-x", "result.docx"]);
+        let res = self.run_command(command, command_name, "result.docx", docx_file);
+        let _ = fs::remove_file(self.path.join("result.docx"));
+        res
     }
 
+    /// zip all files in zipper's tmp dir to `archive`, starting with an
+    /// uncompressed `mimetype` file, and write it to `out`
+    fn generate_archive(&mut self, command_name: &str, archive: &str, out: &mut Write) -> Result<String> {
+        let output = Command::new(command_name)
+            .current_dir(&self.path)
+            .args(&["-X", "-0", archive, "mimetype"])
+            .output()
+            .map_err(|e| {
+                Error::zipper(lformat!("failed to run command '{name}': {error}",
+                                       name = command_name,
+                                       error = e))
+            })?;
+        if !output.status.success() {
+            return Err(Error::zipper(lformat!("{command} didn't return succesfully",
+                                              command = command_name)));
+        }
+        let mut command = Command::new(command_name);
+        command.args(&["-X", "-r", "-9", archive, ".", "-x", "mimetype", archive]);
+        let res = self.run_command(command, command_name, archive, out);
+        // Remove the archive so the directory only contains its files
+        let _ = fs::remove_file(self.path.join(archive));
+        res
+    }
 
     /// generate a pdf file into given file name
-    pub fn generate_pdf(&mut self,
-                        command_name: &str,
-                        tex_file: &str,
-                        pdf_file: &mut Write)
-                        -> Result<String> {
-        // first pass
-        let _ = Command::new(command_name)
-            .current_dir(&self.path)
-            .arg(tex_file)
-            .output();
+    ///
+    /// LaTeX is run until the log stops asking for a rerun and the
+    /// auxiliary files (cross-references, table of contents) are stable,
+    /// at most `max_runs` times. `makeindex` and `biber` are run after the
+    /// first pass if the document needs them.
+    ///
+    /// If LaTeX reports an error, `map_line` is used to convert its line in
+    /// `tex_file` to the source that produced it.
+    pub fn generate_pdf<F>(&mut self,
+                           command_name: &str,
+                           tex_file: &str,
+                           max_runs: u32,
+                           map_line: F,
+                           pdf_file: &mut Write)
+                           -> Result<String>
+        where F: Fn(u32) -> Source
+    {
+        let base = tex_file.trim_right_matches(".tex").to_owned();
+        let mut state = self.auxiliary_state(&base);
+        let mut output;
+        let mut runs = 0;
+        loop {
+            runs += 1;
+            let (success, stdout) = self.run_latex(command_name, tex_file)?;
+            let log = self.read_file(&format!("{}.log", base)).unwrap_or_else(|| stdout.clone());
+            output = stdout;
+            let log = latex_log::parse(&log);
+            if let Some(error) = log.errors.first() {
+                let source = match error.line {
+                    Some(line) => map_line(line),
+                    None => Source::empty(),
+                };
+                let msg = match error.context {
+                    Some(ref context) => {
+                        lformat!("{command} error: {error} (at '{context}')",
+                                 command = command_name,
+                                 error = error.message,
+                                 context = context)
+                    }
+                    None => {
+                        lformat!("{command} error: {error}",
+                                 command = command_name,
+                                 error = error.message)
+                    }
+                };
+                return Err(Error::render(source, msg));
+            }
+            if !success {
+                // Show the end of the output, which usually explains the problem
+                let lines: Vec<&str> = output.lines().collect();
+                let tail = lines[lines.len().saturating_sub(10)..].join("\n");
+                return Err(Error::zipper(lformat!("{command} didn't return succesfully:\n{output}",
+                                                  command = command_name,
+                                                  output = tail)));
+            }
+
+            let mut rerun = log.rerun;
+            if runs == 1 {
+                if self.path.join(format!("{}.idx", base)).exists() {
+                    self.run_tool("makeindex", &format!("{}.idx", base))?;
+                    rerun = true;
+                }
+                if log.biber || self.path.join(format!("{}.bcf", base)).exists() {
+                    self.run_tool("biber", &base)?;
+                    rerun = true;
+                }
+            }
+            let new_state = self.auxiliary_state(&base);
+            if new_state != state {
+                rerun = true;
+            }
+            state = new_state;
+
+            if !rerun {
+                break;
+            }
+            if runs >= max_runs {
+                warn!("{}",
+                      lformat!("{command} still asks to be rerun after {n} runs, \
+                                cross-references might be wrong",
+                               command = command_name,
+                               n = runs));
+                break;
+            }
+            debug!("{}", lformat!("Running {command} again", command = command_name));
+        }
 
-        // second pass
+        let pdf = format!("{}.pdf", base);
+        let mut file = File::open(self.path.join(&pdf))
+            .map_err(|_| {
+                Error::zipper(lformat!("could not open result of command '{command}'",
+                                       command = command_name))
+            })?;
+        io::copy(&mut file, pdf_file)
+            .map_err(|_| Error::zipper(lformat!("error copying file '{file}'",
+                                                file = pdf)))?;
+        Ok(output)
+    }
+
+    /// Runs LaTeX once, returning whether it succeeded and its output
+    fn run_latex(&mut self, command_name: &str, tex_file: &str) -> Result<(bool, String)> {
         let mut command = Command::new(command_name);
-        command.arg(tex_file);
-        self.run_command(command, command_name, "result.pdf", pdf_file)
+        command.current_dir(&self.path);
+        if latex::tex_engine(command_name).ends_with("latex") {
+            // Don't wait for user input on errors
+            command.args(&["-interaction=nonstopmode", "-halt-on-error"]);
+        }
+        let output = command.arg(tex_file)
+            .output()
+            .map_err(|e| {
+                Error::zipper(lformat!("failed to run command '{name}': {error}",
+                                       name = command_name,
+                                       error = e))
+            })?;
+        Ok((output.status.success(), String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    /// Runs an auxiliary tool (e.g. `makeindex`) on a file
+    fn run_tool(&mut self, command_name: &str, file: &str) -> Result<()> {
+        debug!("{}", lformat!("Running {command}", command = command_name));
+        let output = Command::new(command_name)
+            .current_dir(&self.path)
+            .arg(file)
+            .output()
+            .map_err(|e| {
+                Error::zipper(lformat!("failed to run command '{name}': {error}",
+                                       name = command_name,
+                                       error = e))
+            })?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(Error::zipper(lformat!("{command} didn't return succesfully:\n{output}",
+                                       command = command_name,
+                                       output = String::from_utf8_lossy(&output.stdout))))
+        }
+    }
+
+    /// Returns the content of a file in the temporary directory, if it exists
+    fn read_file(&self, file: &str) -> Option<String> {
+        let mut content = vec![];
+        File::open(self.path.join(file))
+            .and_then(|mut f| f.read_to_end(&mut content))
+            .ok()
+            .map(|_| String::from_utf8_lossy(&content).into_owned())
+    }
+
+    /// Returns the content of the auxiliary files that must be stable
+    /// between two LaTeX runs
+    fn auxiliary_state(&self, base: &str) -> Vec<Option<String>> {
+        ["aux", "toc", "lof", "lot", "out"]
+            .iter()
+            .map(|extension| self.read_file(&format!("{}.{}", base, extension)))
+            .collect()
     }
 }
 
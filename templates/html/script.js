@@ -14,28 +14,32 @@ function off(name) {
 }
 
 var display_menu = false;
+// In right-to-left documents, the navigation menu is on the right side
+var rtl = document.documentElement.dir == "rtl";
+var side = rtl ? "right" : "left";
+var contentMargin = rtl ? "marginRight" : "marginLeft";
 function toggle() {
     if (display_menu == true) {
         display_menu = false;
-        document.getElementById("nav").style.left = "-21%";
-        document.getElementById("content").style.marginLeft = "0%";
-        document.getElementById("menu").style.left = "1em";
+        document.getElementById("nav").style[side] = "-21%";
+        document.getElementById("content").style[contentMargin] = "0%";
+        document.getElementById("menu").style[side] = "1em";
 /*        if(document.getElementById("top")) {
-            document.getElementById("top").style.left = "0";
+            document.getElementById("top").style[side] = "0";
         }
         if(document.getElementById("footer")) {
-            document.getElementById("footer").style.marginLeft = "0%";
+            document.getElementById("footer").style[contentMargin] = "0%";
         }*/
     } else {
         display_menu = true;
-        document.getElementById("nav").style.left = "0";
-        document.getElementById("content").style.marginLeft = "20%";
-        document.getElementById("menu").style.left = "20%";
+        document.getElementById("nav").style[side] = "0";
+        document.getElementById("content").style[contentMargin] = "20%";
+        document.getElementById("menu").style[side] = "20%";
 /*        if(document.getElementById("top")) {
-            document.getElementById("top").style.left = "20%";
+            document.getElementById("top").style[side] = "20%";
         }
         if(document.getElementById("footer")) {
-            document.getElementById("footer").style.marginLeft = "20%";
+            document.getElementById("footer").style[contentMargin] = "20%";
         }*/
     }
 }
@@ -0,0 +1,62 @@
+// Typst document generated by Crowbook
+#set document(title: {{{document_title}}}, author: {{{document_author}}})
+#set page(
+  paper: "{{{paper}}}",
+{{#twoside}}
+  margin: (inside: {{{margin_left}}}, outside: {{{margin_right}}}, top: {{{margin_top}}}, bottom: {{{margin_bottom}}}),
+{{/twoside}}
+{{^twoside}}
+  margin: (left: {{{margin_left}}}, right: {{{margin_right}}}, top: {{{margin_top}}}, bottom: {{{margin_bottom}}}),
+{{/twoside}}
+  numbering: "1",
+)
+#set text(
+{{#typst_lang}}
+  lang: "{{{typst_lang}}}",
+{{/typst_lang}}
+{{#typst_region}}
+  region: "{{{typst_region}}}",
+{{/typst_region}}
+{{#font_size}}
+  size: {{{font_size}}},
+{{/font_size}}
+  dir: {{{dir}}},
+)
+#set par(justify: true)
+
+// Chapters start on a new page
+#show heading.where(level: 1): it => {
+  pagebreak(weak: true)
+  v(2em)
+  it
+  v(1em)
+}
+
+#show raw.where(block: true): block.with(
+  width: 100%,
+  inset: 0.5em,
+  stroke: 0.5pt + luma(150),
+)
+
+#show quote.where(block: true): block.with(inset: (left: 1.5em, right: 1.5em))
+
+{{{additional_code}}}
+
+{{#tex_title}}
+#page(numbering: none)[
+  #align(center + horizon)[
+    #text(size: 2em, weight: "bold")[{{{title}}}]
+{{#has_subtitle}}
+
+    #text(size: 1.5em)[{{{subtitle}}}]
+{{/has_subtitle}}
+{{#has_author}}
+
+    #v(2em)
+    #text(size: 1.3em)[{{{author}}}]
+{{/has_author}}
+  ]
+]
+{{/tex_title}}
+
+{{{content}}}